@@ -12,6 +12,9 @@ pub struct Rule {
     pub declarations: Vec<Declaration>,
 }
 
+/// A complex selector: `simple` holds the compound selectors from left to
+/// right and `combinators[i]` (one of `' '`, `'>'`, `'+'`, `'~'`) sits
+/// between `simple[i]` and `simple[i + 1]`.
 #[derive(PartialEq, Eq)]
 pub struct Selector {
     pub simple: Vec<SimpleSelector>,
@@ -31,14 +34,14 @@ pub struct Declaration {
     pub value: Value,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 pub enum Value {
     Color(Color),
     Length(f32, Unit),
     Other(String),
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum Unit {
     Em,
     Ex,
@@ -145,9 +148,12 @@ impl fmt::Debug for Selector {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut result = String::new();
 
-        for sel in &self.simple {
-            if result.len() > 0 {
-                result.push_str(", ");
+        for (i, sel) in self.simple.iter().enumerate() {
+            if i > 0 {
+                match self.combinators.get(i - 1) {
+                    Some(' ') | None => result.push(' '),
+                    Some(c) => result.push_str(&format!(" {} ", c)),
+                }
             }
             result.push_str(&format!("{:?}", sel));
         }
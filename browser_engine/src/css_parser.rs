@@ -8,7 +8,7 @@ pub struct CssParser<'a> {
 }
 
 impl<'a> CssParser<'a> {
-    fn new(full_css: &str) -> CssParser {
+    pub fn new(full_css: &str) -> CssParser {
         CssParser {
             chars: full_css.chars().peekable(),
         }
@@ -25,6 +25,12 @@ impl<'a> CssParser<'a> {
         stylesheet
     }
 
+    // a bare comma separated selector list, as taken by query_selector;
+    // invalid selectors are dropped like they are from a rule
+    pub fn parse_selector_list(&mut self) -> Vec<Selector> {
+        self.parse_selectors()
+    }
+
     fn parse_selectors(&mut self) -> Vec<Selector> {
         let mut selectors = Vec::new();
         while self.chars.peek().map_or(false, |c| *c != '{') {
@@ -42,21 +48,58 @@ impl<'a> CssParser<'a> {
     }
 
     fn parse_selector(&mut self) -> Selector {
-        let mut sselector = SimpleSelector::default();
         let mut selector = Selector::default();
 
         self.consume_while(char::is_whitespace);
 
-        sselector.tag_name = match self.chars.peek() {
-            Some(&c) if is_valid_start_ident(c) => Some(self.parse_identifier()),
-            _ => None,
+        loop {
+            match self.parse_simple_selector() {
+                Some(sselector) => selector.simple.push(sselector),
+                None => {
+                    // a dangling combinator (`div > {`) or an unsupported
+                    // selector (`a:hover`) invalidates the whole selector
+                    self.consume_while(|c| c != ',' && c != '{');
+                    return Selector::default();
+                }
+            }
+
+            let whitespace = self.consume_while(char::is_whitespace);
+            match self.chars.peek() {
+                Some(&c) if c == '>' || c == '+' || c == '~' => {
+                    self.chars.next();
+                    self.consume_while(char::is_whitespace);
+                    selector.combinators.push(c);
+                }
+                Some(&c) if c == ',' || c == '{' => break,
+                Some(_) if !whitespace.is_empty() => selector.combinators.push(' '),
+                _ => break,
+            }
+        }
+
+        selector
+    }
+
+    fn parse_simple_selector(&mut self) -> Option<SimpleSelector> {
+        let mut sselector = SimpleSelector::default();
+        let mut consumed = false;
+
+        match self.chars.peek() {
+            Some(&c) if is_valid_start_ident(c) => {
+                sselector.tag_name = Some(self.parse_identifier());
+                consumed = true;
+            }
+            Some('*') => {
+                self.chars.next();
+                consumed = true;
+            }
+            _ => {}
         };
 
         let mut multiple_ids = false;
         while self
             .chars
             .peek()
-            .map_or(false, |c| *c != ',' && *c != '{' && !(*c).is_whitespace())
+            .is_some_and(|c| !matches!(c, ',' | '{' | '>' | '+' | '~') && !c.is_whitespace())
         {
             match self.chars.peek() {
                 Some(&c) if c == '#' => {
@@ -77,16 +120,16 @@ impl<'a> CssParser<'a> {
                         sselector.classes.push(class_name);
                     }
                 }
-                _ => {
-                    self.consume_while(|c| c != ',' && c != '{');
-                }
+                _ => return None,
             }
+            consumed = true;
         }
 
-        if sselector != SimpleSelector::default() {
-            selector.simple.push(sselector)
+        if consumed {
+            Some(sselector)
+        } else {
+            None
         }
-        selector
     }
 
     fn parse_identifier(&mut self) -> String {
@@ -122,38 +165,27 @@ impl<'a> CssParser<'a> {
 
             let value = self
                 .consume_while(|x| x != ';' && x != '\n' && x != '}')
+                .trim()
                 .to_lowercase();
 
-            let value_enum = match property.as_ref() {
-                "background-color" | "border-color" | "color" => {
-                    Value::Color(translate_color(&value))
-                }
-                "margin-right"
-                | "margin-bottom"
-                | "margin-left"
-                | "margin-top"
-                | "padding-right"
-                | "padding-bottom"
-                | "padding-left"
-                | "padding-top"
-                | "border-right-width"
-                | "border-bottom-width"
-                | "border-left-width"
-                | "border-top-width"
-                | "height"
-                | "width" => translate_length(&value),
-                _ => Value::Other(value),
+            let longhands = match expand_shorthand(&property, &value) {
+                Some(longhands) => longhands,
+                None => vec![(property, value)],
             };
 
-            let declaration = Declaration::new(property, value_enum);
-
             if self.chars.peek().map_or(false, |c| *c == ';') {
-                declarations.push(declaration);
+                for (property, value) in longhands {
+                    let value = translate_value(&property, value);
+                    declarations.push(Declaration::new(property, value));
+                }
                 self.chars.next();
             } else {
                 self.consume_while(char::is_whitespace);
                 if self.chars.peek().map_or(false, |c| *c == '}') {
-                    declarations.push(declaration);
+                    for (property, value) in longhands {
+                        let value = translate_value(&property, value);
+                        declarations.push(Declaration::new(property, value));
+                    }
                 }
             }
             self.consume_while(char::is_whitespace);
@@ -176,13 +208,297 @@ impl<'a> CssParser<'a> {
     }
 }
 
+fn translate_value(property: &str, value: String) -> Value {
+    match property {
+        _ if is_css_wide_keyword(&value) => Value::Other(value),
+        // currentcolor is resolved against `color` while styling
+        _ if value == "auto" || value == "currentcolor" => Value::Other(value),
+        "background-color"
+        | "border-color"
+        | "border-top-color"
+        | "border-right-color"
+        | "border-bottom-color"
+        | "border-left-color"
+        | "color" => match translate_color(&value) {
+            Some(color) => Value::Color(color),
+            None => Value::Other(value),
+        },
+        "margin-right"
+        | "margin-bottom"
+        | "margin-left"
+        | "margin-top"
+        | "padding-right"
+        | "padding-bottom"
+        | "padding-left"
+        | "padding-top"
+        | "border-right-width"
+        | "border-bottom-width"
+        | "border-left-width"
+        | "border-top-width"
+        | "height"
+        | "width"
+        | "flex-basis"
+        | "row-gap"
+        | "column-gap"
+        | "top"
+        | "right"
+        | "bottom"
+        | "left" => translate_length(&value),
+        "font-size" if value.starts_with(|c: char| c.is_ascii_digit() || c == '.') => {
+            translate_length(&value)
+        }
+        // a bare number is a multiplier of the font size and stays as is
+        "line-height" if value.parse::<f32>().is_err() && value != "normal" => {
+            translate_length(&value)
+        }
+        _ => Value::Other(value),
+    }
+}
+
+const SIDES: [&str; 4] = ["top", "right", "bottom", "left"];
+
+const BORDER_STYLES: &[&str] = &[
+    "none", "hidden", "dotted", "dashed", "solid", "double", "groove", "ridge", "inset", "outset",
+];
+
+// Splits a shorthand into its longhands, or returns None if `property` is
+// not a shorthand or `value` does not fit its grammar.
+fn expand_shorthand(property: &str, value: &str) -> Option<Vec<(String, String)>> {
+    if is_css_wide_keyword(value) {
+        let longhands: Vec<String> = match property {
+            "margin" | "padding" => SIDES
+                .iter()
+                .map(|s| format!("{}-{}", property, s))
+                .collect(),
+            "border-width" | "border-style" | "border-color" => {
+                let suffix = &property["border-".len()..];
+                SIDES
+                    .iter()
+                    .map(|s| format!("border-{}-{}", s, suffix))
+                    .collect()
+            }
+            _ => return None,
+        };
+        return Some(
+            longhands
+                .into_iter()
+                .map(|l| (l, value.to_string()))
+                .collect(),
+        );
+    }
+
+    let components = split_components(value);
+    match property {
+        "margin" | "padding" => {
+            let values = box_values(&components)?;
+            Some(
+                SIDES
+                    .iter()
+                    .zip(values)
+                    .map(|(side, v)| (format!("{}-{}", property, side), v))
+                    .collect(),
+            )
+        }
+        "border-width" | "border-style" | "border-color" => {
+            let suffix = &property["border-".len()..];
+            let values = box_values(&components)?;
+            Some(
+                SIDES
+                    .iter()
+                    .zip(values)
+                    .map(|(side, v)| (format!("border-{}-{}", side, suffix), v))
+                    .collect(),
+            )
+        }
+        "border" => {
+            let (width, style, color) = border_values(&components)?;
+            let mut longhands = Vec::new();
+            for side in SIDES {
+                longhands.push((format!("border-{}-width", side), width.clone()));
+                longhands.push((format!("border-{}-style", side), style.clone()));
+                longhands.push((format!("border-{}-color", side), color.clone()));
+            }
+            Some(longhands)
+        }
+        "border-top" | "border-right" | "border-bottom" | "border-left" => {
+            let (width, style, color) = border_values(&components)?;
+            Some(vec![
+                (format!("{}-width", property), width),
+                (format!("{}-style", property), style),
+                (format!("{}-color", property), color),
+            ])
+        }
+        "gap" => match components.len() {
+            1 => Some(vec![
+                ("row-gap".to_string(), components[0].clone()),
+                ("column-gap".to_string(), components[0].clone()),
+            ]),
+            2 => Some(vec![
+                ("row-gap".to_string(), components[0].clone()),
+                ("column-gap".to_string(), components[1].clone()),
+            ]),
+            _ => None,
+        },
+        "flex" => {
+            let (grow, shrink, basis) = flex_values(&components)?;
+            Some(vec![
+                ("flex-grow".to_string(), grow),
+                ("flex-shrink".to_string(), shrink),
+                ("flex-basis".to_string(), basis),
+            ])
+        }
+        "background" => {
+            // only the color layer is used by the painter; images, positions
+            // and repeats are dropped
+            let color = components
+                .iter()
+                .find(|c| is_color(c))
+                .cloned()
+                .unwrap_or_else(|| "transparent".to_string());
+            Some(vec![("background-color".to_string(), color)])
+        }
+        _ => None,
+    }
+}
+
+// the 1/2/3/4 value rule, in top, right, bottom, left order
+fn box_values(components: &[String]) -> Option<[String; 4]> {
+    let c = components;
+    match c.len() {
+        1 => Some([c[0].clone(), c[0].clone(), c[0].clone(), c[0].clone()]),
+        2 => Some([c[0].clone(), c[1].clone(), c[0].clone(), c[1].clone()]),
+        3 => Some([c[0].clone(), c[1].clone(), c[2].clone(), c[1].clone()]),
+        4 => Some([c[0].clone(), c[1].clone(), c[2].clone(), c[3].clone()]),
+        _ => None,
+    }
+}
+
+// `<width> || <style> || <color>` in any order; omitted parts fall back to
+// their initial values, and without a style the border computes to zero width
+fn border_values(components: &[String]) -> Option<(String, String, String)> {
+    let mut width = None;
+    let mut style = None;
+    let mut color = None;
+    for component in components {
+        let c = component.as_str();
+        if width.is_none()
+            && (c.starts_with(|ch: char| ch.is_ascii_digit() || ch == '.')
+                || matches!(c, "thin" | "medium" | "thick"))
+        {
+            width = Some(match c {
+                "thin" => "1px".to_string(),
+                "medium" => "3px".to_string(),
+                "thick" => "5px".to_string(),
+                _ => c.to_string(),
+            });
+        } else if style.is_none() && BORDER_STYLES.contains(&c) {
+            style = Some(c.to_string());
+        } else if color.is_none() && is_color(c) {
+            color = Some(c.to_string());
+        } else {
+            return None;
+        }
+    }
+
+    let style = style.unwrap_or_else(|| "none".to_string());
+    let width = match style.as_str() {
+        "none" | "hidden" => "0px".to_string(),
+        _ => width.unwrap_or_else(|| "3px".to_string()),
+    };
+    Some((
+        width,
+        style,
+        color.unwrap_or_else(|| "currentcolor".to_string()),
+    ))
+}
+
+// `none`, `auto`, or `<grow> <shrink>? || <basis>`; a lone number means a
+// zero basis
+fn flex_values(components: &[String]) -> Option<(String, String, String)> {
+    let keyword =
+        |grow: &str, shrink: &str| Some((grow.to_string(), shrink.to_string(), "auto".to_string()));
+    match components {
+        [c] if c == "none" => return keyword("0", "0"),
+        [c] if c == "auto" => return keyword("1", "1"),
+        _ => {}
+    }
+
+    let mut numbers = Vec::new();
+    let mut basis = None;
+    let mut previous_was_number = false;
+    for component in components {
+        let is_number = component.parse::<f32>().is_ok();
+        if is_number && (numbers.is_empty() || (numbers.len() == 1 && previous_was_number)) {
+            numbers.push(component.clone());
+        } else if basis.is_none() {
+            basis = Some(component.clone());
+        } else {
+            return None;
+        }
+        previous_was_number = is_number;
+    }
+    if numbers.is_empty() && basis.is_none() {
+        return None;
+    }
+
+    let grow = numbers.first().cloned().unwrap_or_else(|| "1".to_string());
+    let shrink = numbers.get(1).cloned().unwrap_or_else(|| "1".to_string());
+    Some((grow, shrink, basis.unwrap_or_else(|| "0%".to_string())))
+}
+
+fn is_color(value: &str) -> bool {
+    value == "currentcolor" || translate_color(value).is_some()
+}
+
+// white space separated components, keeping `rgb(0, 0, 0)` in one piece
+fn split_components(value: &str) -> Vec<String> {
+    let mut components = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        if c.is_whitespace() && depth == 0 {
+            if !current.is_empty() {
+                components.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        components.push(current);
+    }
+    components
+}
+
+pub fn strip_comments(css: &str) -> String {
+    let mut result = String::new();
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        result.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+    result
+}
+
 fn translate_length(value: &str) -> Value {
     let mut num_str = String::new();
     let mut unit = String::new();
     let mut parsing_num = true;
 
-    for c in value.chars() {
-        if c.is_numeric() && parsing_num {
+    for (i, c) in value.chars().enumerate() {
+        let part_of_num = c.is_ascii_digit()
+            || (c == '.' && !num_str.contains('.'))
+            || ((c == '-' || c == '+') && i == 0);
+        if part_of_num && parsing_num {
             num_str.push(c);
         } else {
             unit.push(c);
@@ -213,541 +529,646 @@ fn translate_length(value: &str) -> Value {
     }
 }
 
-fn translate_color(color: &str) -> Color {
-    if color.starts_with("#") {
-        if color.len() == 7 {
-            let red = match u8::from_str_radix(&color[1..3], 16) {
-                Ok(n) => n as f32 / 255.0,
-                Err(_) => 0.0,
-            };
-            let green = match u8::from_str_radix(&color[3..5], 16) {
-                Ok(n) => n as f32 / 255.0,
-                Err(_) => 0.0,
-            };
-            let blue = match u8::from_str_radix(&color[5..7], 16) {
-                Ok(n) => n as f32 / 255.0,
-                Err(_) => 0.0,
-            };
-            return Color::new(red, green, blue, 1.0);
-        } else if color.len() == 4 {
-            let red = match u8::from_str_radix(&color[1..2], 16) {
-                Ok(n) => n as f32 / 15.0,
-                Err(_) => 0.0,
-            };
-            let green = match u8::from_str_radix(&color[2..3], 16) {
-                Ok(n) => n as f32 / 15.0,
-                Err(_) => 0.0,
-            };
-            let blue = match u8::from_str_radix(&color[3..4], 16) {
-                Ok(n) => n as f32 / 15.0,
-                Err(_) => 0.0,
-            };
-            return Color::new(red, green, blue, 1.0);
-        } else {
-            return Color::default();
-        }
-    } else if color.starts_with("rgb") {
-        return Color::default();
-    } else if color.starts_with("hsl") {
-        return Color::default();
+fn translate_color(color: &str) -> Option<Color> {
+    if let Some(hex) = color.strip_prefix('#') {
+        return translate_hex_color(hex);
+    }
+    if let Some(args) = function_args(color, &["rgb", "rgba"]) {
+        return translate_rgb(&args);
+    }
+    if let Some(args) = function_args(color, &["hsl", "hsla"]) {
+        return translate_hsl(&args);
+    }
+    named_color(color)
+}
+
+// #rgb, #rgba, #rrggbb and #rrggbbaa
+fn translate_hex_color(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize, len: usize| -> f32 {
+        let n = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).unwrap_or(0);
+        n as f32 / if len == 1 { 15.0 } else { 255.0 }
+    };
+    match hex.len() {
+        3 => Some(Color::new(digit(0, 1), digit(1, 1), digit(2, 1), 1.0)),
+        4 => Some(Color::new(
+            digit(0, 1),
+            digit(1, 1),
+            digit(2, 1),
+            digit(3, 1),
+        )),
+        6 => Some(Color::new(digit(0, 2), digit(1, 2), digit(2, 2), 1.0)),
+        8 => Some(Color::new(
+            digit(0, 2),
+            digit(1, 2),
+            digit(2, 2),
+            digit(3, 2),
+        )),
+        _ => None,
+    }
+}
+
+// Arguments of `name(...)` for any of `names`, accepting both the legacy
+// comma syntax `rgba(1, 2, 3, 0.5)` and the space syntax `rgb(1 2 3 / 50%)`.
+// The alpha, if any, is always the fourth argument.
+fn function_args(value: &str, names: &[&str]) -> Option<Vec<String>> {
+    let open = value.find('(')?;
+    if !names.contains(&value[..open].trim_end()) || !value.ends_with(')') {
+        return None;
+    }
+    let inner = &value[open + 1..value.len() - 1];
+
+    let args: Vec<String> = if inner.contains(',') {
+        inner.split(',').map(|a| a.trim().to_string()).collect()
     } else {
-        return match color {
-            "black" => Color::new(0.0, 0.0, 0.0, 1.0),
-            "silver" => Color::new(
-                0.7529411764705882,
-                0.7529411764705882,
-                0.7529411764705882,
-                1.0,
-            ),
-            "gray" | "grey" => Color::new(
-                0.5019607843137255,
-                0.5019607843137255,
-                0.5019607843137255,
-                1.0,
-            ),
-            "white" => Color::new(1.0, 1.0, 1.0, 1.0),
-            "maroon" => Color::new(0.5019607843137255, 0.0, 0.0, 1.0),
-            "red" => Color::new(1.0, 0.0, 0.0, 1.0),
-            "purple" => Color::new(0.5019607843137255, 0.0, 0.5019607843137255, 1.0),
-            "fuchsia" => Color::new(1.0, 0.0, 1.0, 1.0),
-            "green" => Color::new(0.0, 0.5019607843137255, 0.0, 1.0),
-            "lime" => Color::new(0.0, 1.0, 0.0, 1.0),
-            "olive" => Color::new(0.5019607843137255, 0.5019607843137255, 0.0, 1.0),
-            "yellow" => Color::new(1.0, 1.0, 0.0, 1.0),
-            "navy" => Color::new(0.0, 0.0, 0.5019607843137255, 1.0),
-            "blue" => Color::new(0.0, 0.0, 1.0, 1.0),
-            "teal" => Color::new(0.0, 0.5019607843137255, 0.5019607843137255, 1.0),
-            "aqua" => Color::new(0.0, 1.0, 1.0, 1.0),
-            "orange" => Color::new(1.0, 0.6470588235294118, 0.0, 1.0),
-            "aliceblue" => Color::new(0.9411764705882353, 0.9725490196078431, 1.0, 1.0),
-            "antiquewhite" => Color::new(
-                0.9803921568627451,
-                0.9215686274509803,
-                0.8431372549019608,
-                1.0,
-            ),
-            "aquamarine" => Color::new(0.4980392156862745, 1.0, 0.8313725490196079, 1.0),
-            "azure" => Color::new(0.9411764705882353, 1.0, 1.0, 1.0),
-            "beige" => Color::new(
-                0.9607843137254902,
-                0.9607843137254902,
-                0.8627450980392157,
-                1.0,
-            ),
-            "bisque" => Color::new(1.0, 0.8941176470588236, 0.7686274509803922, 1.0),
-            "blanchedalmond" => Color::new(1.0, 0.9215686274509803, 0.803921568627451, 1.0),
-            "blueviolet" => Color::new(
-                0.5411764705882353,
-                0.16862745098039217,
-                0.8862745098039215,
-                1.0,
-            ),
-            "brown" => Color::new(
-                0.6470588235294118,
-                0.16470588235294117,
-                0.16470588235294117,
-                1.0,
-            ),
-            "burlywood" => Color::new(
-                0.8705882352941177,
-                0.7215686274509804,
-                0.5294117647058824,
-                1.0,
-            ),
-            "cadetblue" => Color::new(
-                0.37254901960784315,
-                0.6196078431372549,
-                0.6274509803921569,
-                1.0,
-            ),
-            "chartreuse" => Color::new(0.4980392156862745, 1.0, 0.0, 1.0),
-            "chocolate" => Color::new(
-                0.8235294117647058,
-                0.4117647058823529,
-                0.11764705882352941,
-                1.0,
-            ),
-            "coral" => Color::new(1.0, 0.4980392156862745, 0.3137254901960784, 1.0),
-            "cornflowerblue" => Color::new(
-                0.39215686274509803,
-                0.5843137254901961,
-                0.9294117647058824,
-                1.0,
-            ),
-            "cornsilk" => Color::new(1.0, 0.9725490196078431, 0.8627450980392157, 1.0),
-            "crimson" => Color::new(
-                0.8627450980392157,
-                0.0784313725490196,
-                0.23529411764705882,
-                1.0,
-            ),
-            "darkblue" => Color::new(0.0, 0.0, 0.5450980392156862, 1.0),
-            "darkcyan" => Color::new(0.0, 0.5450980392156862, 0.5450980392156862, 1.0),
-            "darkgoldenrod" => Color::new(
-                0.7215686274509804,
-                0.5254901960784314,
-                0.043137254901960784,
-                1.0,
-            ),
-            "darkgray" | "darkgrey" => Color::new(
-                0.6627450980392157,
-                0.6627450980392157,
-                0.6627450980392157,
-                1.0,
-            ),
-            "darkgreen" => Color::new(0.0, 0.39215686274509803, 0.0, 1.0),
-            "darkkhaki" => Color::new(
-                0.7411764705882353,
-                0.7176470588235294,
-                0.4196078431372549,
-                1.0,
-            ),
-            "darkmagenta" => Color::new(0.5450980392156862, 0.0, 0.5450980392156862, 1.0),
-            "darkolivegreen" => Color::new(
-                0.3333333333333333,
-                0.4196078431372549,
-                0.1843137254901961,
-                1.0,
-            ),
-            "darkorange" => Color::new(1.0, 0.5490196078431373, 0.0, 1.0),
-            "darkorchid" => Color::new(0.6, 0.19607843137254902, 0.8, 1.0),
-            "darkred" => Color::new(0.5450980392156862, 0.0, 0.0, 1.0),
-            "darksalmon" => Color::new(
-                0.9137254901960784,
-                0.5882352941176471,
-                0.47843137254901963,
-                1.0,
-            ),
-            "darkseagreen" => Color::new(
-                0.5607843137254902,
-                0.7372549019607844,
-                0.5607843137254902,
-                1.0,
-            ),
-            "darkslateblue" => Color::new(
-                0.2823529411764706,
-                0.23921568627450981,
-                0.5450980392156862,
-                1.0,
-            ),
-            "darkslategray" | "darkslategrey" => Color::new(
-                0.1843137254901961,
-                0.30980392156862746,
-                0.30980392156862746,
-                1.0,
-            ),
-            "darkturquoise" => Color::new(0.0, 0.807843137254902, 0.8196078431372549, 1.0),
-            "darkviolet" => Color::new(0.5803921568627451, 0.0, 0.8274509803921568, 1.0),
-            "deeppink" => Color::new(1.0, 0.0784313725490196, 0.5764705882352941, 1.0),
-            "deepskyblue" => Color::new(0.0, 0.7490196078431373, 1.0, 1.0),
-            "dimgray" | "dimgrey" => Color::new(
-                0.4117647058823529,
-                0.4117647058823529,
-                0.4117647058823529,
-                1.0,
-            ),
-            "dodgerblue" => Color::new(0.11764705882352941, 0.5647058823529412, 1.0, 1.0),
-            "firebrick" => Color::new(
-                0.6980392156862745,
-                0.13333333333333333,
-                0.13333333333333333,
-                1.0,
-            ),
-            "floralwhite" => Color::new(1.0, 0.9803921568627451, 0.9411764705882353, 1.0),
-            "forestgreen" => Color::new(
-                0.13333333333333333,
-                0.5450980392156862,
-                0.13333333333333333,
-                1.0,
-            ),
-            "gainsboro" => Color::new(
-                0.8627450980392157,
-                0.8627450980392157,
-                0.8627450980392157,
-                1.0,
-            ),
-            "ghostwhite" => Color::new(0.9725490196078431, 0.9725490196078431, 1.0, 1.0),
-            "gold" => Color::new(1.0, 0.8431372549019608, 0.0, 1.0),
-            "goldenrod" => Color::new(
-                0.8549019607843137,
-                0.6470588235294118,
-                0.12549019607843137,
-                1.0,
-            ),
-            "greenyellow" => Color::new(0.6784313725490196, 1.0, 0.1843137254901961, 1.0),
-            "honeydew" => Color::new(0.9411764705882353, 1.0, 0.9411764705882353, 1.0),
-            "hotpink" => Color::new(1.0, 0.4117647058823529, 0.7058823529411765, 1.0),
-            "indianred" => Color::new(
-                0.803921568627451,
-                0.3607843137254902,
-                0.3607843137254902,
-                1.0,
-            ),
-            "indigo" => Color::new(0.29411764705882354, 0.0, 0.5098039215686274, 1.0),
-            "ivory" => Color::new(1.0, 1.0, 0.9411764705882353, 1.0),
-            "khaki" => Color::new(
-                0.9411764705882353,
-                0.9019607843137255,
-                0.5490196078431373,
-                1.0,
-            ),
-            "lavender" => Color::new(
-                0.9019607843137255,
-                0.9019607843137255,
-                0.9803921568627451,
-                1.0,
-            ),
-            "lavenderblush" => Color::new(1.0, 0.9411764705882353, 0.9607843137254902, 1.0),
-            "lawngreen" => Color::new(0.48627450980392156, 0.9882352941176471, 0.0, 1.0),
-            "lemonchiffon" => Color::new(1.0, 0.9803921568627451, 0.803921568627451, 1.0),
-            "lightblue" => Color::new(
-                0.6784313725490196,
-                0.8470588235294118,
-                0.9019607843137255,
-                1.0,
-            ),
-            "lightcoral" => Color::new(
-                0.9411764705882353,
-                0.5019607843137255,
-                0.5019607843137255,
-                1.0,
-            ),
-            "lightcyan" => Color::new(0.8784313725490196, 1.0, 1.0, 1.0),
-            "lightgoldenrodyellow" => Color::new(
-                0.9803921568627451,
-                0.9803921568627451,
-                0.8235294117647058,
-                1.0,
-            ),
-            "lightgray" | "lightgrey" => Color::new(
-                0.8274509803921568,
-                0.8274509803921568,
-                0.8274509803921568,
-                1.0,
-            ),
-            "lightgreen" => Color::new(
-                0.5647058823529412,
-                0.9333333333333333,
-                0.5647058823529412,
-                1.0,
-            ),
-            "lightpink" => Color::new(1.0, 0.7137254901960784, 0.7568627450980392, 1.0),
-            "lightsalmon" => Color::new(1.0, 0.6274509803921569, 0.47843137254901963, 1.0),
-            "lightseagreen" => Color::new(
-                0.12549019607843137,
-                0.6980392156862745,
-                0.6666666666666666,
-                1.0,
-            ),
-            "lightskyblue" => Color::new(
-                0.5294117647058824,
-                0.807843137254902,
-                0.9803921568627451,
-                1.0,
-            ),
-            "lightslategray" | "lightslategrey" => {
-                Color::new(0.4666666666666667, 0.5333333333333333, 0.6, 1.0)
-            }
-            "lightsteelblue" => Color::new(
-                0.6901960784313725,
-                0.7686274509803922,
-                0.8705882352941177,
-                1.0,
-            ),
-            "lightyellow" => Color::new(1.0, 1.0, 0.8784313725490196, 1.0),
-            "limegreen" => Color::new(
-                0.19607843137254902,
-                0.803921568627451,
-                0.19607843137254902,
-                1.0,
-            ),
-            "linen" => Color::new(
-                0.9803921568627451,
-                0.9411764705882353,
-                0.9019607843137255,
-                1.0,
-            ),
-            "mediumaquamarine" => Color::new(0.4, 0.803921568627451, 0.6666666666666666, 1.0),
-            "mediumblue" => Color::new(0.0, 0.0, 0.803921568627451, 1.0),
-            "mediumorchid" => Color::new(
-                0.7294117647058823,
-                0.3333333333333333,
-                0.8274509803921568,
-                1.0,
-            ),
-            "mediumpurple" => Color::new(
-                0.5764705882352941,
-                0.4392156862745098,
-                0.8588235294117647,
-                1.0,
-            ),
-            "mediumseagreen" => Color::new(
-                0.23529411764705882,
-                0.7019607843137254,
-                0.44313725490196076,
-                1.0,
-            ),
-            "mediumslateblue" => Color::new(
-                0.4823529411764706,
-                0.40784313725490196,
-                0.9333333333333333,
-                1.0,
-            ),
-            "mediumspringgreen" => Color::new(0.0, 0.9803921568627451, 0.6039215686274509, 1.0),
-            "mediumturquoise" => Color::new(0.2823529411764706, 0.8196078431372549, 0.8, 1.0),
-            "mediumvioletred" => Color::new(
-                0.7803921568627451,
-                0.08235294117647059,
-                0.5215686274509804,
-                1.0,
-            ),
-            "midnightblue" => Color::new(
-                0.09803921568627451,
-                0.09803921568627451,
-                0.4392156862745098,
-                1.0,
-            ),
-            "mintcream" => Color::new(0.9607843137254902, 1.0, 0.9803921568627451, 1.0),
-            "mistyrose" => Color::new(1.0, 0.8941176470588236, 0.8823529411764706, 1.0),
-            "moccasin" => Color::new(1.0, 0.8941176470588236, 0.7098039215686275, 1.0),
-            "navajowhite" => Color::new(1.0, 0.8705882352941177, 0.6784313725490196, 1.0),
-            "oldlace" => Color::new(
-                0.9921568627450981,
-                0.9607843137254902,
-                0.9019607843137255,
-                1.0,
-            ),
-            "olivedrab" => Color::new(
-                0.4196078431372549,
-                0.5568627450980392,
-                0.13725490196078433,
-                1.0,
-            ),
-            "orangered" => Color::new(1.0, 0.27058823529411763, 0.0, 1.0),
-            "orchid" => Color::new(
-                0.8549019607843137,
-                0.4392156862745098,
-                0.8392156862745098,
-                1.0,
-            ),
-            "palegoldenrod" => Color::new(
-                0.9333333333333333,
-                0.9098039215686274,
-                0.6666666666666666,
-                1.0,
-            ),
-            "palegreen" => Color::new(0.596078431372549, 0.984313725490196, 0.596078431372549, 1.0),
-            "paleturquoise" => Color::new(
-                0.6862745098039216,
-                0.9333333333333333,
-                0.9333333333333333,
-                1.0,
-            ),
-            "palevioletred" => Color::new(
-                0.8588235294117647,
-                0.4392156862745098,
-                0.5764705882352941,
-                1.0,
-            ),
-            "papayawhip" => Color::new(1.0, 0.9372549019607843, 0.8352941176470589, 1.0),
-            "peachpuff" => Color::new(1.0, 0.8549019607843137, 0.7254901960784313, 1.0),
-            "peru" => Color::new(
-                0.803921568627451,
-                0.5215686274509804,
-                0.24705882352941178,
-                1.0,
-            ),
-            "pink" => Color::new(1.0, 0.7529411764705882, 0.796078431372549, 1.0),
-            "plum" => Color::new(
-                0.8666666666666667,
-                0.6274509803921569,
-                0.8666666666666667,
-                1.0,
-            ),
-            "powderblue" => Color::new(
-                0.6901960784313725,
-                0.8784313725490196,
-                0.9019607843137255,
-                1.0,
-            ),
-            "rosybrown" => Color::new(
-                0.7372549019607844,
-                0.5607843137254902,
-                0.5607843137254902,
-                1.0,
-            ),
-            "royalblue" => Color::new(
-                0.2549019607843137,
-                0.4117647058823529,
-                0.8823529411764706,
-                1.0,
-            ),
-            "saddlebrown" => Color::new(
-                0.5450980392156862,
-                0.27058823529411763,
-                0.07450980392156863,
-                1.0,
-            ),
-            "salmon" => Color::new(
-                0.9803921568627451,
-                0.5019607843137255,
-                0.4470588235294118,
-                1.0,
-            ),
-            "sandybrown" => Color::new(
-                0.9568627450980393,
-                0.6431372549019608,
-                0.3764705882352941,
-                1.0,
-            ),
-            "seagreen" => Color::new(
-                0.1803921568627451,
-                0.5450980392156862,
-                0.3411764705882353,
-                1.0,
-            ),
-            "seashell" => Color::new(1.0, 0.9607843137254902, 0.9333333333333333, 1.0),
-            "sienna" => Color::new(
-                0.6274509803921569,
-                0.3215686274509804,
-                0.17647058823529413,
-                1.0,
-            ),
-            "skyblue" => Color::new(
-                0.5294117647058824,
-                0.807843137254902,
-                0.9215686274509803,
-                1.0,
-            ),
-            "slateblue" => Color::new(
-                0.41568627450980394,
-                0.35294117647058826,
-                0.803921568627451,
-                1.0,
-            ),
-            "slategray" | "slategrey" => Color::new(
-                0.4392156862745098,
-                0.5019607843137255,
-                0.5647058823529412,
-                1.0,
-            ),
-            "snow" => Color::new(1.0, 0.9803921568627451, 0.9803921568627451, 1.0),
-            "springgreen" => Color::new(0.0, 1.0, 0.4980392156862745, 1.0),
-            "steelblue" => Color::new(
-                0.27450980392156865,
-                0.5098039215686274,
-                0.7058823529411765,
-                1.0,
-            ),
-            "tan" => Color::new(
-                0.8235294117647058,
-                0.7058823529411765,
-                0.5490196078431373,
-                1.0,
-            ),
-            "thistle" => Color::new(
-                0.8470588235294118,
-                0.7490196078431373,
-                0.8470588235294118,
-                1.0,
-            ),
-            "tomato" => Color::new(1.0, 0.38823529411764707, 0.2784313725490196, 1.0),
-            "turquoise" => Color::new(
-                0.25098039215686274,
-                0.8784313725490196,
-                0.8156862745098039,
-                1.0,
-            ),
-            "violet" => Color::new(
-                0.9333333333333333,
-                0.5098039215686274,
-                0.9333333333333333,
-                1.0,
-            ),
-            "wheat" => Color::new(
-                0.9607843137254902,
-                0.8705882352941177,
-                0.7019607843137254,
-                1.0,
-            ),
-            "whitesmoke" => Color::new(
-                0.9607843137254902,
-                0.9607843137254902,
-                0.9607843137254902,
-                1.0,
-            ),
-            "yellowgreen" => Color::new(
-                0.6039215686274509,
-                0.803921568627451,
-                0.19607843137254902,
-                1.0,
-            ),
-            "rebeccapurple" => Color::new(0.4, 0.2, 0.6, 1.0),
-            _ => Color::new(0.0, 0.0, 0.0, 1.0),
+        let (channels, alpha) = match inner.split_once('/') {
+            Some((channels, alpha)) => (channels, Some(alpha.trim())),
+            None => (inner, None),
         };
+        let mut args: Vec<String> = channels.split_whitespace().map(String::from).collect();
+        if let Some(alpha) = alpha {
+            if args.len() != 3 {
+                return None;
+            }
+            args.push(alpha.to_string());
+        }
+        args
+    };
+
+    match args.len() {
+        3 | 4 if args.iter().all(|a| !a.is_empty()) => Some(args),
+        _ => None,
     }
 }
 
+fn translate_rgb(args: &[String]) -> Option<Color> {
+    let channel = |arg: &str| -> Option<f32> {
+        let v = match arg.strip_suffix('%') {
+            Some(pct) => pct.parse::<f32>().ok()? / 100.0,
+            None => arg.parse::<f32>().ok()? / 255.0,
+        };
+        Some(v.clamp(0.0, 1.0))
+    };
+    Some(Color::new(
+        channel(&args[0])?,
+        channel(&args[1])?,
+        channel(&args[2])?,
+        alpha(args.get(3))?,
+    ))
+}
+
+fn translate_hsl(args: &[String]) -> Option<Color> {
+    let hue = translate_hue(&args[0])?;
+    // percentages are required by CSS Color 3, Color 4 allows bare numbers
+    let percentage = |arg: &str| -> Option<f32> {
+        let v = arg.strip_suffix('%').unwrap_or(arg).parse::<f32>().ok()?;
+        Some((v / 100.0).clamp(0.0, 1.0))
+    };
+    let saturation = percentage(&args[1])?;
+    let lightness = percentage(&args[2])?;
+
+    // CSS Color 4 §7.1 hsl-to-rgb
+    let f = |n: f32| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    Some(Color::new(f(0.0), f(8.0), f(4.0), alpha(args.get(3))?))
+}
+
+// hue in degrees, normalised to [0, 360)
+fn translate_hue(arg: &str) -> Option<f32> {
+    let split = arg
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(arg.len());
+    let n = arg[..split].parse::<f32>().ok()?;
+    let degrees = match &arg[split..] {
+        "" | "deg" => n,
+        "rad" => n.to_degrees(),
+        "grad" => n * 0.9,
+        "turn" => n * 360.0,
+        _ => return None,
+    };
+    Some(degrees.rem_euclid(360.0))
+}
+
+fn alpha(arg: Option<&String>) -> Option<f32> {
+    let arg = match arg {
+        Some(arg) => arg,
+        None => return Some(1.0),
+    };
+    let v = match arg.strip_suffix('%') {
+        Some(pct) => pct.parse::<f32>().ok()? / 100.0,
+        None => arg.parse::<f32>().ok()?,
+    };
+    Some(v.clamp(0.0, 1.0))
+}
+
+fn named_color(name: &str) -> Option<Color> {
+    let color = match name {
+        "transparent" => Color::new(0.0, 0.0, 0.0, 0.0),
+        "black" => Color::new(0.0, 0.0, 0.0, 1.0),
+        "silver" => Color::new(
+            0.7529411764705882,
+            0.7529411764705882,
+            0.7529411764705882,
+            1.0,
+        ),
+        "gray" | "grey" => Color::new(
+            0.5019607843137255,
+            0.5019607843137255,
+            0.5019607843137255,
+            1.0,
+        ),
+        "white" => Color::new(1.0, 1.0, 1.0, 1.0),
+        "maroon" => Color::new(0.5019607843137255, 0.0, 0.0, 1.0),
+        "red" => Color::new(1.0, 0.0, 0.0, 1.0),
+        "purple" => Color::new(0.5019607843137255, 0.0, 0.5019607843137255, 1.0),
+        "fuchsia" | "magenta" => Color::new(1.0, 0.0, 1.0, 1.0),
+        "green" => Color::new(0.0, 0.5019607843137255, 0.0, 1.0),
+        "lime" => Color::new(0.0, 1.0, 0.0, 1.0),
+        "olive" => Color::new(0.5019607843137255, 0.5019607843137255, 0.0, 1.0),
+        "yellow" => Color::new(1.0, 1.0, 0.0, 1.0),
+        "navy" => Color::new(0.0, 0.0, 0.5019607843137255, 1.0),
+        "blue" => Color::new(0.0, 0.0, 1.0, 1.0),
+        "teal" => Color::new(0.0, 0.5019607843137255, 0.5019607843137255, 1.0),
+        "aqua" | "cyan" => Color::new(0.0, 1.0, 1.0, 1.0),
+        "orange" => Color::new(1.0, 0.6470588235294118, 0.0, 1.0),
+        "aliceblue" => Color::new(0.9411764705882353, 0.9725490196078431, 1.0, 1.0),
+        "antiquewhite" => Color::new(
+            0.9803921568627451,
+            0.9215686274509803,
+            0.8431372549019608,
+            1.0,
+        ),
+        "aquamarine" => Color::new(0.4980392156862745, 1.0, 0.8313725490196079, 1.0),
+        "azure" => Color::new(0.9411764705882353, 1.0, 1.0, 1.0),
+        "beige" => Color::new(
+            0.9607843137254902,
+            0.9607843137254902,
+            0.8627450980392157,
+            1.0,
+        ),
+        "bisque" => Color::new(1.0, 0.8941176470588236, 0.7686274509803922, 1.0),
+        "blanchedalmond" => Color::new(1.0, 0.9215686274509803, 0.803921568627451, 1.0),
+        "blueviolet" => Color::new(
+            0.5411764705882353,
+            0.16862745098039217,
+            0.8862745098039215,
+            1.0,
+        ),
+        "brown" => Color::new(
+            0.6470588235294118,
+            0.16470588235294117,
+            0.16470588235294117,
+            1.0,
+        ),
+        "burlywood" => Color::new(
+            0.8705882352941177,
+            0.7215686274509804,
+            0.5294117647058824,
+            1.0,
+        ),
+        "cadetblue" => Color::new(
+            0.37254901960784315,
+            0.6196078431372549,
+            0.6274509803921569,
+            1.0,
+        ),
+        "chartreuse" => Color::new(0.4980392156862745, 1.0, 0.0, 1.0),
+        "chocolate" => Color::new(
+            0.8235294117647058,
+            0.4117647058823529,
+            0.11764705882352941,
+            1.0,
+        ),
+        "coral" => Color::new(1.0, 0.4980392156862745, 0.3137254901960784, 1.0),
+        "cornflowerblue" => Color::new(
+            0.39215686274509803,
+            0.5843137254901961,
+            0.9294117647058824,
+            1.0,
+        ),
+        "cornsilk" => Color::new(1.0, 0.9725490196078431, 0.8627450980392157, 1.0),
+        "crimson" => Color::new(
+            0.8627450980392157,
+            0.0784313725490196,
+            0.23529411764705882,
+            1.0,
+        ),
+        "darkblue" => Color::new(0.0, 0.0, 0.5450980392156862, 1.0),
+        "darkcyan" => Color::new(0.0, 0.5450980392156862, 0.5450980392156862, 1.0),
+        "darkgoldenrod" => Color::new(
+            0.7215686274509804,
+            0.5254901960784314,
+            0.043137254901960784,
+            1.0,
+        ),
+        "darkgray" | "darkgrey" => Color::new(
+            0.6627450980392157,
+            0.6627450980392157,
+            0.6627450980392157,
+            1.0,
+        ),
+        "darkgreen" => Color::new(0.0, 0.39215686274509803, 0.0, 1.0),
+        "darkkhaki" => Color::new(
+            0.7411764705882353,
+            0.7176470588235294,
+            0.4196078431372549,
+            1.0,
+        ),
+        "darkmagenta" => Color::new(0.5450980392156862, 0.0, 0.5450980392156862, 1.0),
+        "darkolivegreen" => Color::new(
+            0.3333333333333333,
+            0.4196078431372549,
+            0.1843137254901961,
+            1.0,
+        ),
+        "darkorange" => Color::new(1.0, 0.5490196078431373, 0.0, 1.0),
+        "darkorchid" => Color::new(0.6, 0.19607843137254902, 0.8, 1.0),
+        "darkred" => Color::new(0.5450980392156862, 0.0, 0.0, 1.0),
+        "darksalmon" => Color::new(
+            0.9137254901960784,
+            0.5882352941176471,
+            0.47843137254901963,
+            1.0,
+        ),
+        "darkseagreen" => Color::new(
+            0.5607843137254902,
+            0.7372549019607844,
+            0.5607843137254902,
+            1.0,
+        ),
+        "darkslateblue" => Color::new(
+            0.2823529411764706,
+            0.23921568627450981,
+            0.5450980392156862,
+            1.0,
+        ),
+        "darkslategray" | "darkslategrey" => Color::new(
+            0.1843137254901961,
+            0.30980392156862746,
+            0.30980392156862746,
+            1.0,
+        ),
+        "darkturquoise" => Color::new(0.0, 0.807843137254902, 0.8196078431372549, 1.0),
+        "darkviolet" => Color::new(0.5803921568627451, 0.0, 0.8274509803921568, 1.0),
+        "deeppink" => Color::new(1.0, 0.0784313725490196, 0.5764705882352941, 1.0),
+        "deepskyblue" => Color::new(0.0, 0.7490196078431373, 1.0, 1.0),
+        "dimgray" | "dimgrey" => Color::new(
+            0.4117647058823529,
+            0.4117647058823529,
+            0.4117647058823529,
+            1.0,
+        ),
+        "dodgerblue" => Color::new(0.11764705882352941, 0.5647058823529412, 1.0, 1.0),
+        "firebrick" => Color::new(
+            0.6980392156862745,
+            0.13333333333333333,
+            0.13333333333333333,
+            1.0,
+        ),
+        "floralwhite" => Color::new(1.0, 0.9803921568627451, 0.9411764705882353, 1.0),
+        "forestgreen" => Color::new(
+            0.13333333333333333,
+            0.5450980392156862,
+            0.13333333333333333,
+            1.0,
+        ),
+        "gainsboro" => Color::new(
+            0.8627450980392157,
+            0.8627450980392157,
+            0.8627450980392157,
+            1.0,
+        ),
+        "ghostwhite" => Color::new(0.9725490196078431, 0.9725490196078431, 1.0, 1.0),
+        "gold" => Color::new(1.0, 0.8431372549019608, 0.0, 1.0),
+        "goldenrod" => Color::new(
+            0.8549019607843137,
+            0.6470588235294118,
+            0.12549019607843137,
+            1.0,
+        ),
+        "greenyellow" => Color::new(0.6784313725490196, 1.0, 0.1843137254901961, 1.0),
+        "honeydew" => Color::new(0.9411764705882353, 1.0, 0.9411764705882353, 1.0),
+        "hotpink" => Color::new(1.0, 0.4117647058823529, 0.7058823529411765, 1.0),
+        "indianred" => Color::new(
+            0.803921568627451,
+            0.3607843137254902,
+            0.3607843137254902,
+            1.0,
+        ),
+        "indigo" => Color::new(0.29411764705882354, 0.0, 0.5098039215686274, 1.0),
+        "ivory" => Color::new(1.0, 1.0, 0.9411764705882353, 1.0),
+        "khaki" => Color::new(
+            0.9411764705882353,
+            0.9019607843137255,
+            0.5490196078431373,
+            1.0,
+        ),
+        "lavender" => Color::new(
+            0.9019607843137255,
+            0.9019607843137255,
+            0.9803921568627451,
+            1.0,
+        ),
+        "lavenderblush" => Color::new(1.0, 0.9411764705882353, 0.9607843137254902, 1.0),
+        "lawngreen" => Color::new(0.48627450980392156, 0.9882352941176471, 0.0, 1.0),
+        "lemonchiffon" => Color::new(1.0, 0.9803921568627451, 0.803921568627451, 1.0),
+        "lightblue" => Color::new(
+            0.6784313725490196,
+            0.8470588235294118,
+            0.9019607843137255,
+            1.0,
+        ),
+        "lightcoral" => Color::new(
+            0.9411764705882353,
+            0.5019607843137255,
+            0.5019607843137255,
+            1.0,
+        ),
+        "lightcyan" => Color::new(0.8784313725490196, 1.0, 1.0, 1.0),
+        "lightgoldenrodyellow" => Color::new(
+            0.9803921568627451,
+            0.9803921568627451,
+            0.8235294117647058,
+            1.0,
+        ),
+        "lightgray" | "lightgrey" => Color::new(
+            0.8274509803921568,
+            0.8274509803921568,
+            0.8274509803921568,
+            1.0,
+        ),
+        "lightgreen" => Color::new(
+            0.5647058823529412,
+            0.9333333333333333,
+            0.5647058823529412,
+            1.0,
+        ),
+        "lightpink" => Color::new(1.0, 0.7137254901960784, 0.7568627450980392, 1.0),
+        "lightsalmon" => Color::new(1.0, 0.6274509803921569, 0.47843137254901963, 1.0),
+        "lightseagreen" => Color::new(
+            0.12549019607843137,
+            0.6980392156862745,
+            0.6666666666666666,
+            1.0,
+        ),
+        "lightskyblue" => Color::new(
+            0.5294117647058824,
+            0.807843137254902,
+            0.9803921568627451,
+            1.0,
+        ),
+        "lightslategray" | "lightslategrey" => {
+            Color::new(0.4666666666666667, 0.5333333333333333, 0.6, 1.0)
+        }
+        "lightsteelblue" => Color::new(
+            0.6901960784313725,
+            0.7686274509803922,
+            0.8705882352941177,
+            1.0,
+        ),
+        "lightyellow" => Color::new(1.0, 1.0, 0.8784313725490196, 1.0),
+        "limegreen" => Color::new(
+            0.19607843137254902,
+            0.803921568627451,
+            0.19607843137254902,
+            1.0,
+        ),
+        "linen" => Color::new(
+            0.9803921568627451,
+            0.9411764705882353,
+            0.9019607843137255,
+            1.0,
+        ),
+        "mediumaquamarine" => Color::new(0.4, 0.803921568627451, 0.6666666666666666, 1.0),
+        "mediumblue" => Color::new(0.0, 0.0, 0.803921568627451, 1.0),
+        "mediumorchid" => Color::new(
+            0.7294117647058823,
+            0.3333333333333333,
+            0.8274509803921568,
+            1.0,
+        ),
+        "mediumpurple" => Color::new(
+            0.5764705882352941,
+            0.4392156862745098,
+            0.8588235294117647,
+            1.0,
+        ),
+        "mediumseagreen" => Color::new(
+            0.23529411764705882,
+            0.7019607843137254,
+            0.44313725490196076,
+            1.0,
+        ),
+        "mediumslateblue" => Color::new(
+            0.4823529411764706,
+            0.40784313725490196,
+            0.9333333333333333,
+            1.0,
+        ),
+        "mediumspringgreen" => Color::new(0.0, 0.9803921568627451, 0.6039215686274509, 1.0),
+        "mediumturquoise" => Color::new(0.2823529411764706, 0.8196078431372549, 0.8, 1.0),
+        "mediumvioletred" => Color::new(
+            0.7803921568627451,
+            0.08235294117647059,
+            0.5215686274509804,
+            1.0,
+        ),
+        "midnightblue" => Color::new(
+            0.09803921568627451,
+            0.09803921568627451,
+            0.4392156862745098,
+            1.0,
+        ),
+        "mintcream" => Color::new(0.9607843137254902, 1.0, 0.9803921568627451, 1.0),
+        "mistyrose" => Color::new(1.0, 0.8941176470588236, 0.8823529411764706, 1.0),
+        "moccasin" => Color::new(1.0, 0.8941176470588236, 0.7098039215686275, 1.0),
+        "navajowhite" => Color::new(1.0, 0.8705882352941177, 0.6784313725490196, 1.0),
+        "oldlace" => Color::new(
+            0.9921568627450981,
+            0.9607843137254902,
+            0.9019607843137255,
+            1.0,
+        ),
+        "olivedrab" => Color::new(
+            0.4196078431372549,
+            0.5568627450980392,
+            0.13725490196078433,
+            1.0,
+        ),
+        "orangered" => Color::new(1.0, 0.27058823529411763, 0.0, 1.0),
+        "orchid" => Color::new(
+            0.8549019607843137,
+            0.4392156862745098,
+            0.8392156862745098,
+            1.0,
+        ),
+        "palegoldenrod" => Color::new(
+            0.9333333333333333,
+            0.9098039215686274,
+            0.6666666666666666,
+            1.0,
+        ),
+        "palegreen" => Color::new(0.596078431372549, 0.984313725490196, 0.596078431372549, 1.0),
+        "paleturquoise" => Color::new(
+            0.6862745098039216,
+            0.9333333333333333,
+            0.9333333333333333,
+            1.0,
+        ),
+        "palevioletred" => Color::new(
+            0.8588235294117647,
+            0.4392156862745098,
+            0.5764705882352941,
+            1.0,
+        ),
+        "papayawhip" => Color::new(1.0, 0.9372549019607843, 0.8352941176470589, 1.0),
+        "peachpuff" => Color::new(1.0, 0.8549019607843137, 0.7254901960784313, 1.0),
+        "peru" => Color::new(
+            0.803921568627451,
+            0.5215686274509804,
+            0.24705882352941178,
+            1.0,
+        ),
+        "pink" => Color::new(1.0, 0.7529411764705882, 0.796078431372549, 1.0),
+        "plum" => Color::new(
+            0.8666666666666667,
+            0.6274509803921569,
+            0.8666666666666667,
+            1.0,
+        ),
+        "powderblue" => Color::new(
+            0.6901960784313725,
+            0.8784313725490196,
+            0.9019607843137255,
+            1.0,
+        ),
+        "rosybrown" => Color::new(
+            0.7372549019607844,
+            0.5607843137254902,
+            0.5607843137254902,
+            1.0,
+        ),
+        "royalblue" => Color::new(
+            0.2549019607843137,
+            0.4117647058823529,
+            0.8823529411764706,
+            1.0,
+        ),
+        "saddlebrown" => Color::new(
+            0.5450980392156862,
+            0.27058823529411763,
+            0.07450980392156863,
+            1.0,
+        ),
+        "salmon" => Color::new(
+            0.9803921568627451,
+            0.5019607843137255,
+            0.4470588235294118,
+            1.0,
+        ),
+        "sandybrown" => Color::new(
+            0.9568627450980393,
+            0.6431372549019608,
+            0.3764705882352941,
+            1.0,
+        ),
+        "seagreen" => Color::new(
+            0.1803921568627451,
+            0.5450980392156862,
+            0.3411764705882353,
+            1.0,
+        ),
+        "seashell" => Color::new(1.0, 0.9607843137254902, 0.9333333333333333, 1.0),
+        "sienna" => Color::new(
+            0.6274509803921569,
+            0.3215686274509804,
+            0.17647058823529413,
+            1.0,
+        ),
+        "skyblue" => Color::new(
+            0.5294117647058824,
+            0.807843137254902,
+            0.9215686274509803,
+            1.0,
+        ),
+        "slateblue" => Color::new(
+            0.41568627450980394,
+            0.35294117647058826,
+            0.803921568627451,
+            1.0,
+        ),
+        "slategray" | "slategrey" => Color::new(
+            0.4392156862745098,
+            0.5019607843137255,
+            0.5647058823529412,
+            1.0,
+        ),
+        "snow" => Color::new(1.0, 0.9803921568627451, 0.9803921568627451, 1.0),
+        "springgreen" => Color::new(0.0, 1.0, 0.4980392156862745, 1.0),
+        "steelblue" => Color::new(
+            0.27450980392156865,
+            0.5098039215686274,
+            0.7058823529411765,
+            1.0,
+        ),
+        "tan" => Color::new(
+            0.8235294117647058,
+            0.7058823529411765,
+            0.5490196078431373,
+            1.0,
+        ),
+        "thistle" => Color::new(
+            0.8470588235294118,
+            0.7490196078431373,
+            0.8470588235294118,
+            1.0,
+        ),
+        "tomato" => Color::new(1.0, 0.38823529411764707, 0.2784313725490196, 1.0),
+        "turquoise" => Color::new(
+            0.25098039215686274,
+            0.8784313725490196,
+            0.8156862745098039,
+            1.0,
+        ),
+        "violet" => Color::new(
+            0.9333333333333333,
+            0.5098039215686274,
+            0.9333333333333333,
+            1.0,
+        ),
+        "wheat" => Color::new(
+            0.9607843137254902,
+            0.8705882352941177,
+            0.7019607843137254,
+            1.0,
+        ),
+        "whitesmoke" => Color::new(
+            0.9607843137254902,
+            0.9607843137254902,
+            0.9607843137254902,
+            1.0,
+        ),
+        "yellowgreen" => Color::new(
+            0.6039215686274509,
+            0.803921568627451,
+            0.19607843137254902,
+            1.0,
+        ),
+        "rebeccapurple" => Color::new(0.4, 0.2, 0.6, 1.0),
+        _ => return None,
+    };
+    Some(color)
+}
+
+fn is_css_wide_keyword(value: &str) -> bool {
+    matches!(value, "inherit" | "initial" | "unset")
+}
+
 fn is_valid_ident(c: char) -> bool {
     is_valid_start_ident(c) || c.is_digit(10) || c == '-'
 }
@@ -771,3 +1192,228 @@ fn is_lower_letter(c: char) -> bool {
 fn is_non_ascii(c: char) -> bool {
     c >= '\u{0080}'
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_selector_with_combinators() {
+        let stylesheet =
+            CssParser::new("div  p > .a+#b ~ *, a:hover, ul li { }").parse_stylesheet();
+        let selectors = &stylesheet.rules[0].selectors;
+
+        assert_eq!(selectors.len(), 2);
+        assert_eq!(selectors[0].simple.len(), 5);
+        assert_eq!(selectors[0].combinators, vec![' ', '>', '+', '~']);
+        assert_eq!(format!("{:?}", selectors[0]), "div p > .a + #b ~ ");
+        assert_eq!(format!("{:?}", selectors[1]), "ul li");
+    }
+
+    #[test]
+    fn translate_decimal_and_signed_lengths() {
+        assert!(translate_length("1.5em") == Value::Length(1.5, Unit::Em));
+        assert!(translate_length("-4px") == Value::Length(-4.0, Unit::Px));
+        assert!(translate_length(".5in") == Value::Length(0.5, Unit::In));
+        assert!(translate_length("12.5%") == Value::Length(12.5, Unit::Pct));
+        assert!(translate_length("3Q".to_lowercase().as_str()) == Value::Length(3.0, Unit::Q));
+    }
+
+    fn declarations(css: &str) -> Vec<(String, Value)> {
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        stylesheet.rules[0]
+            .declarations
+            .iter()
+            .map(|d| (d.property.clone(), d.value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn box_shorthands_expand_to_sides() {
+        let px = |n| Value::Length(n, Unit::Px);
+        let margin = declarations("div { margin: 1px 2px 3px; padding: 4px 5px }");
+        assert!(
+            margin
+                == vec![
+                    ("margin-top".to_string(), px(1.0)),
+                    ("margin-right".to_string(), px(2.0)),
+                    ("margin-bottom".to_string(), px(3.0)),
+                    ("margin-left".to_string(), px(2.0)),
+                    ("padding-top".to_string(), px(4.0)),
+                    ("padding-right".to_string(), px(5.0)),
+                    ("padding-bottom".to_string(), px(4.0)),
+                    ("padding-left".to_string(), px(5.0)),
+                ]
+        );
+
+        let auto = declarations("div { margin: 0 auto; }");
+        assert!(auto[1] == ("margin-right".to_string(), Value::Other("auto".to_string())));
+        assert!(auto[3] == ("margin-left".to_string(), Value::Other("auto".to_string())));
+
+        // five values don't fit the grammar and the declaration stays as is
+        let invalid = declarations("div { margin: 1px 2px 3px 4px 5px; }");
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].0, "margin");
+    }
+
+    #[test]
+    fn border_and_background_shorthands() {
+        let border = declarations("div { border: red 2px solid; border-left: thick dashed }");
+        assert_eq!(border.len(), 15);
+        assert!(border[0] == ("border-top-width".to_string(), Value::Length(2.0, Unit::Px)));
+        assert!(
+            border[1]
+                == (
+                    "border-top-style".to_string(),
+                    Value::Other("solid".to_string())
+                )
+        );
+        assert!(
+            border[2]
+                == (
+                    "border-top-color".to_string(),
+                    Value::Color(Color::new(1.0, 0.0, 0.0, 1.0))
+                )
+        );
+        assert!(
+            border[12]
+                == (
+                    "border-left-width".to_string(),
+                    Value::Length(5.0, Unit::Px)
+                )
+        );
+        assert!(
+            border[14]
+                == (
+                    "border-left-color".to_string(),
+                    Value::Other("currentcolor".to_string())
+                )
+        );
+
+        // without a style the border has no width
+        let unstyled = declarations("div { border-top: 4px blue }");
+        assert!(unstyled[0] == ("border-top-width".to_string(), Value::Length(0.0, Unit::Px)));
+
+        let background = declarations("div { background: url(a.png) no-repeat #00ff00 }");
+        assert!(
+            background
+                == vec![(
+                    "background-color".to_string(),
+                    Value::Color(Color::new(0.0, 1.0, 0.0, 1.0))
+                )]
+        );
+    }
+
+    fn approx(color: Option<Color>, expected: (f32, f32, f32, f32)) -> bool {
+        let c = color.expect("color should parse");
+        let (r, g, b, a) = expected;
+        [(c.r, r), (c.g, g), (c.b, b), (c.a, a)]
+            .iter()
+            .all(|(x, y)| (x - y).abs() < 0.005)
+    }
+
+    #[test]
+    fn translate_hex_and_named_colors() {
+        assert!(approx(translate_color("#f00"), (1.0, 0.0, 0.0, 1.0)));
+        assert!(approx(translate_color("#0f08"), (0.0, 1.0, 0.0, 0.533)));
+        assert!(approx(translate_color("#336699"), (0.2, 0.4, 0.6, 1.0)));
+        assert!(approx(translate_color("#33669980"), (0.2, 0.4, 0.6, 0.502)));
+        assert!(approx(translate_color("transparent"), (0.0, 0.0, 0.0, 0.0)));
+        assert!(approx(translate_color("cyan"), (0.0, 1.0, 1.0, 1.0)));
+        assert!(approx(
+            translate_color("rebeccapurple"),
+            (0.4, 0.2, 0.6, 1.0)
+        ));
+        assert!(translate_color("#12345").is_none());
+        assert!(translate_color("#ggg").is_none());
+        assert!(translate_color("notacolor").is_none());
+    }
+
+    #[test]
+    fn translate_color_functions() {
+        assert!(approx(
+            translate_color("rgb(255, 0, 51)"),
+            (1.0, 0.0, 0.2, 1.0)
+        ));
+        assert!(approx(
+            translate_color("rgba(255,0,0,0.5)"),
+            (1.0, 0.0, 0.0, 0.5)
+        ));
+        assert!(approx(
+            translate_color("rgb(100% 50% 0% / 25%)"),
+            (1.0, 0.5, 0.0, 0.25)
+        ));
+        assert!(approx(
+            translate_color("rgba(0 0 255)"),
+            (0.0, 0.0, 1.0, 1.0)
+        ));
+        assert!(approx(
+            translate_color("rgb(300, -5, 0)"),
+            (1.0, 0.0, 0.0, 1.0)
+        ));
+        assert!(approx(
+            translate_color("hsl(120, 100%, 50%)"),
+            (0.0, 1.0, 0.0, 1.0)
+        ));
+        assert!(approx(
+            translate_color("hsla(0, 100%, 50%, .3)"),
+            (1.0, 0.0, 0.0, 0.3)
+        ));
+        assert!(approx(
+            translate_color("hsl(240deg 100% 25% / 0.5)"),
+            (0.0, 0.0, 0.5, 0.5)
+        ));
+        assert!(approx(
+            translate_color("hsl(0.5turn 100% 50%)"),
+            (0.0, 1.0, 1.0, 1.0)
+        ));
+        assert!(approx(
+            translate_color("hsl(-60, 100%, 50%)"),
+            (1.0, 0.0, 1.0, 1.0)
+        ));
+        assert!(translate_color("rgb(1, 2)").is_none());
+        assert!(translate_color("rgb(1 2 / 3)").is_none());
+        assert!(translate_color("hsl(10foo, 1%, 1%)").is_none());
+
+        // an invalid color isn't painted as black
+        let invalid = declarations("p { color: rgb(1, x, 3); }");
+        assert!(invalid[0].1 == Value::Other("rgb(1, x, 3)".to_string()));
+    }
+
+    #[test]
+    fn flex_and_gap_shorthands() {
+        let longhands = |css: &str| {
+            declarations(css)
+                .into_iter()
+                .map(|(name, value)| match value {
+                    Value::Other(v) => format!("{}: {}", name, v),
+                    Value::Length(n, Unit::Px) => format!("{}: {}px", name, n),
+                    Value::Length(n, Unit::Pct) => format!("{}: {}%", name, n),
+                    _ => name,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            longhands("a { flex: 2; }"),
+            ["flex-grow: 2", "flex-shrink: 1", "flex-basis: 0%"]
+        );
+        assert_eq!(
+            longhands("a { flex: none; }"),
+            ["flex-grow: 0", "flex-shrink: 0", "flex-basis: auto"]
+        );
+        assert_eq!(
+            longhands("a { flex: 30px 2 3; }"),
+            ["flex-grow: 2", "flex-shrink: 3", "flex-basis: 30px"]
+        );
+        assert_eq!(
+            longhands("a { flex: 1 20px; }"),
+            ["flex-grow: 1", "flex-shrink: 1", "flex-basis: 20px"]
+        );
+        assert_eq!(longhands("a { flex: 1 2px 3; }"), ["flex: 1 2px 3"]);
+        assert_eq!(
+            longhands("a { gap: 4px 8px; }"),
+            ["row-gap: 4px", "column-gap: 8px"]
+        );
+    }
+}
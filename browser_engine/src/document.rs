@@ -0,0 +1,370 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::css::Stylesheet;
+use crate::css_parser::{strip_comments, CssParser};
+use crate::dom::{AttrMap, ElementData, Node, NodeId, NodeType};
+use crate::html_parser::HtmlParser;
+use crate::layout::{layout_tree_cached, Dimensions, LayoutBox, LayoutCache, Rectangle};
+use crate::painting::{paint, Canvas};
+use crate::style::{StyledNode, Viewport};
+
+// The DOM together with the style tree and box geometry of its last layout.
+// The DOM is changed through the document so that the next layout only
+// restyles the elements that changed and only lays out their boxes and
+// those of their ancestors again.
+pub struct Document {
+    root: Node,
+    // how to reach each node from the root, by NodeId; nodes are never
+    // added or removed through the document, so they stay valid
+    paths: Vec<Vec<usize>>,
+    pub stylesheet: Stylesheet,
+    styled: Option<(Viewport, StyledNode)>,
+    layout_cache: LayoutCache,
+}
+
+impl Document {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Document> {
+        let path = path.as_ref();
+        let html = fs::read_to_string(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Ok(Document::parse(&html, base_dir))
+    }
+
+    // `<link rel=stylesheet>` hrefs are resolved against `base_dir`; only
+    // local files are loaded and missing ones are skipped
+    pub fn parse(html: &str, base_dir: &Path) -> Document {
+        let root = into_root(HtmlParser::new(html).parse_nodes());
+
+        let mut stylesheet = Stylesheet::default();
+        for css in collect_css(&root, base_dir) {
+            let mut sheet = CssParser::new(&strip_comments(&css)).parse_stylesheet();
+            stylesheet.rules.append(&mut sheet.rules);
+        }
+
+        Document {
+            paths: root.paths(),
+            root,
+            stylesheet,
+            styled: None,
+            layout_cache: LayoutCache::default(),
+        }
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        self.root.at_path(self.paths.get(id)?)
+    }
+
+    pub fn node_id(&self, node: &Node) -> Option<NodeId> {
+        self.root.id_of(node)
+    }
+
+    fn element_mut(&mut self, id: NodeId) -> Option<&mut ElementData> {
+        let path = self.paths.get(id)?;
+        self.root.at_path_mut(path)?.element_mut()
+    }
+
+    // Returns false, changing nothing, if `id` isn't an element.
+    pub fn set_attribute(&mut self, id: NodeId, name: &str, value: &str) -> bool {
+        match self.element_mut(id) {
+            Some(element) => element.set_attribute(name, value),
+            None => return false,
+        }
+        self.invalidate(id);
+        true
+    }
+
+    pub fn remove_attribute(&mut self, id: NodeId, name: &str) -> Option<String> {
+        let removed = self.element_mut(id).and_then(|e| e.remove_attribute(name));
+        if removed.is_some() {
+            self.invalidate(id);
+        }
+        removed
+    }
+
+    fn invalidate(&mut self, id: NodeId) {
+        if let Some((_, styled)) = self.styled.as_mut() {
+            styled.invalidate(id);
+        }
+    }
+
+    // a fresh style tree, independent of the one kept for layout
+    pub fn style_tree(&self, viewport: Viewport) -> StyledNode {
+        StyledNode::with_viewport(&self.root, &self.stylesheet, viewport)
+    }
+
+    // Brings the kept style tree up to date and lays it out. A new viewport
+    // size changes what relative lengths resolve to, so it starts over.
+    pub fn layout(&mut self, width: usize, height: usize) -> LayoutBox {
+        let viewport = Viewport {
+            width: width as f32,
+            height: height as f32,
+        };
+        match self.styled {
+            Some((v, ref mut styled)) if v == viewport => {
+                for id in styled.restyle(&self.root, &self.stylesheet, viewport) {
+                    self.layout_cache.invalidate(id);
+                }
+            }
+            _ => {
+                self.styled = Some((viewport, self.style_tree(viewport)));
+                self.layout_cache.clear();
+            }
+        }
+
+        let (_, styled) = self.styled.as_ref().unwrap();
+        layout_tree_cached(
+            styled,
+            Dimensions::viewport(viewport.width, viewport.height),
+            &mut self.layout_cache,
+        )
+    }
+
+    pub fn render(&mut self, width: usize, height: usize) -> Canvas {
+        let layout_root = self.layout(width, height);
+        let (_, styled) = self.styled.as_ref().unwrap();
+        paint(
+            &layout_root,
+            styled,
+            Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: width as f32,
+                height: height as f32,
+            },
+        )
+    }
+}
+
+// the style tree needs a single root: use the <html> element if there is
+// one, otherwise wrap whatever was parsed in a synthetic <html>
+fn into_root(mut nodes: Vec<Node>) -> Node {
+    let html = nodes.iter().position(|n| match n.node_type {
+        NodeType::Element(ref e) => e.tag_name == "html",
+        _ => false,
+    });
+    match html {
+        Some(i) => nodes.swap_remove(i),
+        None => Node::new(
+            NodeType::Element(ElementData::new("html".to_string(), AttrMap::new())),
+            nodes,
+        ),
+    }
+}
+
+// style sheets in document order
+fn collect_css(node: &Node, base_dir: &Path) -> Vec<String> {
+    let mut sheets = Vec::new();
+    if let NodeType::Element(ref e) = node.node_type {
+        match e.tag_name.as_ref() {
+            "style" => {
+                let is_css = e
+                    .get_attribute("type")
+                    .is_none_or(|t| t.eq_ignore_ascii_case("text/css"));
+                if is_css {
+                    for child in &node.children {
+                        if let NodeType::Text(ref css) = child.node_type {
+                            sheets.push(css.clone());
+                        }
+                    }
+                }
+                return sheets;
+            }
+            "link" => {
+                let is_stylesheet = e.get_attribute("rel").is_some_and(|rel| {
+                    rel.split_whitespace()
+                        .any(|r| r.eq_ignore_ascii_case("stylesheet"))
+                });
+                if let (true, Some(href)) = (is_stylesheet, e.get_attribute("href")) {
+                    if let Some(css) = load_local_stylesheet(href, base_dir) {
+                        sheets.push(css);
+                    }
+                }
+                return sheets;
+            }
+            _ => {}
+        }
+    }
+
+    for child in &node.children {
+        sheets.append(&mut collect_css(child, base_dir));
+    }
+    sheets
+}
+
+fn load_local_stylesheet(href: &str, base_dir: &Path) -> Option<String> {
+    let path = match href.strip_prefix("file://") {
+        Some(path) => Path::new(path).to_path_buf(),
+        None if href.contains("://") => return None,
+        None => base_dir.join(href),
+    };
+    fs::read_to_string(path).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::Color;
+    use crate::layout::layout_tree;
+    use std::env;
+
+    #[test]
+    fn collects_style_and_link_stylesheets() {
+        let dir = env::temp_dir().join("browser_engine_document_test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("site.css"), "/* linked */ p { content: linked; }").unwrap();
+
+        let html = "<!doctype html><html><head>\
+                    <link rel=\"stylesheet\" href=\"site.css\">\
+                    <link rel=\"stylesheet\" href=\"missing.css\">\
+                    <style>p { content: inline; } div { content: inline; }</style>\
+                    </head><body><p>hi</p></body></html>";
+        let document = Document::parse(html, &dir);
+        assert_eq!(document.stylesheet.rules.len(), 3);
+
+        let styled = document.style_tree(Viewport::default());
+        let p = &styled.children[1].children[0];
+        assert_eq!(p.tag_name(), Some("p"));
+        assert_eq!(format!("{:?}", p.value("content").unwrap()), "\"inline\"");
+    }
+
+    fn geometry(layout_box: &LayoutBox, out: &mut Vec<String>) {
+        out.push(format!("{:?}", layout_box.dimensions));
+        for fragment in &layout_box.fragments {
+            out.push(format!("{} {:?}", fragment.text, fragment.rect));
+        }
+        for child in &layout_box.children {
+            geometry(child, out);
+        }
+    }
+
+    #[test]
+    fn incremental_layout_matches_a_fresh_one() {
+        let html = "<style> \
+            .tall { height: 40px; } \
+            .big { font-size: 20px; } \
+            .row { display: flex; } \
+            .tall + p { padding-left: 30px; } \
+            #abs { position: absolute; right: 0; bottom: 0; width: 10px; height: 10px; } \
+            #rel { position: relative; top: 5px; } \
+            span { display: inline-block; width: 15px; } \
+            </style><body><div id=first>one</div><p id=para>some words <span>x</span> \
+            that wrap</p><div id=box><div id=rel>a</div><div>b <em>c</em></div>\
+            <div id=abs></div></div></body>";
+        let mut document = Document::parse(html, Path::new("."));
+        let id = |document: &Document, selector| {
+            let node = document.root().query_selector(selector).unwrap();
+            document.node_id(node).unwrap()
+        };
+        let (first, para, container) = (
+            id(&document, "#first"),
+            id(&document, "#para"),
+            id(&document, "#box"),
+        );
+        let check = |document: &mut Document, width| {
+            let mut incremental = Vec::new();
+            geometry(&document.layout(width, 300), &mut incremental);
+            let styled = document.style_tree(Viewport {
+                width: width as f32,
+                height: 300.0,
+            });
+            let fresh_root = layout_tree(&styled, Dimensions::viewport(width as f32, 300.0));
+            let mut fresh = Vec::new();
+            geometry(&fresh_root, &mut fresh);
+            assert_eq!(incremental, fresh);
+        };
+
+        check(&mut document, 200);
+        assert!(document.set_attribute(first, "class", "tall"));
+        check(&mut document, 200);
+        assert!(document.set_attribute(para, "class", "big"));
+        check(&mut document, 200);
+        assert!(document.set_attribute(container, "class", "row"));
+        check(&mut document, 200);
+        assert_eq!(
+            document.remove_attribute(first, "class").as_deref(),
+            Some("tall")
+        );
+        check(&mut document, 200);
+        check(&mut document, 120);
+        assert!(!document.set_attribute(first + 1, "class", "tall"));
+    }
+
+    #[test]
+    fn incremental_layout_moves_empty_inline_boxes() {
+        // whole pixels, so that moving boxes back and forth stays exact
+        let html = "<style>body { font-size: 10px; line-height: 20px; } \
+            .tall { height: 40px; } b { padding-left: 4px; }</style>\
+            <body><div id=first>one</div><p>ab<b></b> cd<br>ef<i> </i></p></body>";
+        let mut document = Document::parse(html, Path::new("."));
+        let node = document.root().query_selector("#first").unwrap();
+        let first = document.node_id(node).unwrap();
+        let check = |document: &mut Document| {
+            let mut incremental = Vec::new();
+            geometry(&document.layout(200, 300), &mut incremental);
+            let styled = document.style_tree(Viewport {
+                width: 200.0,
+                height: 300.0,
+            });
+            let fresh_root = layout_tree(&styled, Dimensions::viewport(200.0, 300.0));
+            let mut fresh = Vec::new();
+            geometry(&fresh_root, &mut fresh);
+            assert_eq!(incremental, fresh);
+        };
+
+        check(&mut document);
+        // the paragraph is reused and only moved down
+        assert!(document.set_attribute(first, "class", "tall"));
+        check(&mut document);
+        assert_eq!(
+            document.remove_attribute(first, "class").as_deref(),
+            Some("tall")
+        );
+        check(&mut document);
+    }
+
+    #[test]
+    fn layouts_outlive_changes_to_the_dom() {
+        let html = "<style>.tall { height: 40px; }</style>\
+                    <body><div id=first>one</div><p id=para>two</p></body>";
+        let mut document = Document::parse(html, Path::new("."));
+        let id = |document: &Document, selector| {
+            let node = document.root().query_selector(selector).unwrap();
+            document.node_id(node).unwrap()
+        };
+        let (first, para) = (id(&document, "#first"), id(&document, "#para"));
+        let hit = |layout: &LayoutBox| layout.hit_test(10.0, 45.0).map(|b| b.node_id());
+
+        // the text of the paragraph, until the div grows over it
+        let before = document.layout(200, 300);
+        assert!(document.set_attribute(first, "Class", "tall"));
+        let after = document.layout(200, 300);
+        assert_eq!(hit(&before), Some(para + 1));
+        assert_eq!(hit(&after), Some(first));
+
+        assert_eq!(
+            document.remove_attribute(first, "CLASS").as_deref(),
+            Some("tall")
+        );
+        assert_eq!(hit(&document.layout(200, 300)), Some(para + 1));
+    }
+
+    #[test]
+    fn renders_boxes_into_a_canvas() {
+        let html = "<style>body { margin-top: 0px; margin-left: 0px; } \
+                    div { height: 10px; background-color: #ff0000; }</style>\
+                    <div></div>";
+        let mut document = Document::parse(html, Path::new("."));
+        let canvas = document.render(20, 20);
+
+        let red = Color::new(1.0, 0.0, 0.0, 1.0);
+        let white = Color::new(1.0, 1.0, 1.0, 1.0);
+        assert!(canvas.pixels[5 * 20 + 5] == red);
+        assert!(canvas.pixels[15 * 20 + 5] == white);
+    }
+}
@@ -3,6 +3,10 @@ use std::{
     fmt,
 };
 
+use crate::css_parser::CssParser;
+use crate::html_parser::{RAW_TEXT_ELEMENTS, VOID_ELEMENTS};
+use crate::style::selector_matches;
+
 pub struct Node {
     pub children: Vec<Node>,
     pub node_type: NodeType,
@@ -30,6 +34,28 @@ impl ElementData {
         self.attributes.get("id")
     }
 
+    // attribute names are ASCII case-insensitive, and stored lowercased
+    pub fn get_attribute(&self, name: &str) -> Option<&String> {
+        self.attributes.get(&name.to_ascii_lowercase())
+    }
+
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attributes.contains_key(&name.to_ascii_lowercase())
+    }
+
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        self.attributes
+            .insert(name.to_ascii_lowercase(), value.to_string());
+    }
+
+    pub fn remove_attribute(&mut self, name: &str) -> Option<String> {
+        self.attributes.remove(&name.to_ascii_lowercase())
+    }
+
+    pub fn attributes(&self) -> &AttrMap {
+        &self.attributes
+    }
+
     pub fn get_classes(&self) -> HashSet<&str> {
         match self.attributes.get("class") {
             Some(s) => s.split(' ').collect(),
@@ -40,6 +66,11 @@ impl ElementData {
 
 pub type AttrMap = HashMap<String, String>;
 
+// A node's position in a pre-order walk of the tree, text and comments
+// included. Ids survive attribute changes but shift when nodes are added or
+// removed.
+pub type NodeId = usize;
+
 impl Node {
     pub fn new(node_type: NodeType, children: Vec<Node>) -> Node {
         Node {
@@ -47,6 +78,240 @@ impl Node {
             children,
         }
     }
+
+    pub fn element(&self) -> Option<&ElementData> {
+        match self.node_type {
+            NodeType::Element(ref e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn element_mut(&mut self) -> Option<&mut ElementData> {
+        match self.node_type {
+            NodeType::Element(ref mut e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        if id == 0 {
+            return Some(self);
+        }
+        let mut first = 1;
+        for child in &self.children {
+            let size = child.subtree_size();
+            if id < first + size {
+                return child.get(id - first);
+            }
+            first += size;
+        }
+        None
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        if id == 0 {
+            return Some(self);
+        }
+        let mut first = 1;
+        for child in &mut self.children {
+            let size = child.subtree_size();
+            if id < first + size {
+                return child.get_mut(id - first);
+            }
+            first += size;
+        }
+        None
+    }
+
+    // the id of `node` if it is this node or one of its descendants
+    pub fn id_of(&self, node: &Node) -> Option<NodeId> {
+        self.find_id(node, &mut 0)
+    }
+
+    fn find_id(&self, node: &Node, next_id: &mut NodeId) -> Option<NodeId> {
+        let id = *next_id;
+        *next_id += 1;
+        if std::ptr::eq(self, node) {
+            return Some(id);
+        }
+        self.children.iter().find_map(|c| c.find_id(node, next_id))
+    }
+
+    fn subtree_size(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .map(|c| c.subtree_size())
+            .sum::<usize>()
+    }
+
+    // The child indices leading to every node, by id, so that a node can be
+    // reached without counting its way there. They stay valid as long as no
+    // node is added or removed.
+    pub fn paths(&self) -> Vec<Vec<usize>> {
+        let mut paths = vec![Vec::new()];
+        self.collect_paths(&mut Vec::new(), &mut paths);
+        paths
+    }
+
+    fn collect_paths(&self, path: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+        for (i, child) in self.children.iter().enumerate() {
+            path.push(i);
+            paths.push(path.clone());
+            child.collect_paths(path, paths);
+            path.pop();
+        }
+    }
+
+    pub fn at_path(&self, path: &[usize]) -> Option<&Node> {
+        path.iter().try_fold(self, |node, &i| node.children.get(i))
+    }
+
+    pub fn at_path_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        path.iter()
+            .try_fold(self, |node, &i| node.children.get_mut(i))
+    }
+
+    // the first descendant element, in document order, matching any of the
+    // comma separated `selectors`
+    pub fn query_selector(&self, selectors: &str) -> Option<&Node> {
+        let selectors = CssParser::new(selectors).parse_selector_list();
+        let mut found = None;
+        self.walk_elements(0, &mut Vec::new(), &mut |node, index, ancestors| {
+            if selectors
+                .iter()
+                .any(|s| selector_matches(node, index, ancestors, s))
+            {
+                found = Some(node);
+            }
+            found.is_none()
+        });
+        found
+    }
+
+    pub fn query_selector_all(&self, selectors: &str) -> Vec<&Node> {
+        let selectors = CssParser::new(selectors).parse_selector_list();
+        let mut found = Vec::new();
+        self.walk_elements(0, &mut Vec::new(), &mut |node, index, ancestors| {
+            if selectors
+                .iter()
+                .any(|s| selector_matches(node, index, ancestors, s))
+            {
+                found.push(node);
+            }
+            true
+        });
+        found
+    }
+
+    // `*` matches every element
+    pub fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<&Node> {
+        let tag_name = tag_name.to_ascii_lowercase();
+        let mut found = Vec::new();
+        self.walk_elements(0, &mut Vec::new(), &mut |node, _, _| {
+            if tag_name == "*" || node.element().is_some_and(|e| e.tag_name == tag_name) {
+                found.push(node);
+            }
+            true
+        });
+        found
+    }
+
+    // Visits the descendant elements in document order with the index and
+    // ancestor stack selector matching needs; stops once `visit` returns
+    // false.
+    fn walk_elements<'n>(
+        &'n self,
+        index: usize,
+        ancestors: &mut Vec<(&'n Node, usize)>,
+        visit: &mut impl FnMut(&'n Node, usize, &[(&'n Node, usize)]) -> bool,
+    ) -> bool {
+        ancestors.push((self, index));
+        let mut more = true;
+        for (i, child) in self.children.iter().enumerate() {
+            if child.element().is_none() {
+                continue;
+            }
+            more = visit(child, i, &ancestors[..]) && child.walk_elements(i, ancestors, visit);
+            if !more {
+                break;
+            }
+        }
+        ancestors.pop();
+        more
+    }
+
+    // the text of all descendant text nodes, in document order
+    pub fn text_content(&self) -> String {
+        match self.node_type {
+            NodeType::Text(ref t) => t.clone(),
+            NodeType::Comment(_) => String::new(),
+            NodeType::Element(_) => self.children.iter().map(|c| c.text_content()).collect(),
+        }
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        serialize(self, false, &mut html);
+        html
+    }
+
+    pub fn inner_html(&self) -> String {
+        let raw_text = self
+            .element()
+            .is_some_and(|e| RAW_TEXT_ELEMENTS.contains(&e.tag_name.as_str()));
+        let mut html = String::new();
+        for child in &self.children {
+            serialize(child, raw_text, &mut html);
+        }
+        html
+    }
+}
+
+// Markup that parses back into the same tree: attributes are written in name
+// order, void elements get no end tag and raw text is left unescaped.
+fn serialize(node: &Node, raw_text: bool, html: &mut String) {
+    match node.node_type {
+        NodeType::Text(ref t) if raw_text => html.push_str(t),
+        NodeType::Text(ref t) => html.push_str(&escape(t, false)),
+        NodeType::Comment(ref c) => {
+            html.push_str("<!--");
+            html.push_str(c);
+            html.push_str("-->");
+        }
+        NodeType::Element(ref e) => {
+            html.push('<');
+            html.push_str(&e.tag_name);
+            let mut attributes: Vec<_> = e.attributes.iter().collect();
+            attributes.sort();
+            for (name, value) in attributes {
+                html.push_str(&format!(" {}=\"{}\"", name, escape(value, true)));
+            }
+            html.push('>');
+            if VOID_ELEMENTS.contains(&e.tag_name.as_str()) {
+                return;
+            }
+            html.push_str(&node.inner_html());
+            html.push_str("</");
+            html.push_str(&e.tag_name);
+            html.push('>');
+        }
+    }
+}
+
+fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '\u{a0}' => escaped.push_str("&nbsp;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '<' if !attribute => escaped.push_str("&lt;"),
+            '>' if !attribute => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
 }
 
 impl fmt::Debug for Node {
@@ -74,20 +339,104 @@ impl fmt::Debug for ElementData {
     }
 }
 
-fn pretty_print(n: &Node, indent_size: usize) {
-    let indent = (0..indent_size).map(|_| " ").collect::<String>();
-    match n.node_type {
-        NodeType::Element(ref e) => println!("{}{:?}", indent, e),
-        NodeType::Text(ref t) => println!("{}{}", indent, t),
-        NodeType::Comment(ref c) => println!("{}<-!---{}---->", indent, c),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html_parser::HtmlParser;
+
+    fn parse(html: &str) -> Node {
+        HtmlParser::new(html).parse_nodes().remove(0)
+    }
+
+    fn ids(nodes: Vec<&Node>) -> Vec<&str> {
+        nodes
+            .iter()
+            .map(|n| n.element().and_then(|e| e.get_id()).map_or("", |s| s))
+            .collect()
     }
 
-    for child in n.children.iter() {
-        pretty_print(&child, indent_size + 2);
+    #[test]
+    fn query_selectors_in_document_order() {
+        let root = parse(
+            "<div id=root><ul id=list><li id=a class=item>a</li><li id=b>b</li>\
+             <li id=c class='item last'>c</li></ul><p id=p><span id=s class=item>x</span></p></div>",
+        );
+
+        assert_eq!(ids(root.query_selector_all(".item")), ["a", "c", "s"]);
+        assert_eq!(ids(root.query_selector_all("ul > li + li")), ["b", "c"]);
+        assert_eq!(
+            ids(root.query_selector_all("#root .item, p")),
+            ["a", "c", "p", "s"]
+        );
+        assert_eq!(ids(root.query_selector_all("ul ~ p span")), ["s"]);
+        assert!(root.query_selector_all("li:hover").is_empty());
+
+        let first = root.query_selector("li.item.last").unwrap();
+        assert_eq!(first.element().unwrap().get_id().unwrap(), "c");
+        assert!(root.query_selector("table").is_none());
+
+        assert_eq!(ids(root.get_elements_by_tag_name("LI")), ["a", "b", "c"]);
+        assert_eq!(root.get_elements_by_tag_name("*").len(), 6);
+    }
+
+    #[test]
+    fn node_ids_follow_document_order() {
+        let root = parse("<div id=root><p id=a>x<!-- c --></p>y<p id=b></p></div>");
+        let b = root.query_selector("#b").unwrap();
+        assert_eq!(root.id_of(b), Some(5));
+        assert!(std::ptr::eq(root.get(5).unwrap(), b));
+        assert_eq!(root.get(4).unwrap().text_content(), "y");
+        assert!(matches!(
+            root.get(3).unwrap().node_type,
+            NodeType::Comment(_)
+        ));
+        assert!(root.get(6).is_none());
+
+        let paths = root.paths();
+        assert_eq!(paths.len(), 6);
+        assert_eq!(paths[5], [2]);
+        assert!(std::ptr::eq(root.at_path(&paths[5]).unwrap(), b));
+        assert_eq!(root.at_path(&paths[2]).unwrap().text_content(), "x");
+
+        let mut root = root;
+        root.at_path_mut(&paths[1])
+            .unwrap()
+            .element_mut()
+            .unwrap()
+            .set_attribute("id", "z");
+        assert_eq!(ids(root.query_selector_all("p")), ["z", "b"]);
+    }
+
+    #[test]
+    fn attributes_and_text_content() {
+        let mut root = parse("<p title=\"t\">Hello <b>big</b> <!-- no --> world</p>");
+        assert_eq!(root.text_content(), "Hello big  world");
+
+        let p = root.element_mut().unwrap();
+        assert!(p.has_attribute("title"));
+        p.set_attribute("Data-X", "1");
+        assert_eq!(p.get_attribute("data-x").map(String::as_str), Some("1"));
+        assert!(p.has_attribute("DATA-X"));
+        assert_eq!(p.get_attribute("Title").map(String::as_str), Some("t"));
+        assert_eq!(p.remove_attribute("title"), Some("t".to_string()));
+        assert!(!p.has_attribute("title"));
+        assert_eq!(p.attributes().len(), 1);
+        assert_eq!(p.remove_attribute("Data-X"), Some("1".to_string()));
+        assert!(p.attributes().is_empty());
     }
 
-    match n.node_type {
-        NodeType::Element(ref e) => println!("{}</{}>", indent, e.tag_name),
-        _ => {}
+    #[test]
+    fn serializes_back_to_markup() {
+        let html = "<div title=\"a &amp; &quot;b&quot;\" class=\"x\">1 &lt; 2<br>\
+                    <script>if (a < b) {}</script><!-- c --></div>";
+        let root = parse(html);
+        let expected = "<div class=\"x\" title=\"a &amp; &quot;b&quot;\">1 &lt; 2<br>\
+                        <script>if (a < b) {}</script><!-- c --></div>";
+        assert_eq!(root.to_html(), expected);
+        assert_eq!(parse(&root.to_html()).to_html(), expected);
+        assert_eq!(
+            root.inner_html(),
+            "1 &lt; 2<br><script>if (a < b) {}</script><!-- c -->"
+        );
     }
 }
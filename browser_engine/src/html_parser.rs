@@ -2,59 +2,102 @@ use crate::dom::{AttrMap, ElementData, Node, NodeType};
 use std::iter::Peekable;
 use std::str::Chars;
 
+pub(crate) const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+// content is taken verbatim up to the matching end tag
+pub(crate) const RAW_TEXT_ELEMENTS: &[&str] =
+    &["iframe", "noembed", "noframes", "script", "style", "xmp"];
+
+// like raw text, but character references are still decoded
+const ESCAPABLE_RAW_TEXT_ELEMENTS: &[&str] = &["textarea", "title"];
+
+// start tags that close an open <p>
+const CLOSES_P: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+const SCOPE_BOUNDARIES: &[&str] = &[
+    "applet", "button", "caption", "html", "marquee", "object", "table", "td", "template", "th",
+];
+
 pub struct HtmlParser<'a> {
     chars: Peekable<Chars<'a>>,
-    node_q: Vec<String>,
+    document: Vec<Node>,
+    open_elements: Vec<(ElementData, Vec<Node>)>,
 }
 
 impl<'a> HtmlParser<'a> {
     pub fn new(full_html: &str) -> HtmlParser {
         HtmlParser {
             chars: full_html.chars().peekable(),
-            node_q: Vec::new(),
+            document: Vec::new(),
+            open_elements: Vec::new(),
         }
     }
 
     pub fn parse_nodes(&mut self) -> Vec<Node> {
-        let mut nodes = Vec::new();
         while self.chars.peek().is_some() {
-            self.consume_while(char::is_whitespace);
-            if self.chars.peek().map_or(false, |c| *c == '<') {
+            if self.at_markup() {
                 self.chars.next();
-                if self.chars.peek().map_or(false, |c| *c == '/') {
-                    self.consume_while(char::is_whitespace);
-                    let close_tag_name = self.consume_while(is_valid_tag_name);
-                    self.consume_while(|x| x != '>');
-                    self.chars.next();
-                    self.node_q.push(close_tag_name);
-                    break;
-                } else if self.chars.peek().map_or(false, |c| *c == '!') {
-                    self.chars.next();
-                    nodes.push(self.parse_comment_node());
-                } else {
-                    let mut node = self.parse_node();
-                    let insert_index = nodes.len();
-                    match node.node_type {
-                        NodeType::Element(ref e) => {
-                            if self.node_q.len() > 0 {
-                                let assumed_tag = self.node_q.remove(0);
-
-                                if e.tag_name != assumed_tag {
-                                    nodes.append(&mut node.children);
-                                    self.node_q.insert(0, assumed_tag);
-                                }
-                            }
+                match self.chars.peek() {
+                    Some('/') => self.parse_end_tag(),
+                    Some('!') => {
+                        self.chars.next();
+                        if self.starts_with_ignore_case("doctype") {
+                            self.consume_while(|c| c != '>');
+                            self.chars.next();
+                        } else {
+                            let comment = self.parse_comment_node();
+                            self.insert(comment);
                         }
-                        _ => {}
                     }
-                    nodes.insert(insert_index, node);
+                    Some('?') => {
+                        let comment = self.parse_bogus_comment();
+                        self.insert(comment);
+                    }
+                    _ => self.parse_start_tag(),
                 }
             } else {
-                nodes.push(self.parse_text_node())
+                let text = self.parse_text_node();
+                self.insert(text);
             }
         }
 
-        nodes
+        while !self.open_elements.is_empty() {
+            self.close_current();
+        }
+        std::mem::take(&mut self.document)
     }
 
     fn consume_while<F>(&mut self, condition: F) -> String
@@ -62,48 +105,205 @@ impl<'a> HtmlParser<'a> {
         F: Fn(char) -> bool,
     {
         let mut result = String::new();
-        while self.chars.peek().map_or(false, |c| condition(*c)) {
+        while self.chars.peek().is_some_and(|c| condition(*c)) {
             result.push(self.chars.next().unwrap());
         }
 
         result
     }
 
-    fn parse_node(&mut self) -> Node {
-        let tagname = self.consume_while(is_valid_tag_name);
+    fn starts_with_ignore_case(&self, s: &str) -> bool {
+        let mut chars = self.chars.clone();
+        s.chars()
+            .all(|c| chars.next().is_some_and(|x| x.eq_ignore_ascii_case(&c)))
+    }
+
+    // a `<` only opens markup when followed by a tag name, `/`, `!` or `?`;
+    // otherwise it is plain text
+    fn at_markup(&self) -> bool {
+        let mut chars = self.chars.clone();
+        chars.next() == Some('<')
+            && chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!' || c == '?')
+    }
+
+    fn insert(&mut self, node: Node) {
+        let (parent, siblings) = match self.open_elements.last_mut() {
+            Some((element, children)) => (Some(element.tag_name.as_str()), children),
+            None => (None, &mut self.document),
+        };
+
+        if let NodeType::Text(ref text) = node.node_type {
+            // inter-element white space outside of <body> carries no content
+            if text.trim().is_empty() && matches!(parent, None | Some("html") | Some("head")) {
+                return;
+            }
+            if let Some(Node {
+                node_type: NodeType::Text(ref mut previous),
+                ..
+            }) = siblings.last_mut()
+            {
+                if previous.ends_with(' ') {
+                    previous.push_str(text.strip_prefix(' ').unwrap_or(text));
+                } else {
+                    previous.push_str(text);
+                }
+                return;
+            }
+        }
+        siblings.push(node);
+    }
+
+    fn close_current(&mut self) {
+        if let Some((element, children)) = self.open_elements.pop() {
+            self.insert(Node::new(NodeType::Element(element), children));
+        }
+    }
+
+    fn close_until(&mut self, index: usize) {
+        while self.open_elements.len() > index {
+            self.close_current();
+        }
+    }
+
+    fn in_scope(&self, tag_names: &[&str], boundaries: &[&str]) -> Option<usize> {
+        for (i, (element, _)) in self.open_elements.iter().enumerate().rev() {
+            if tag_names.contains(&element.tag_name.as_str()) {
+                return Some(i);
+            }
+            if boundaries.contains(&element.tag_name.as_str()) {
+                return None;
+            }
+        }
+        None
+    }
+
+    // elements whose end tag may be omitted get closed by the start tag of
+    // a following sibling
+    fn close_implied(&mut self, tag_name: &str) {
+        if CLOSES_P.contains(&tag_name) {
+            if let Some(i) = self.in_scope(&["p"], SCOPE_BOUNDARIES) {
+                self.close_until(i);
+            }
+        }
+
+        let implied = match tag_name {
+            "li" => Some((&["li"][..], &["ol", "ul"][..])),
+            "dt" | "dd" => Some((&["dt", "dd"][..], &["dl"][..])),
+            "option" => Some((&["option"][..], &["select", "datalist", "optgroup"][..])),
+            "tr" => Some((
+                &["tr", "td", "th"][..],
+                &["table", "tbody", "thead", "tfoot"][..],
+            )),
+            "td" | "th" => Some((&["td", "th"][..], &["tr", "table"][..])),
+            _ => None,
+        };
+        if let Some((tag_names, boundaries)) = implied {
+            if let Some(i) = self.in_scope(tag_names, boundaries) {
+                self.close_until(i);
+            }
+        }
+    }
+
+    fn parse_start_tag(&mut self) {
+        let tag_name = self.consume_while(is_valid_tag_name).to_ascii_lowercase();
         let attributes = self.parse_attributes();
-        let ele = ElementData::new(tagname, attributes);
-        let children = self.parse_nodes();
-        Node::new(NodeType::Element(ele), children)
+        let ele = ElementData::new(tag_name, attributes);
+
+        self.close_implied(&ele.tag_name);
+
+        let tag_name = ele.tag_name.as_str();
+        if VOID_ELEMENTS.contains(&tag_name) {
+            self.insert(Node::new(NodeType::Element(ele), Vec::new()));
+        } else if RAW_TEXT_ELEMENTS.contains(&tag_name)
+            || ESCAPABLE_RAW_TEXT_ELEMENTS.contains(&tag_name)
+        {
+            let mut text = self.parse_raw_text(tag_name);
+            if ESCAPABLE_RAW_TEXT_ELEMENTS.contains(&tag_name) {
+                text = decode_character_references(&text);
+            }
+            let children = if text.is_empty() {
+                Vec::new()
+            } else {
+                vec![Node::new(NodeType::Text(text), Vec::new())]
+            };
+            self.insert(Node::new(NodeType::Element(ele), children));
+        } else {
+            self.open_elements.push((ele, Vec::new()));
+        }
+    }
+
+    fn parse_end_tag(&mut self) {
+        self.chars.next();
+        let tag_name = self.consume_while(is_valid_tag_name).to_ascii_lowercase();
+        self.consume_while(|c| c != '>');
+        self.chars.next();
+
+        // an end tag without a matching open element is dropped, one that
+        // matches further up closes everything opened after it
+        if let Some(i) = self
+            .open_elements
+            .iter()
+            .rposition(|(e, _)| e.tag_name == tag_name)
+        {
+            self.close_until(i);
+        }
+    }
+
+    fn parse_raw_text(&mut self, tag_name: &str) -> String {
+        let end_tag = format!("</{}", tag_name);
+        let mut text = String::new();
+        while self.chars.peek().is_some() {
+            if self.starts_with_ignore_case(&end_tag) {
+                let mut chars = self.chars.clone();
+                let after = chars.nth(end_tag.chars().count());
+                if after.is_none_or(|c| c.is_whitespace() || c == '/' || c == '>') {
+                    self.consume_while(|c| c != '>');
+                    self.chars.next();
+                    break;
+                }
+            }
+            text.push(self.chars.next().unwrap());
+        }
+        text
     }
 
     fn parse_attributes(&mut self) -> AttrMap {
         let mut attributes = AttrMap::new();
-        while self.chars.peek().map_or(false, |c| *c != '>') {
-            self.consume_while((char::is_whitespace));
-            let name = self
-                .consume_while(|c| is_valid_attr_name(c))
-                .to_ascii_lowercase();
+        loop {
             self.consume_while(char::is_whitespace);
-            let value = if self.chars.peek().map_or(false, |c| *c == '=') {
+            match self.chars.peek() {
+                None => break,
+                Some('>') => {
+                    self.chars.next();
+                    break;
+                }
+                _ => {}
+            }
+
+            let name = self.consume_while(is_valid_attr_name).to_ascii_lowercase();
+            if name.is_empty() {
+                // a stray `/`, quote or `=`: skip it so the loop always advances
+                self.chars.next();
+                continue;
+            }
+
+            self.consume_while(char::is_whitespace);
+            let value = if self.chars.peek() == Some(&'=') {
                 self.chars.next();
                 self.consume_while(char::is_whitespace);
-                let s = self.parse_attr_value();
-                self.consume_while(|c| !c.is_whitespace() && c != '>');
-                self.consume_while(char::is_whitespace);
-                s
+                decode_character_references(&self.parse_attr_value())
             } else {
                 "".to_string()
             };
-            attributes.insert(name, value);
+            attributes.entry(name).or_insert(value);
         }
-        self.chars.next();
         attributes
     }
 
     fn parse_attr_value(&mut self) -> String {
-        self.consume_while(char::is_whitespace);
-        let result = match self.chars.peek() {
+        match self.chars.peek() {
             Some(&c) if c == '"' || c == '\'' => {
                 self.chars.next();
                 let ret = self.consume_while(|x| x != c);
@@ -111,125 +311,157 @@ impl<'a> HtmlParser<'a> {
                 ret
             }
             _ => self.consume_while(is_valid_attr_value),
-        };
-        result
+        }
     }
 
+    // called after `<!`; `<!-->` and `<!--->` are empty comments and an
+    // unterminated comment runs to the end of the input
     fn parse_comment_node(&mut self) -> Node {
-        let mut comment_content = String::new();
-
-        if self.chars.peek().map_or(false, |c| *c == '-') {
-            self.chars.next();
-            if self.chars.peek().map_or(false, |c| *c == '-') {
-                self.chars.next();
-            } else {
-                self.consume_while(|c| c != '>');
-                return Node::new(NodeType::Comment(comment_content), Vec::new());
-            }
-        } else {
-            self.consume_while(|c| c != '>');
-            return Node::new(NodeType::Comment(comment_content), Vec::new());
+        if !self.starts_with_ignore_case("--") {
+            return self.parse_bogus_comment();
         }
+        self.chars.next();
+        self.chars.next();
 
-        if self.chars.peek().map_or(false, |c| *c == '>') {
+        let mut comment_content = String::new();
+        if self.starts_with_ignore_case(">") || self.starts_with_ignore_case("->") {
+            self.consume_while(|c| c == '-');
             self.chars.next();
             return Node::new(NodeType::Comment(comment_content), Vec::new());
         }
 
-        if self.chars.peek().map_or(false, |c| *c == '-') {
-            self.chars.next();
-            if self.chars.peek().map_or(false, |c| *c == '>') {
-                self.chars.next();
-                return Node::new(NodeType::Comment(comment_content), Vec::new());
-            } else {
-                comment_content.push('-');
-            }
-        }
-
         while self.chars.peek().is_some() {
-            comment_content.push_str(&self.consume_while(|c| c != '<' && c != '-'));
-            if self.chars.peek().map_or(false, |c| *c == '<') {
-                self.chars.next();
-                if self.chars.peek().map_or(false, |c| *c == '!') {
-                    self.chars.next();
-                    if self.chars.peek().map_or(false, |c| *c == '-') {
-                        self.chars.next();
-                        if self.chars.peek().map_or(false, |c| *c == '-') {
-                            self.consume_while(|c| c != '>');
-
-                            return Node::new(NodeType::Comment(String::from("")), Vec::new());
-                        } else {
-                            comment_content.push_str("<!-");
-                        }
-                    } else if self.chars.peek().map_or(false, |c| *c == ' ') {
-                        self.chars.next();
-                        if self.chars.peek().map_or(false, |c| *c == '-') {
-                            self.chars.next();
-                            if self.chars.peek().map_or(false, |c| *c == '-') {
-                                self.chars.next();
-                                if self.chars.peek().map_or(false, |c| *c == '-') {
-                                    self.chars.next();
-                                    if self.chars.peek().map_or(false, |c| *c == '>') {
-                                        self.chars.next();
-                                        return Node::new(
-                                            NodeType::Comment(String::from("")),
-                                            Vec::new(),
-                                        );
-                                    } else {
-                                        comment_content.push_str("<! --");
-                                    }
-                                } else {
-                                    comment_content.push_str("<! -");
-                                }
-                            } else {
-                                comment_content.push_str("<! ");
-                            }
-                        }
-                    } else {
-                        comment_content.push_str("<!");
-                    }
-                } else {
-                    comment_content.push('<');
-                }
-            } else if self.chars.peek().map_or(false, |c| *c == '-') {
+            if self.starts_with_ignore_case("-->") {
+                self.consume_while(|c| c == '-');
                 self.chars.next();
-                if self.chars.peek().map_or(false, |c| *c == '-') {
-                    self.chars.next();
-                    if self.chars.peek().map_or(false, |c| *c == '>') {
-                        self.chars.next();
-                        break;
-                    } else {
-                        comment_content.push_str("--");
-                    }
-                } else {
-                    comment_content.push('-');
-                }
+                break;
             }
+            comment_content.push(self.chars.next().unwrap());
         }
 
         Node::new(NodeType::Comment(comment_content), Vec::new())
     }
 
+    fn parse_bogus_comment(&mut self) -> Node {
+        let comment_content = self.consume_while(|c| c != '>');
+        self.chars.next();
+        Node::new(NodeType::Comment(comment_content), Vec::new())
+    }
+
     fn parse_text_node(&mut self) -> Node {
         let mut text_content = String::new();
-        while self.chars.peek().map_or(false, |c| *c != '<') {
+        while self.chars.peek().is_some() && !self.at_markup() {
             let whitespace = self.consume_while(char::is_whitespace);
-            if whitespace.len() > 0 {
+            if !whitespace.is_empty() {
                 text_content.push(' ');
             }
             let text_part = self.consume_while(|x| !x.is_whitespace() && x != '<');
             text_content.push_str(&text_part);
+            if !self.at_markup() && self.chars.peek() == Some(&'<') {
+                text_content.push('<');
+                self.chars.next();
+            }
+        }
+        Node::new(
+            NodeType::Text(decode_character_references(&text_content)),
+            vec![],
+        )
+    }
+}
+
+fn decode_character_references(raw: &str) -> String {
+    let mut result = String::new();
+    let mut rest = raw;
+    while let Some(i) = rest.find('&') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        match parse_character_reference(rest) {
+            Some((c, len)) => {
+                result.push(c);
+                rest = &rest[len..];
+            }
+            None => result.push('&'),
         }
-        Node::new(NodeType::Text(text_content), vec![])
     }
+    result.push_str(rest);
+    result
+}
+
+// `s` starts right after the `&`; returns the character and how many bytes
+// of `s` it took
+fn parse_character_reference(s: &str) -> Option<(char, usize)> {
+    if let Some(number) = s.strip_prefix('#') {
+        let (radix, prefix) = match number.chars().next() {
+            Some('x') | Some('X') => (16, 2),
+            _ => (10, 1),
+        };
+        let digits_len = s[prefix..]
+            .find(|c: char| !c.is_digit(radix))
+            .unwrap_or(s.len() - prefix);
+        if digits_len == 0 {
+            return None;
+        }
+        let digits = &s[prefix..prefix + digits_len];
+        let mut len = prefix + digits_len;
+        if s[len..].starts_with(';') {
+            len += 1;
+        }
+        let c = u32::from_str_radix(digits, radix)
+            .ok()
+            .filter(|&n| n != 0)
+            .and_then(char::from_u32)
+            .unwrap_or('\u{FFFD}');
+        return Some((c, len));
+    }
+
+    let name_len = s
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(s.len());
+    let name = &s[..name_len];
+    let terminated = s[name_len..].starts_with(';');
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        _ if !terminated => return None,
+        "apos" => '\'',
+        "nbsp" => '\u{A0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "middot" => '·',
+        "bull" => '•',
+        "deg" => '°',
+        "plusmn" => '±',
+        "times" => '×',
+        "divide" => '÷',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "sect" => '§',
+        "para" => '¶',
+        _ => return None,
+    };
+    Some((c, name_len + if terminated { 1 } else { 0 }))
 }
 
 fn is_valid_tag_name(ch: char) -> bool {
-    ch.is_digit(36)
+    ch.is_ascii_alphanumeric() || ch == '-'
 }
 
 fn is_valid_attr_name(c: char) -> bool {
-    !is_excluded_name(c) && !is_control(c)
+    !is_excluded_name(c) && !is_control(c) && !c.is_whitespace()
 }
 
 fn is_control(ch: char) -> bool {
@@ -251,6 +483,94 @@ fn is_excluded_name(c: char) -> bool {
 fn is_valid_attr_value(c: char) -> bool {
     match c {
         ' ' | '"' | '\'' | '=' | '<' | '>' | '`' => false,
-        _ => true,
+        c => !c.is_whitespace(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(nodes: &[Node]) -> String {
+        let mut out = String::new();
+        for node in nodes {
+            match node.node_type {
+                NodeType::Element(ref e) => {
+                    out.push_str(&format!("<{}>", e.tag_name));
+                    out.push_str(&dump(&node.children));
+                    out.push_str(&format!("</{}>", e.tag_name));
+                }
+                NodeType::Text(ref t) => out.push_str(t),
+                NodeType::Comment(ref c) => out.push_str(&format!("<!--{}-->", c)),
+            }
+        }
+        out
+    }
+
+    fn parse(html: &str) -> String {
+        dump(&HtmlParser::new(html).parse_nodes())
+    }
+
+    #[test]
+    fn nested_elements() {
+        assert_eq!(
+            parse("<div><p>hi</p><span class=\"a b\">x</span></div>"),
+            "<div><p>hi</p><span>x</span></div>"
+        );
+    }
+
+    #[test]
+    fn character_references() {
+        assert_eq!(
+            parse("<p title='&lt;&#x41;&#66;'>a &amp; b &copy; &bogus; &#0; &amp</p>"),
+            "<p>a & b © &bogus; \u{FFFD} &</p>"
+        );
+        let nodes = HtmlParser::new("<p id='&lt;&#x41;&#66;'></p>").parse_nodes();
+        match nodes[0].node_type {
+            NodeType::Element(ref e) => assert_eq!(e.get_id().unwrap(), "<AB"),
+            _ => panic!("expected an element"),
+        }
+    }
+
+    #[test]
+    fn doctype_void_and_raw_text_elements() {
+        assert_eq!(
+            parse("<!DOCTYPE html>\n<head>\n  <meta charset=utf-8>\n  <style>p > a { }</style>\n</head>"),
+            "<head><meta></meta><style>p > a { }</style></head>"
+        );
+        assert_eq!(
+            parse("<p>a<br>b<img src=x.png/>c</p>"),
+            "<p>a<br></br>b<img></img>c</p>"
+        );
+        assert_eq!(
+            parse("<script>if (a < b && c) { x = '</div>'; }</SCRIPT><p>x</p>"),
+            "<script>if (a < b && c) { x = '</div>'; }</script><p>x</p>"
+        );
+    }
+
+    #[test]
+    fn implied_end_tags() {
+        assert_eq!(
+            parse("<p>one<p>two<div>three</div>"),
+            "<p>one</p><p>two</p><div>three</div>"
+        );
+        assert_eq!(
+            parse("<ul><li>a<li>b<ul><li>c</ul></ul>"),
+            "<ul><li>a</li><li>b<ul><li>c</li></ul></li></ul>"
+        );
+    }
+
+    #[test]
+    fn recovers_from_unclosed_and_mismatched_tags() {
+        assert_eq!(parse("<div><b>x</div>y</b>"), "<div><b>x</b></div>y");
+        assert_eq!(parse("<div><span>x"), "<div><span>x</span></div>");
+        assert_eq!(parse("a < b </p> c"), "a < b c");
+        assert_eq!(parse("<!-- a -- b --><!---->"), "<!-- a -- b --><!---->");
+
+        for html in [
+            "<", "</", "<!", "<!--", "<a", "<a b='", "<a b=c", "&#", "&#x;", "<p/>",
+        ] {
+            HtmlParser::new(html).parse_nodes();
+        }
     }
 }
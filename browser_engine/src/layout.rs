@@ -1,14 +1,65 @@
-use std::fmt;
+use std::collections::HashMap;
+use std::{fmt, mem};
 
 use crate::css::{Unit, Value};
-use crate::style::{Display, StyledNode};
+use crate::dom::NodeId;
+use crate::style::{Display, Position, StyleIndex, StyledNode};
 
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+// Text is measured as if it were set in a monospace bitmap font: every glyph
+// advances by the same fraction of the font size.
+pub const GLYPH_ADVANCE: f32 = 0.5;
+const NORMAL_LINE_HEIGHT: f32 = 1.2;
+// distance from the top of the em box to the alphabetic baseline
+pub const GLYPH_ASCENT: f32 = 0.8;
+
+// A box owns its geometry and refers to the node it was generated for by
+// id, so the DOM and style tree can change while a layout is kept around.
 #[derive(Clone)]
-pub struct LayoutBox<'a> {
+pub struct LayoutBox {
     pub dimensions: Dimensions,
+    pub box_type: BoxType,
+    // the DOM node this box was generated for; anonymous boxes have the
+    // node of the box they were created in
+    pub node: NodeId,
+    position: Position,
+    z_index: i32,
+    pub children: Vec<LayoutBox>,
+    pub fragments: Vec<TextFragment>,
+    // the last layout of this subtree, to move into place instead of laying
+    // it out again
+    reuse: Option<Vec<Snapshot>>,
+    laid_out: Option<LayoutInput>,
+}
+
+// Geometry kept from the previous layout. A box whose node and descendants
+// kept their computed values, laid out against a containing block of the
+// same width, ends up the same apart from where it is placed.
+#[derive(Default)]
+pub struct LayoutCache {
+    // every box of the last tree in pre-order
+    boxes: Vec<Snapshot>,
+    // where each node's box starts in `boxes`
+    entries: HashMap<NodeId, usize>,
+}
+
+#[derive(Clone)]
+struct Snapshot {
     box_type: BoxType,
-    pub styled_node: &'a StyledNode<'a>,
-    pub children: Vec<LayoutBox<'a>>,
+    dimensions: Dimensions,
+    fragments: Vec<TextFragment>,
+    laid_out: Option<LayoutInput>,
+    // the number of boxes in this subtree, this one included
+    len: usize,
+}
+
+// what `layout` was called with, and where it left the content box before
+// the parent moved it
+#[derive(Clone, Copy)]
+struct LayoutInput {
+    width: f32,
+    origin: (f32, f32),
+    position: (f32, f32),
 }
 
 #[derive(Clone, Copy, Default)]
@@ -17,7 +68,13 @@ pub struct Dimensions {
     padding: EdgeSizes,
     pub border: EdgeSizes,
     margin: EdgeSizes,
-    current: Rectangle,
+}
+
+// the part of a text node that ended up on one line box
+#[derive(Clone)]
+pub struct TextFragment {
+    pub text: String,
+    pub rect: Rectangle,
 }
 
 #[derive(Clone, Copy, Default)]
@@ -41,212 +98,1309 @@ pub enum BoxType {
     Block,
     Inline,
     InlineBlock,
+    Flex,
     Anonymous,
 }
 
-impl<'a> LayoutBox<'a> {
-    pub fn new(box_type: BoxType, styled_node: &'a StyledNode) -> LayoutBox<'a> {
+impl LayoutBox {
+    // anonymous boxes share their parent's style but are never positioned
+    pub fn new(box_type: BoxType, styled_node: &StyledNode) -> LayoutBox {
+        let mut layout_box = LayoutBox::anonymous(styled_node.id());
+        if !matches!(box_type, BoxType::Anonymous) {
+            layout_box.position = styled_node.get_position();
+            // z-index: auto stacks like 0
+            layout_box.z_index = match styled_node.value("z-index") {
+                Some(Value::Other(z)) => z.parse().unwrap_or(0),
+                _ => 0,
+            };
+        }
+        layout_box.box_type = box_type;
+        layout_box
+    }
+
+    fn anonymous(node: NodeId) -> LayoutBox {
         LayoutBox {
-            box_type: box_type,
-            styled_node: styled_node,
+            box_type: BoxType::Anonymous,
+            node,
+            position: Position::Static,
+            z_index: 0,
             dimensions: Default::default(),
             children: Vec::new(),
+            fragments: Vec::new(),
+            reuse: None,
+            laid_out: None,
+        }
+    }
+
+    fn layout(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        let width = b_box.content.width;
+        let origin = (b_box.content.x, b_box.content.y + b_box.content.height);
+        if let Some(snapshots) = self.reuse.take() {
+            if snapshots[0].laid_out.is_some_and(|l| l.width == width) {
+                self.restore(&snapshots, origin);
+                return;
+            }
+        }
+
+        match self.box_type {
+            BoxType::Block => self.layout_block(styles, b_box),
+            BoxType::Inline => self.layout_block(styles, b_box),
+            BoxType::InlineBlock => self.layout_inline_block(styles, b_box),
+            BoxType::Flex => self.layout_flex(styles, b_box),
+            BoxType::Anonymous => self.layout_anonymous(styles, b_box),
+        }
+        self.laid_out = Some(LayoutInput {
+            width,
+            origin,
+            position: (self.dimensions.content.x, self.dimensions.content.y),
+        });
+    }
+
+    // Puts the subtree back the way its last `layout` left it, shifted to
+    // the new origin. The snapshots were taken after the parent had moved
+    // the box, so that move is undone first.
+    fn restore(&mut self, snapshots: &[Snapshot], origin: (f32, f32)) {
+        let first = &snapshots[0];
+        let laid_out = first.laid_out.unwrap();
+        let shift = (origin.0 - laid_out.origin.0, origin.1 - laid_out.origin.1);
+        let moved = (
+            laid_out.position.0 - first.dimensions.content.x + shift.0,
+            laid_out.position.1 - first.dimensions.content.y + shift.1,
+        );
+        self.restore_from(&mut snapshots.iter(), shift, moved);
+    }
+
+    fn restore_from<'s>(
+        &mut self,
+        snapshots: &mut impl Iterator<Item = &'s Snapshot>,
+        shift: (f32, f32),
+        moved: (f32, f32),
+    ) {
+        let snapshot = snapshots.next().unwrap();
+        self.dimensions = snapshot.dimensions;
+        self.dimensions.content.x += moved.0;
+        self.dimensions.content.y += moved.1;
+        self.fragments = snapshot.fragments.clone();
+        for fragment in &mut self.fragments {
+            fragment.rect.x += moved.0;
+            fragment.rect.y += moved.1;
+        }
+        // descendants keep their own inputs so they can be reused on their
+        // own next time
+        self.laid_out = snapshot.laid_out.map(|l| LayoutInput {
+            width: l.width,
+            origin: (l.origin.0 + shift.0, l.origin.1 + shift.1),
+            position: (l.position.0 + shift.0, l.position.1 + shift.1),
+        });
+        for child in &mut self.children {
+            child.restore_from(snapshots, shift, moved);
         }
     }
 
-    fn layout(&mut self, b_box: Dimensions) {
+    fn box_count(&self) -> usize {
+        1 + self.children.iter().map(|c| c.box_count()).sum::<usize>()
+    }
+
+    pub fn style<'s>(&self, styles: &StyleIndex<'s>) -> &'s StyledNode {
+        styles.get(self.node)
+    }
+
+    fn get_inline_container(&mut self) -> &mut LayoutBox {
         match self.box_type {
-            BoxType::Block => self.layout_block(b_box),
-            BoxType::Inline => self.layout_block(b_box),
-            BoxType::InlineBlock => self.layout_inline_block(b_box),
-            BoxType::Anonymous => {}
+            BoxType::Inline | BoxType::Anonymous => self,
+            BoxType::Block | BoxType::InlineBlock | BoxType::Flex => {
+                match self.children.last() {
+                    Some(LayoutBox {
+                        box_type: BoxType::Anonymous,
+                        ..
+                    }) => {}
+                    _ => self.children.push(LayoutBox::anonymous(self.node)),
+                }
+                self.children.last_mut().unwrap()
+            }
         }
     }
 
-    fn layout_inline_block(&mut self, b_box: Dimensions) {
-        self.calculate_inline_width(b_box);
-        self.calculate_inline_position(b_box);
-        self.layout_children();
-        self.calculate_height();
+    fn layout_inline_block(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        self.calculate_inline_width(styles, b_box);
+        self.calculate_inline_position(styles, b_box);
+        self.layout_children(styles);
+        self.calculate_height(styles);
     }
 
-    fn calculate_inline_width(&mut self, b_box: Dimensions) {
-        let s = self.styled_node;
+    fn calculate_inline_width(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        let s = self.style(styles);
         let d = &mut self.dimensions;
 
         d.content.width = get_absolute_num(s, b_box, "width").unwrap_or(0.0);
-        d.margin.left = s.num_or("margin-left", 0.0);
-        d.margin.left = s.num_or("margin-left", 0.0);
-        d.margin.right = s.num_or("margin-right", 0.0);
-        d.padding.left = s.num_or("padding-left", 0.0);
-        d.padding.right = s.num_or("padding-right", 0.0);
+        d.margin.left = get_absolute_num(s, b_box, "margin-left").unwrap_or(0.0);
+        d.margin.right = get_absolute_num(s, b_box, "margin-right").unwrap_or(0.0);
+        d.padding.left = get_absolute_num(s, b_box, "padding-left").unwrap_or(0.0);
+        d.padding.right = get_absolute_num(s, b_box, "padding-right").unwrap_or(0.0);
         d.border.left = s.num_or("border-left-width", 0.0);
         d.border.right = s.num_or("border-right-width", 0.0);
     }
 
-    fn calculate_inline_position(&mut self, b_box: Dimensions) {
-        let style = self.styled_node;
+    fn calculate_inline_position(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        let style = self.style(styles);
         let d = &mut self.dimensions;
 
-        d.margin.top = style.num_or("margin-top", 0.0);
-        d.margin.bottom = style.num_or("margin-bottom", 0.0);
+        d.margin.top = get_absolute_num(style, b_box, "margin-top").unwrap_or(0.0);
+        d.margin.bottom = get_absolute_num(style, b_box, "margin-bottom").unwrap_or(0.0);
         d.border.top = style.num_or("border-top-width", 0.0);
         d.border.bottom = style.num_or("border-bottom-width", 0.0);
-        d.padding.top = style.num_or("padding-top", 0.0);
-        d.padding.bottom = style.num_or("padding-bottom", 0.0);
+        d.padding.top = get_absolute_num(style, b_box, "padding-top").unwrap_or(0.0);
+        d.padding.bottom = get_absolute_num(style, b_box, "padding-bottom").unwrap_or(0.0);
 
-        d.content.x =
-            b_box.content.x + b_box.current.x + d.margin.left + d.border.left + d.padding.left;
+        d.content.x = b_box.content.x + d.margin.left + d.border.left + d.padding.left;
         d.content.y =
             b_box.content.height + b_box.content.y + d.margin.top + d.border.top + d.padding.top;
     }
 
-    fn layout_block(&mut self, b_box: Dimensions) {
-        self.calculate_width(b_box);
-        self.calculate_position(b_box);
-        self.layout_children();
-        self.calculate_height();
+    fn layout_block(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        self.calculate_width(styles, b_box);
+        self.calculate_position(styles, b_box);
+        self.layout_children(styles);
+        self.calculate_height(styles);
     }
 
-    fn calculate_width(&mut self, b_box: Dimensions) {
-        let style = self.styled_node;
+    // CSS 2.1 §10.3.3: width + horizontal margins, borders and paddings must
+    // add up to the containing block's width
+    fn calculate_width(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        let style = self.style(styles);
         let d = &mut self.dimensions;
-        let width = get_absolute_num(style, b_box, "width").unwrap_or(0.0);
-        let margin_l = style.value("margin-left");
-        let margin_r = style.value("margin-right");
+        let is_auto = |name| matches!(style.value(name), Some(Value::Other(s)) if s == "auto");
 
-        let margin_l_num = match margin_l {
-            Some(m) => match **m {
-                Value::Other(ref s) => s.parse().unwrap_or(0.0),
-                _ => 0.0,
-            },
-            None => 0.0,
-        };
-
-        let margin_r_num = match margin_r {
-            Some(m) => match **m {
-                Value::Other(ref s) => s.parse().unwrap_or(0.0),
-                _ => 0.0,
-            },
-            None => 0.0,
-        };
+        let width = get_absolute_num(style, b_box, "width");
+        let mut margin_l_auto = is_auto("margin-left");
+        let mut margin_r_auto = is_auto("margin-right");
+        let mut margin_l = get_absolute_num(style, b_box, "margin-left").unwrap_or(0.0);
+        let mut margin_r = get_absolute_num(style, b_box, "margin-right").unwrap_or(0.0);
 
         d.border.left = style.num_or("border-left-width", 0.0);
         d.border.right = style.num_or("border-right-width", 0.0);
-        d.padding.left = style.num_or("padding-left", 0.0);
-        d.padding.right = style.num_or("padding-right", 0.0);
+        d.padding.left = get_absolute_num(style, b_box, "padding-left").unwrap_or(0.0);
+        d.padding.right = get_absolute_num(style, b_box, "padding-right").unwrap_or(0.0);
 
-        let total = width
-            + margin_l_num
-            + margin_r_num
+        let total = width.unwrap_or(0.0)
+            + margin_l
+            + margin_r
             + d.border.left
             + d.border.right
             + d.padding.left
             + d.padding.right;
-
         let underflow = b_box.content.width - total;
 
-        match (width, margin_l, margin_r) {
-            (0.0, _, _) => {
-                if underflow >= 0.0 {
-                    d.content.width = underflow;
-                    d.margin.right = margin_r_num;
-                } else {
-                    d.margin.right = margin_r_num + underflow;
-                    d.content.width = width;
-                }
-                d.margin.left = margin_l_num;
+        // an overconstrained box can't have negative auto margins
+        if width.is_some() && underflow < 0.0 {
+            margin_l_auto = false;
+            margin_r_auto = false;
+        }
+
+        match (width, margin_l_auto, margin_r_auto) {
+            (Some(w), false, false) => {
+                d.content.width = w;
+                margin_r += underflow;
             }
-            (w, None, Some(_)) if w != 0.0 => {
-                d.margin.left = underflow;
-                d.margin.right = margin_r_num;
+            (Some(w), true, false) => {
                 d.content.width = w;
+                margin_l = underflow;
             }
-            (w, Some(_), None) if w != 0.0 => {
-                d.margin.right = underflow;
-                d.margin.left = margin_l_num;
+            (Some(w), false, true) => {
                 d.content.width = w;
+                margin_r = underflow;
             }
-            (w, None, None) if w != 0.0 => {
-                d.margin.left = underflow / 2.0;
-                d.margin.right = underflow / 2.0;
+            (Some(w), true, true) => {
                 d.content.width = w;
+                margin_l = underflow / 2.0;
+                margin_r = underflow / 2.0;
             }
-            (_, _, _) => {
-                d.margin.right = margin_r_num + underflow;
-                d.margin.left = margin_l_num;
-                d.content.width = width
+            // auto margins next to an auto width become 0
+            (None, _, _) => {
+                if underflow >= 0.0 {
+                    d.content.width = underflow;
+                } else {
+                    d.content.width = 0.0;
+                    margin_r += underflow;
+                }
             }
         }
+
+        d.margin.left = margin_l;
+        d.margin.right = margin_r;
     }
-    fn calculate_position(&mut self, b_box: Dimensions) {
-        let style = self.styled_node;
+    fn calculate_position(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        let style = self.style(styles);
         let d = &mut self.dimensions;
-        d.margin.top = style.num_or("margin-top", 0.0);
-        d.margin.bottom = style.num_or("margin-bottom", 0.0);
+        d.margin.top = get_absolute_num(style, b_box, "margin-top").unwrap_or(0.0);
+        d.margin.bottom = get_absolute_num(style, b_box, "margin-bottom").unwrap_or(0.0);
         d.border.top = style.num_or("border-top-width", 0.0);
         d.border.bottom = style.num_or("border-bottom-width", 0.0);
-        d.padding.top = style.num_or("padding-top", 0.0);
-        d.padding.bottom = style.num_or("padding-bottom", 0.0);
+        d.padding.top = get_absolute_num(style, b_box, "padding-top").unwrap_or(0.0);
+        d.padding.bottom = get_absolute_num(style, b_box, "padding-bottom").unwrap_or(0.0);
 
         d.content.x = b_box.content.x + d.margin.left + d.border.left + d.padding.left;
         d.content.y =
             b_box.content.height + b_box.content.y + d.margin.top + d.border.top + d.padding.top;
     }
 
-    fn calculate_height(&mut self) {
-        self.styled_node.value("height").map_or((), |h| match **h {
-            Value::Length(n, _) => self.dimensions.content.height = n,
+    fn calculate_height(&mut self, styles: &StyleIndex) {
+        self.style(styles).value("height").map_or((), |h| match *h {
+            Value::Length(n, Unit::Px) => self.dimensions.content.height = n,
             _ => {}
         })
     }
 
-    fn layout_children(&mut self) {
+    fn layout_children(&mut self, styles: &StyleIndex) {
         let d = &mut self.dimensions;
-        let mut max_child_height = 0.0;
-        let mut prevBoxType = BoxType::Block;
         for child in &mut self.children {
-            match prevBoxType {
-                BoxType::InlineBlock => match child.box_type {
-                    BoxType::Block => {
-                        d.content.height += max_child_height;
-                        d.current.x = 0.0;
-                    }
-                    _ => {}
+            // remember where the box would have been as its static position
+            if child.is_out_of_flow() {
+                child.dimensions.content.x = d.content.x;
+                child.dimensions.content.y = d.content.y + d.content.height;
+                continue;
+            }
+            child.layout(styles, *d);
+            d.content.height += child.dimensions.margin_box().height;
+        }
+    }
+
+    fn layout_flex(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        self.calculate_width(styles, b_box);
+        self.calculate_position(styles, b_box);
+        self.layout_flex_items(styles);
+        self.calculate_height(styles);
+    }
+
+    // A single-line flex container (CSS Flexbox §9): every item gets a base
+    // size along the main axis, the free space is shared out by flex-grow or
+    // taken back by flex-shrink, and the items are then placed by
+    // justify-content and align-items.
+    fn layout_flex_items(&mut self, styles: &StyleIndex) {
+        let style = self.style(styles);
+        let container = self.dimensions;
+        let (row, reverse) = flex_direction(style);
+        let gap = flex_gap(style, container, row);
+        let stretch = matches!(
+            keyword(style, "align-items"),
+            None | Some("stretch" | "normal")
+        );
+        let definite_main = if row {
+            Some(container.content.width)
+        } else {
+            definite_height(style)
+        };
+
+        let mut flow = Vec::with_capacity(self.children.len());
+        for (i, child) in self.children.iter_mut().enumerate() {
+            if child.is_out_of_flow() {
+                child.dimensions.content.x = container.content.x;
+                child.dimensions.content.y = container.content.y;
+            } else {
+                flow.push(i);
+            }
+        }
+
+        let mut items = Vec::with_capacity(flow.len());
+        for &i in &flow {
+            let child = &mut self.children[i];
+            child.calculate_edges(styles, container);
+            let d = child.dimensions;
+            let horizontal = d.margin.left
+                + d.border.left
+                + d.padding.left
+                + d.padding.right
+                + d.border.right
+                + d.margin.right;
+            let vertical = d.margin.top
+                + d.border.top
+                + d.padding.top
+                + d.padding.bottom
+                + d.border.bottom
+                + d.margin.bottom;
+            let (main_edges, cross_edges) = if row {
+                (horizontal, vertical)
+            } else {
+                (vertical, horizontal)
+            };
+
+            child.dimensions.content.x =
+                container.content.x + d.margin.left + d.border.left + d.padding.left;
+            child.dimensions.content.y =
+                container.content.y + d.margin.top + d.border.top + d.padding.top;
+
+            // a column's items are laid out at their cross size up front, so
+            // their content height can serve as the base size
+            if !row {
+                let width = match child.explicit_size(styles, "width", container) {
+                    Some(w) => w,
+                    None if stretch => container.content.width - cross_edges,
+                    None => (child.max_content_width(styles) - cross_edges)
+                        .min(container.content.width - cross_edges),
+                };
+                child.dimensions.content.width = width.max(0.0);
+                child.layout_contents(styles);
+            }
+
+            let base = match child.flex_basis(styles, definite_main) {
+                Some(basis) => basis,
+                None if row => match child.explicit_size(styles, "width", container) {
+                    Some(w) => w,
+                    None => child.max_content_width(styles) - main_edges,
                 },
-                _ => {}
+                None => child.dimensions.content.height,
+            };
+            items.push(FlexItem {
+                base: base.max(0.0),
+                main: base.max(0.0),
+                main_edges,
+                cross_edges,
+                grow: child.flex_factor(styles, "flex-grow", 0.0),
+                shrink: child.flex_factor(styles, "flex-shrink", 1.0),
+            });
+        }
+
+        let gaps = gap * items.len().saturating_sub(1) as f32;
+        let outer_total =
+            |items: &[FlexItem]| items.iter().map(|i| i.main + i.main_edges).sum::<f32>() + gaps;
+        let free = definite_main.map_or(0.0, |m| m - outer_total(&items));
+        if free > 0.0 {
+            let total_grow: f32 = items.iter().map(|i| i.grow).sum();
+            // factors summing to less than 1 only take that share of the space
+            let share = if total_grow < 1.0 {
+                free
+            } else {
+                free / total_grow
+            };
+            for item in &mut items {
+                item.main = item.base + share * item.grow;
+            }
+        } else if free < 0.0 {
+            let total_scaled: f32 = items.iter().map(|i| i.shrink * i.base).sum();
+            if total_scaled > 0.0 {
+                for item in &mut items {
+                    let ratio = item.shrink * item.base / total_scaled;
+                    item.main = (item.base + free * ratio).max(0.0);
+                }
+            }
+        }
+
+        // sizes along the main axis are final: lay out the contents and
+        // measure the cross axis
+        let mut line_cross: f32 = 0.0;
+        for (&i, item) in flow.iter().zip(&items) {
+            let child = &mut self.children[i];
+            if row {
+                child.dimensions.content.width = item.main;
+                child.layout_contents(styles);
+            } else {
+                child.dimensions.content.height = item.main;
+            }
+            let cross = if row {
+                child.dimensions.content.height
+            } else {
+                child.dimensions.content.width
+            };
+            line_cross = line_cross.max(cross + item.cross_edges);
+        }
+        if row {
+            line_cross = definite_height(style).unwrap_or(line_cross);
+        } else {
+            line_cross = container.content.width;
+        }
+
+        let remaining = definite_main.map_or(0.0, |m| m - outer_total(&items));
+        let count = items.len() as f32;
+        // flex-start and flex-end follow the direction, start and end don't
+        let justify = match keyword(style, "justify-content") {
+            Some("start" | "left") => "start",
+            Some("end" | "right") => "end",
+            Some("flex-end") if reverse => "start",
+            Some("flex-end") => "end",
+            Some(j @ ("center" | "space-between" | "space-around" | "space-evenly")) => j,
+            _ if reverse => "end",
+            _ => "start",
+        };
+        let (start, between) = match justify {
+            "end" => (remaining, 0.0),
+            "center" => (remaining / 2.0, 0.0),
+            "space-between" if remaining > 0.0 && count > 1.0 => (0.0, remaining / (count - 1.0)),
+            "space-around" if remaining > 0.0 => (remaining / count / 2.0, remaining / count),
+            "space-evenly" if remaining > 0.0 => {
+                (remaining / (count + 1.0), remaining / (count + 1.0))
+            }
+            // without room to distribute the space-* values pack at flex-start
+            _ if reverse && justify != "start" => (remaining, 0.0),
+            _ => (0.0, 0.0),
+        };
+
+        let align = keyword(style, "align-items");
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        if reverse {
+            order.reverse();
+        }
+        let mut cursor = start;
+        for i in order {
+            let item = &items[i];
+            let child = &mut self.children[flow[i]];
+            let explicit_cross = if row {
+                child.explicit_size(styles, "height", container)
+            } else {
+                child.explicit_size(styles, "width", container)
+            };
+            let mut cross = if row {
+                child.dimensions.content.height
+            } else {
+                child.dimensions.content.width
+            };
+            if row && stretch && explicit_cross.is_none() {
+                cross = (line_cross - item.cross_edges).max(0.0);
+                child.dimensions.content.height = cross;
+            }
+            let cross_offset = match align {
+                Some("flex-end" | "end" | "self-end") => line_cross - cross - item.cross_edges,
+                Some("center") => (line_cross - cross - item.cross_edges) / 2.0,
+                _ => 0.0,
+            };
+
+            if row {
+                child.translate(cursor, cross_offset);
+            } else {
+                child.translate(cross_offset, cursor);
+            }
+            cursor += item.main + item.main_edges + gap + between;
+        }
+
+        self.dimensions.content.height = if row {
+            line_cross
+        } else {
+            definite_main.unwrap_or_else(|| outer_total(&items))
+        };
+    }
+
+    // Lays out the children of a flex item or an out-of-flow box once its
+    // content box has been placed and its width fixed.
+    fn layout_contents(&mut self, styles: &StyleIndex) {
+        self.dimensions.content.height = 0.0;
+        match self.box_type {
+            BoxType::Anonymous => self.layout_inline_children(styles),
+            BoxType::Flex => {
+                self.layout_flex_items(styles);
+                self.calculate_height(styles);
+            }
+            _ => {
+                self.layout_children(styles);
+                self.calculate_height(styles);
+            }
+        }
+    }
+
+    fn calculate_edges(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        // anonymous items carry the container's style, not their own
+        if matches!(self.box_type, BoxType::Anonymous) {
+            return;
+        }
+        self.calculate_inline_edges(styles, b_box);
+        let style = self.style(styles);
+        let d = &mut self.dimensions;
+        d.margin.top = get_absolute_num(style, b_box, "margin-top").unwrap_or(0.0);
+        d.margin.bottom = get_absolute_num(style, b_box, "margin-bottom").unwrap_or(0.0);
+    }
+
+    fn explicit_size(&self, styles: &StyleIndex, name: &str, b_box: Dimensions) -> Option<f32> {
+        match self.box_type {
+            BoxType::Anonymous => None,
+            _ if name == "height" => definite_height(self.style(styles)),
+            _ => get_absolute_num(self.style(styles), b_box, name),
+        }
+    }
+
+    fn flex_factor(&self, styles: &StyleIndex, name: &str, default: f32) -> f32 {
+        match (&self.box_type, self.style(styles).value(name)) {
+            (BoxType::Anonymous, _) => default,
+            (_, Some(Value::Other(n))) => n
+                .parse()
+                .ok()
+                .filter(|n: &f32| *n >= 0.0)
+                .unwrap_or(default),
+            _ => default,
+        }
+    }
+
+    // the flex-basis as a content size, or None for `auto`/`content`
+    fn flex_basis(&self, styles: &StyleIndex, definite_main: Option<f32>) -> Option<f32> {
+        if matches!(self.box_type, BoxType::Anonymous) {
+            return None;
+        }
+        match self.style(styles).value("flex-basis") {
+            Some(Value::Length(n, Unit::Pct)) => definite_main.map(|m| n * m / 100.0),
+            Some(Value::Length(n, _)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    // The margin box width the box would take if nothing ever wrapped.
+    fn max_content_width(&self, styles: &StyleIndex) -> f32 {
+        let style = self.style(styles);
+        if let Some(text) = style.text() {
+            let mut collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if text.ends_with(char::is_whitespace) && !collapsed.is_empty() {
+                collapsed.push(' ');
+            }
+            return text_width(&collapsed, font_size(style));
+        }
+
+        let children = self
+            .children
+            .iter()
+            .filter(|c| !c.is_out_of_flow())
+            .map(|c| c.max_content_width(styles));
+        let content = match self.box_type {
+            BoxType::Anonymous => return children.sum(),
+            _ if get_absolute_num(style, Dimensions::default(), "width").is_some() => {
+                get_absolute_num(style, Dimensions::default(), "width").unwrap_or(0.0)
+            }
+            BoxType::Inline => children.sum(),
+            BoxType::Flex if flex_direction(style).0 => {
+                let gap = flex_gap(style, Dimensions::default(), true);
+                let count = self.children.iter().filter(|c| !c.is_out_of_flow()).count();
+                children.sum::<f32>() + gap * count.saturating_sub(1) as f32
+            }
+            _ => children.fold(0.0, f32::max),
+        };
+
+        let edge = |name| get_absolute_num(style, Dimensions::default(), name).unwrap_or(0.0);
+        content
+            + edge("margin-left")
+            + edge("margin-right")
+            + edge("padding-left")
+            + edge("padding-right")
+            + style.num_or("border-left-width", 0.0)
+            + style.num_or("border-right-width", 0.0)
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    fn is_out_of_flow(&self) -> bool {
+        matches!(self.position(), Position::Absolute | Position::Fixed)
+    }
+
+    pub fn z_index(&self) -> i32 {
+        self.z_index
+    }
+
+    // The positioned descendants this box stacks, each standing for its own
+    // subtree, sorted by z-index; ties keep tree order.
+    pub fn layers(&self) -> Vec<&LayoutBox> {
+        let mut layers = Vec::new();
+        for child in &self.children {
+            child.collect_layers(&mut layers);
+        }
+        layers.sort_by_key(|l| l.z_index());
+        layers
+    }
+
+    fn collect_layers<'b>(&'b self, layers: &mut Vec<&'b LayoutBox>) {
+        if !matches!(self.position(), Position::Static) {
+            layers.push(self);
+            return;
+        }
+        for child in &self.children {
+            child.collect_layers(layers);
+        }
+    }
+
+    // The deepest box under the point, found by walking the stacking
+    // context in reverse painting order so the topmost box wins. Anonymous
+    // boxes are never hit themselves, and text boxes only where a fragment
+    // was placed.
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<&LayoutBox> {
+        let layers = self.layers();
+        for layer in layers.iter().rev().filter(|l| l.z_index() >= 0) {
+            if let Some(hit) = layer.hit_test(x, y) {
+                return Some(hit);
+            }
+        }
+        for child in self.children.iter().rev() {
+            if let Some(hit) = child.hit_test_in_flow(x, y) {
+                return Some(hit);
+            }
+        }
+        for layer in layers.iter().rev().filter(|l| l.z_index() < 0) {
+            if let Some(hit) = layer.hit_test(x, y) {
+                return Some(hit);
+            }
+        }
+        Some(self).filter(|b| b.contains(x, y))
+    }
+
+    fn hit_test_in_flow(&self, x: f32, y: f32) -> Option<&LayoutBox> {
+        if !matches!(self.position(), Position::Static) {
+            return None;
+        }
+        for child in self.children.iter().rev() {
+            if let Some(hit) = child.hit_test_in_flow(x, y) {
+                return Some(hit);
+            }
+        }
+        Some(self).filter(|b| b.contains(x, y))
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        match self.box_type {
+            BoxType::Anonymous => false,
+            // only text boxes have fragments
+            _ if !self.fragments.is_empty() => self.fragments.iter().any(|f| f.rect.contains(x, y)),
+            _ => self.dimensions.border_box().contains(x, y),
+        }
+    }
+
+    pub fn node_id(&self) -> NodeId {
+        self.node
+    }
+
+    // Second pass over the laid out tree: relatively positioned boxes are
+    // shifted by their offsets, and absolutely positioned ones are laid out
+    // against the padding box of their nearest positioned ancestor (or the
+    // viewport when fixed).
+    fn layout_positioned(
+        &mut self,
+        styles: &StyleIndex,
+        containing_block: Rectangle,
+        viewport: Rectangle,
+    ) {
+        let parent = self.dimensions.content;
+        let containing_block = match self.position() {
+            Position::Static => containing_block,
+            _ => self.dimensions.padding_box(),
+        };
+        for child in &mut self.children {
+            match child.position() {
+                Position::Relative => {
+                    let dx = match (
+                        child.offset(styles, "left", parent.width),
+                        child.offset(styles, "right", parent.width),
+                    ) {
+                        (Some(left), _) => left,
+                        (None, Some(right)) => -right,
+                        (None, None) => 0.0,
+                    };
+                    let dy = match (
+                        child.offset(styles, "top", parent.height),
+                        child.offset(styles, "bottom", parent.height),
+                    ) {
+                        (Some(top), _) => top,
+                        (None, Some(bottom)) => -bottom,
+                        (None, None) => 0.0,
+                    };
+                    child.translate(dx, dy);
+                }
+                Position::Absolute => child.layout_absolute(styles, containing_block),
+                Position::Fixed => child.layout_absolute(styles, viewport),
+                Position::Static => {}
+            }
+            child.layout_positioned(styles, containing_block, viewport);
+        }
+    }
+
+    // CSS 2.1 §10.3.7 and §10.6.4 without auto margins: offsets that are
+    // auto fall back to the static position, and an auto width shrinks to
+    // fit unless both left and right are given.
+    fn layout_absolute(&mut self, styles: &StyleIndex, cb: Rectangle) {
+        let static_x = self.dimensions.content.x;
+        let static_y = self.dimensions.content.y;
+        let b_box = Dimensions {
+            content: cb,
+            ..Default::default()
+        };
+        self.calculate_edges(styles, b_box);
+        let d = self.dimensions;
+        let horizontal = d.margin.left
+            + d.border.left
+            + d.padding.left
+            + d.padding.right
+            + d.border.right
+            + d.margin.right;
+        let vertical = d.margin.top
+            + d.border.top
+            + d.padding.top
+            + d.padding.bottom
+            + d.border.bottom
+            + d.margin.bottom;
+
+        let left = self.offset(styles, "left", cb.width);
+        let right = self.offset(styles, "right", cb.width);
+        let top = self.offset(styles, "top", cb.height);
+        let bottom = self.offset(styles, "bottom", cb.height);
+
+        let width = match (self.explicit_size(styles, "width", b_box), left, right) {
+            (Some(width), _, _) => width,
+            (None, Some(l), Some(r)) => cb.width - l - r - horizontal,
+            (None, _, _) => {
+                let available = cb.width - left.unwrap_or(0.0) - right.unwrap_or(0.0) - horizontal;
+                (self.max_content_width(styles) - horizontal).min(available)
+            }
+        }
+        .max(0.0);
+        let x = match (left, right) {
+            (Some(l), _) => cb.x + l,
+            (None, Some(r)) => cb.x + cb.width - r - width - horizontal,
+            (None, None) => static_x,
+        };
+
+        let d = &mut self.dimensions;
+        d.content.width = width;
+        d.content.x = x + d.margin.left + d.border.left + d.padding.left;
+        d.content.y = static_y + d.margin.top + d.border.top + d.padding.top;
+        self.layout_contents(styles);
+        if let (None, Some(t), Some(b)) = (definite_height(self.style(styles)), top, bottom) {
+            self.dimensions.content.height = (cb.height - t - b - vertical).max(0.0);
+        }
+
+        let height = self.dimensions.content.height + vertical;
+        let y = match (top, bottom) {
+            (Some(t), _) => cb.y + t,
+            (None, Some(b)) => cb.y + cb.height - b - height,
+            (None, None) => static_y,
+        };
+        self.translate(0.0, y - static_y);
+    }
+
+    // a top/right/bottom/left offset, None when auto
+    fn offset(&self, styles: &StyleIndex, name: &str, basis: f32) -> Option<f32> {
+        match self.style(styles).value(name) {
+            Some(Value::Length(n, Unit::Pct)) => Some(n * basis / 100.0),
+            Some(Value::Length(n, _)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn layout_anonymous(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        let d = &mut self.dimensions;
+        d.content.x = b_box.content.x;
+        d.content.y = b_box.content.y + b_box.content.height;
+        d.content.width = b_box.content.width;
+        d.content.height = 0.0;
+        self.layout_inline_children(styles);
+    }
+
+    // Lays the (inline-level) children out in line boxes: the subtree is
+    // flattened into words and atomic boxes, which are broken greedily into
+    // lines, aligned on a common baseline and then written back as text
+    // fragments or box offsets.
+    fn layout_inline_children(&mut self, styles: &StyleIndex) {
+        let available_width = self.dimensions.content.width;
+        let mut items = Vec::new();
+        for (i, child) in self.children.iter_mut().enumerate() {
+            collect_inline_items(styles, child, vec![i], available_width, &mut items);
+        }
+
+        let strut_height = line_height(self.style(styles));
+        let strut = Placement {
+            item: usize::MAX,
+            x: 0.0,
+            width: 0.0,
+            height: strut_height,
+            ascent: text_ascent(font_size(self.style(styles)), strut_height),
+            mark: false,
+        };
+        let mut lines = LineBuilder {
+            available_width,
+            align: text_align(self.style(styles)),
+            strut,
+            top: 0.0,
+            current: Vec::new(),
+            placed: Vec::new(),
+        };
+
+        let mut cursor = 0.0;
+        let mut pending_space = 0.0;
+        let mut pending_edges = 0.0;
+        for (i, item) in items.iter().enumerate() {
+            match *item {
+                InlineItem::Space { width } => {
+                    if lines.has_content() {
+                        pending_space = width;
+                    }
+                }
+                InlineItem::Open { width } => pending_edges += width,
+                InlineItem::Close { width } => cursor += width,
+                InlineItem::Break => {
+                    lines.finish_line(true);
+                    cursor = 0.0;
+                    pending_space = 0.0;
+                }
+                InlineItem::Mark { .. } => lines.current.push(Placement {
+                    item: i,
+                    x: cursor + pending_space + pending_edges,
+                    width: 0.0,
+                    height: 0.0,
+                    ascent: 0.0,
+                    mark: true,
+                }),
+                InlineItem::Word {
+                    width,
+                    height,
+                    ascent,
+                    ..
+                }
+                | InlineItem::Atomic {
+                    width,
+                    height,
+                    ascent,
+                    ..
+                } => {
+                    let mut x = cursor + pending_space + pending_edges;
+                    if lines.has_content() && x + width > available_width {
+                        lines.finish_line(false);
+                        x = pending_edges;
+                    }
+                    lines.current.push(Placement {
+                        item: i,
+                        x,
+                        width,
+                        height,
+                        ascent,
+                        mark: false,
+                    });
+                    cursor = x + width;
+                    pending_space = 0.0;
+                    pending_edges = 0.0;
+                }
             }
+        }
+        lines.finish_line(false);
 
-            child.layout(*d);
-            let new_height = child.dimensions.margin_box().height;
-            if new_height > max_child_height {
-                max_child_height = new_height;
-            }
-            match child.box_type {
-                BoxType::Block => d.content.height += child.dimensions.margin_box().height,
-                BoxType::InlineBlock => {
-                    d.current.x = child.dimensions.margin_box().width;
-                    if d.current.x > d.content.width {
-                        d.content.height += max_child_height;
-                        d.current.x = 0.0;
-                        child.layout(*d);
-                        d.current.x += child.dimensions.margin_box().width;
+        let origin = self.dimensions.content;
+        let mut last_word: Option<(usize, &[usize])> = None;
+        for (line, placement, y) in lines.placed {
+            let x = origin.x + placement.x;
+            let y = origin.y + y;
+            match items[placement.item] {
+                InlineItem::Word {
+                    ref path, ref text, ..
+                } => {
+                    let text_box = self.box_at_mut(path);
+                    match last_word {
+                        Some((l, p)) if l == line && p == &path[..] => {
+                            let fragment = text_box.fragments.last_mut().unwrap();
+                            fragment.text.push(' ');
+                            fragment.text.push_str(text);
+                            fragment.rect.width = x + placement.width - fragment.rect.x;
+                        }
+                        _ => text_box.fragments.push(TextFragment {
+                            text: text.clone(),
+                            rect: Rectangle {
+                                x,
+                                y,
+                                width: placement.width,
+                                height: placement.height,
+                            },
+                        }),
                     }
+                    last_word = Some((line, path));
+                }
+                InlineItem::Atomic { ref path, .. } => {
+                    let atomic = self.box_at_mut(path);
+                    let margin_box = atomic.dimensions.margin_box();
+                    atomic.translate(x - margin_box.x, y - margin_box.y);
+                    last_word = None;
+                }
+                // boxes that end up with nothing placed inside stay here
+                InlineItem::Mark { ref path } => {
+                    self.box_at_mut(path).dimensions.content = Rectangle {
+                        x,
+                        y,
+                        width: 0.0,
+                        height: 0.0,
+                    };
                 }
                 _ => {}
             }
-            prevBoxType = child.box_type.clone()
         }
+
+        for child in &mut self.children {
+            child.fit_inline_content();
+        }
+        self.dimensions.content.height = lines.top;
+    }
+
+    fn box_at_mut(&mut self, path: &[usize]) -> &mut LayoutBox {
+        path.iter().fold(self, |b, &i| &mut b.children[i])
+    }
+
+    fn translate(&mut self, dx: f32, dy: f32) {
+        self.dimensions.content.x += dx;
+        self.dimensions.content.y += dy;
+        for fragment in &mut self.fragments {
+            fragment.rect.x += dx;
+            fragment.rect.y += dy;
+        }
+        for child in &mut self.children {
+            child.translate(dx, dy);
+        }
+    }
+
+    // Sizes text boxes and inline element boxes to the union of what was
+    // placed inside them and returns their margin box, if any.
+    fn fit_inline_content(&mut self) -> Option<Rectangle> {
+        if self.is_out_of_flow() {
+            return None;
+        }
+        match self.box_type {
+            BoxType::Inline => {
+                let mut content: Option<Rectangle> = None;
+                for rect in self.fragments.iter().map(|f| f.rect) {
+                    content = Some(content.map_or(rect, |c| c.union(rect)));
+                }
+                for child in &mut self.children {
+                    if let Some(rect) = child.fit_inline_content() {
+                        content = Some(content.map_or(rect, |c| c.union(rect)));
+                    }
+                }
+                content.map(|c| {
+                    self.dimensions.content = c;
+                    self.dimensions.margin_box()
+                })
+            }
+            _ => Some(self.dimensions.margin_box()),
+        }
+    }
+
+    fn calculate_inline_edges(&mut self, styles: &StyleIndex, b_box: Dimensions) {
+        let s = self.style(styles);
+        let d = &mut self.dimensions;
+
+        d.margin.left = get_absolute_num(s, b_box, "margin-left").unwrap_or(0.0);
+        d.margin.right = get_absolute_num(s, b_box, "margin-right").unwrap_or(0.0);
+        d.padding.left = get_absolute_num(s, b_box, "padding-left").unwrap_or(0.0);
+        d.padding.right = get_absolute_num(s, b_box, "padding-right").unwrap_or(0.0);
+        d.border.left = s.num_or("border-left-width", 0.0);
+        d.border.right = s.num_or("border-right-width", 0.0);
+        d.padding.top = get_absolute_num(s, b_box, "padding-top").unwrap_or(0.0);
+        d.padding.bottom = get_absolute_num(s, b_box, "padding-bottom").unwrap_or(0.0);
+        d.border.top = s.num_or("border-top-width", 0.0);
+        d.border.bottom = s.num_or("border-bottom-width", 0.0);
+    }
+}
+
+struct FlexItem {
+    base: f32,
+    main: f32,
+    main_edges: f32,
+    cross_edges: f32,
+    grow: f32,
+    shrink: f32,
+}
+
+// (is the main axis horizontal, is it reversed)
+fn flex_direction(style: &StyledNode) -> (bool, bool) {
+    match keyword(style, "flex-direction") {
+        Some("row-reverse") => (true, true),
+        Some("column") => (false, false),
+        Some("column-reverse") => (false, true),
+        _ => (true, false),
+    }
+}
+
+// the gap between neighbouring items along the main axis
+fn flex_gap(style: &StyledNode, b_box: Dimensions, row: bool) -> f32 {
+    let name = if row { "column-gap" } else { "row-gap" };
+    get_absolute_num(style, b_box, name).unwrap_or(0.0)
+}
+
+fn definite_height(style: &StyledNode) -> Option<f32> {
+    match style.value("height") {
+        Some(Value::Length(n, Unit::Px)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn keyword<'a>(style: &'a StyledNode, name: &str) -> Option<&'a str> {
+    match style.value(name) {
+        Some(Value::Other(k)) => Some(k),
+        _ => None,
+    }
+}
+
+enum InlineItem {
+    Word {
+        path: Vec<usize>,
+        text: String,
+        width: f32,
+        height: f32,
+        ascent: f32,
+    },
+    Atomic {
+        path: Vec<usize>,
+        width: f32,
+        height: f32,
+        ascent: f32,
+    },
+    // collapsible white space between words
+    Space {
+        width: f32,
+    },
+    // left and right margin, border and padding of an inline element
+    Open {
+        width: f32,
+    },
+    Close {
+        width: f32,
+    },
+    Break,
+    // where a text box or inline element starts, for the ones left empty
+    Mark {
+        path: Vec<usize>,
+    },
+}
+
+#[derive(Clone, Copy)]
+struct Placement {
+    item: usize,
+    x: f32,
+    width: f32,
+    height: f32,
+    ascent: f32,
+    // a Mark, which takes no room on the line
+    mark: bool,
+}
+
+struct LineBuilder {
+    available_width: f32,
+    align: f32,
+    strut: Placement,
+    top: f32,
+    current: Vec<Placement>,
+    // (line number, placement, y relative to the container)
+    placed: Vec<(usize, Placement, f32)>,
+}
+
+impl LineBuilder {
+    fn has_content(&self) -> bool {
+        self.current.iter().any(|p| !p.mark)
+    }
+
+    fn finish_line(&mut self, forced: bool) {
+        if self.current.is_empty() && !forced {
+            return;
+        }
+
+        // a line holding nothing but marks or the static positions of
+        // out-of-flow boxes takes no room, unless a <br> ends it
+        let collapsed = if self.has_content() {
+            self.current
+                .iter()
+                .all(|p| p.width == 0.0 && p.height == 0.0)
+        } else {
+            !forced
+        };
+        let (baseline, height) = if collapsed {
+            (0.0, 0.0)
+        } else {
+            let baseline = self
+                .current
+                .iter()
+                .chain(Some(&self.strut))
+                .map(|p| p.ascent)
+                .fold(0.0, f32::max);
+            let height = self
+                .current
+                .iter()
+                .chain(Some(&self.strut))
+                .map(|p| baseline - p.ascent + p.height)
+                .fold(0.0, f32::max);
+            (baseline, height)
+        };
+        let width = self
+            .current
+            .iter()
+            .rev()
+            .find(|p| !p.mark)
+            .map_or(0.0, |p| p.x + p.width);
+        let shift = ((self.available_width - width) * self.align).max(0.0);
+
+        let line = self.placed.last().map_or(0, |(l, _, _)| l + 1);
+        for mut p in self.current.drain(..) {
+            let y = self.top + baseline - p.ascent;
+            p.x += shift;
+            self.placed.push((line, p, y));
+        }
+        self.top += height;
+    }
+}
+
+fn collect_inline_items(
+    styles: &StyleIndex,
+    b: &mut LayoutBox,
+    path: Vec<usize>,
+    available_width: f32,
+    items: &mut Vec<InlineItem>,
+) {
+    let style = b.style(styles);
+    if let Some(text) = style.text() {
+        items.push(InlineItem::Mark { path: path.clone() });
+        let size = font_size(style);
+        let height = line_height(style);
+        let ascent = text_ascent(size, height);
+        let space = InlineItem::Space {
+            width: text_width(" ", size),
+        };
+
+        if text.starts_with(char::is_whitespace) {
+            items.push(space);
+        }
+        for (i, word) in text.split_whitespace().enumerate() {
+            if i > 0 {
+                items.push(InlineItem::Space {
+                    width: text_width(" ", size),
+                });
+            }
+            items.push(InlineItem::Word {
+                path: path.clone(),
+                text: word.to_string(),
+                width: text_width(word, size),
+                height,
+                ascent,
+            });
+        }
+        if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+            items.push(InlineItem::Space {
+                width: text_width(" ", size),
+            });
+        }
+        return;
+    }
+
+    if style.tag_name() == Some("br") {
+        items.push(InlineItem::Mark { path });
+        items.push(InlineItem::Break);
+        return;
+    }
+
+    // an empty box marks the static position of out-of-flow boxes
+    if b.is_out_of_flow() {
+        items.push(InlineItem::Atomic {
+            path,
+            width: 0.0,
+            height: 0.0,
+            ascent: 0.0,
+        });
+        return;
+    }
+
+    match b.box_type {
+        BoxType::Inline => {
+            let mut containing_block = Dimensions::default();
+            containing_block.content.width = available_width;
+            b.calculate_inline_edges(styles, containing_block);
+            let d = b.dimensions;
+            items.push(InlineItem::Open {
+                width: d.margin.left + d.border.left + d.padding.left,
+            });
+            items.push(InlineItem::Mark { path: path.clone() });
+            for (i, child) in b.children.iter_mut().enumerate() {
+                let mut child_path = path.clone();
+                child_path.push(i);
+                collect_inline_items(styles, child, child_path, available_width, items);
+            }
+            items.push(InlineItem::Close {
+                width: d.margin.right + d.border.right + d.padding.right,
+            });
+        }
+        BoxType::InlineBlock | BoxType::Block | BoxType::Flex | BoxType::Anonymous => {
+            let block_level = !matches!(b.box_type, BoxType::InlineBlock);
+            let mut containing_block = Dimensions::default();
+            containing_block.content.width = available_width;
+            b.layout(styles, containing_block);
+
+            let margin_box = b.dimensions.margin_box();
+            if block_level {
+                items.push(InlineItem::Break);
+            }
+            items.push(InlineItem::Atomic {
+                path,
+                width: margin_box.width,
+                height: margin_box.height,
+                ascent: margin_box.height,
+            });
+            if block_level {
+                items.push(InlineItem::Break);
+            }
+        }
+    }
+}
+
+pub fn font_size(style: &StyledNode) -> f32 {
+    match style.value("font-size") {
+        Some(v) => match *v {
+            Value::Length(n, Unit::Px) if n > 0.0 => n,
+            _ => DEFAULT_FONT_SIZE,
+        },
+        None => DEFAULT_FONT_SIZE,
+    }
+}
+
+pub fn line_height(style: &StyledNode) -> f32 {
+    let size = font_size(style);
+    let normal = size * NORMAL_LINE_HEIGHT;
+    match style.value("line-height") {
+        Some(v) => match *v {
+            Value::Length(n, Unit::Px) => n,
+            Value::Other(ref s) => {
+                if let Some(px) = s.strip_suffix("px") {
+                    px.parse().unwrap_or(normal)
+                } else if let Some(pct) = s.strip_suffix('%') {
+                    pct.parse().map_or(normal, |p: f32| p * size / 100.0)
+                } else {
+                    s.parse().map_or(normal, |n: f32| n * size)
+                }
+            }
+            _ => normal,
+        },
+        None => normal,
+    }
+}
+
+pub fn text_width(text: &str, font_size: f32) -> f32 {
+    text.chars().count() as f32 * font_size * GLYPH_ADVANCE
+}
+
+// the glyphs sit in the middle of the line height (half-leading above)
+fn text_ascent(font_size: f32, line_height: f32) -> f32 {
+    (line_height - font_size) / 2.0 + font_size * GLYPH_ASCENT
+}
+
+fn text_align(style: &StyledNode) -> f32 {
+    match style.value("text-align") {
+        Some(v) => match *v {
+            Value::Other(ref s) => match s.as_ref() {
+                "center" => 0.5,
+                "right" | "end" => 1.0,
+                _ => 0.0,
+            },
+            _ => 0.0,
+        },
+        None => 0.0,
     }
 }
 
-impl<'a> fmt::Debug for LayoutBox<'a> {
+impl fmt::Debug for LayoutBox {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "type:\n  {:?}\n{:?}\n", self.box_type, self.dimensions)
     }
 }
 
 impl Dimensions {
+    pub fn viewport(width: f32, height: f32) -> Dimensions {
+        let mut d = Dimensions::default();
+        d.content.width = width;
+        d.content.height = height;
+        d
+    }
+
     fn padding_box(&self) -> Rectangle {
         self.content.expanded(self.padding)
     }
@@ -271,6 +1425,22 @@ impl fmt::Debug for Dimensions {
 }
 
 impl Rectangle {
+    // edges are half open, so boxes that touch never both contain a point
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    fn union(&self, other: Rectangle) -> Rectangle {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rectangle {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+
     fn expanded(&self, e: EdgeSizes) -> Rectangle {
         Rectangle {
             x: self.x - e.left,
@@ -307,6 +1477,7 @@ impl fmt::Debug for BoxType {
             BoxType::Block => "block",
             BoxType::Inline => "inline",
             BoxType::InlineBlock => "inline-block",
+            BoxType::Flex => "flex",
             BoxType::Anonymous => "anonymous",
         };
 
@@ -314,13 +1485,14 @@ impl fmt::Debug for BoxType {
     }
 }
 
+// lengths arrive in px from the style tree; percentages are resolved here
+// against the containing block's width
 fn get_absolute_num(s_node: &StyledNode, b_box: Dimensions, prop: &str) -> Option<f32> {
     match s_node.value(prop) {
-        Some(ref v) => match ***v {
+        Some(v) => match *v {
             Value::Length(l, ref u) => match *u {
-                Unit::Px => Some(l),
                 Unit::Pct => Some(l * b_box.content.width / 100.0),
-                _ => panic!("Unimplemented css length unit"),
+                _ => Some(l),
             },
             _ => None,
         },
@@ -328,33 +1500,123 @@ fn get_absolute_num(s_node: &StyledNode, b_box: Dimensions, prop: &str) -> Optio
     }
 }
 
-pub fn layout_tree<'a>(
-    root: &'a StyledNode<'a>,
+pub fn layout_tree(root: &StyledNode, containing_block: Dimensions) -> LayoutBox {
+    layout_tree_cached(root, containing_block, &mut LayoutCache::default())
+}
+
+// Like `layout_tree`, but boxes still in `cache` are moved into place rather
+// than laid out, and the cache is refilled from the new tree. Positioning
+// runs in full every time, after the cache has been taken, since it depends
+// on boxes outside the subtree.
+pub fn layout_tree_cached(
+    root: &StyledNode,
     mut containing_block: Dimensions,
-) -> LayoutBox<'a> {
+    cache: &mut LayoutCache,
+) -> LayoutBox {
+    let styles = &root.index();
+    let viewport = containing_block.content;
     containing_block.content.height = 0.0;
     let mut root_box = build_layout_tree(root);
-    root_box.layout(containing_block);
-    return root_box;
+    cache.attach(&mut root_box);
+    root_box.layout(styles, containing_block);
+    cache.store(&root_box);
+    root_box.layout_positioned(styles, viewport, viewport);
+    root_box
 }
 
-fn build_layout_tree<'a>(node: &'a StyledNode) -> LayoutBox<'a> {
-    let mut layout_node = LayoutBox::new(
-        match node.get_display() {
-            Display::Block => BoxType::Block,
-            Display::Inline => BoxType::Inline,
-            Display::InlineBlock => BoxType::InlineBlock,
-            Display::None => BoxType::Anonymous,
-        },
-        node,
-    );
+impl LayoutCache {
+    // forgets the box of `id`; call it for every node `StyledNode::restyle`
+    // reports
+    pub fn invalidate(&mut self, id: NodeId) {
+        self.entries.remove(&id);
+    }
+
+    pub fn clear(&mut self) {
+        self.boxes.clear();
+        self.entries.clear();
+    }
+
+    // hands the topmost boxes with a usable snapshot their subtree
+    fn attach(&self, layout_box: &mut LayoutBox) {
+        if !matches!(layout_box.box_type, BoxType::Anonymous) {
+            if let Some(&start) = self.entries.get(&layout_box.node_id()) {
+                let first = &self.boxes[start];
+                let same_type =
+                    mem::discriminant(&first.box_type) == mem::discriminant(&layout_box.box_type);
+                if same_type && first.len == layout_box.box_count() {
+                    layout_box.reuse = Some(self.boxes[start..start + first.len].to_vec());
+                    return;
+                }
+            }
+        }
+        for child in &mut layout_box.children {
+            self.attach(child);
+        }
+    }
+
+    fn store(&mut self, root: &LayoutBox) {
+        self.clear();
+        self.push(root);
+    }
+
+    fn push(&mut self, layout_box: &LayoutBox) {
+        let start = self.boxes.len();
+        self.boxes.push(Snapshot {
+            box_type: layout_box.box_type.clone(),
+            dimensions: layout_box.dimensions,
+            fragments: layout_box.fragments.clone(),
+            laid_out: layout_box.laid_out,
+            len: 0,
+        });
+        for child in &layout_box.children {
+            self.push(child);
+        }
+        self.boxes[start].len = self.boxes.len() - start;
+        let anonymous = matches!(layout_box.box_type, BoxType::Anonymous);
+        if layout_box.laid_out.is_some() && !anonymous {
+            self.entries.insert(layout_box.node_id(), start);
+        }
+    }
+}
+
+fn build_layout_tree(node: &StyledNode) -> LayoutBox {
+    let box_type = match node.get_display() {
+        Display::Block => BoxType::Block,
+        Display::Inline => BoxType::Inline,
+        Display::InlineBlock => BoxType::InlineBlock,
+        Display::Flex => BoxType::Flex,
+        Display::None => return LayoutBox::new(BoxType::Anonymous, node),
+    };
+    build_box(node, box_type)
+}
+
+fn build_box(node: &StyledNode, box_type: BoxType) -> LayoutBox {
+    let mut layout_node = LayoutBox::new(box_type, node);
+    let flex_container = matches!(layout_node.box_type, BoxType::Flex);
 
     for child in &node.children {
         match child.get_display() {
-            Display::Block => layout_node.children.push(build_layout_tree(child)),
-            Display::Inline => layout_node.children.push(build_layout_tree(child)),
-            Display::InlineBlock => layout_node.children.push(build_layout_tree(child)),
             Display::None => {}
+            // white space between flex items isn't rendered
+            _ if flex_container && child.text().is_some_and(|t| t.trim().is_empty()) => {}
+            // flex items and out-of-flow boxes are blockified; runs of text
+            // become anonymous flex items
+            Display::Inline | Display::InlineBlock if flex_container && child.text().is_none() => {
+                layout_node.children.push(build_box(child, BoxType::Block))
+            }
+            Display::Inline | Display::InlineBlock
+                if matches!(child.get_position(), Position::Absolute | Position::Fixed) =>
+            {
+                layout_node
+                    .get_inline_container()
+                    .children
+                    .push(build_box(child, BoxType::Block))
+            }
+            Display::Block | Display::Flex => layout_node.children.push(build_layout_tree(child)),
+            Display::Inline | Display::InlineBlock => layout_node
+                .get_inline_container()
+                .children
+                .push(build_layout_tree(child)),
         }
     }
     layout_node
@@ -367,3 +1629,383 @@ pub fn pretty_print<'a>(n: &'a LayoutBox, level: usize) {
         pretty_print(&child, level + 1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css_parser::CssParser;
+    use crate::document::Document;
+    use crate::dom::{AttrMap, ElementData, Node, NodeType};
+    use std::path::Path;
+
+    fn elem(tag: &str, children: Vec<Node>) -> Node {
+        Node::new(
+            NodeType::Element(ElementData::new(tag.to_string(), AttrMap::new())),
+            children,
+        )
+    }
+
+    fn text(t: &str) -> Node {
+        Node::new(NodeType::Text(t.to_string()), vec![])
+    }
+
+    fn viewport(width: f32) -> Dimensions {
+        let mut d = Dimensions::default();
+        d.content.width = width;
+        d
+    }
+
+    fn fragments<'a>(b: &'a LayoutBox, out: &mut Vec<(&'a str, f32, f32, f32)>) {
+        for f in &b.fragments {
+            out.push((&f.text, f.rect.x, f.rect.y, f.rect.width));
+        }
+        for child in &b.children {
+            fragments(child, out);
+        }
+    }
+
+    #[test]
+    fn text_breaks_into_line_boxes() {
+        let dom = elem("div", vec![text("hello world foo")]);
+        let css = "div { width: 60px; margin-left: 0px; font-size: 10px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, viewport(800.0));
+
+        let mut out = Vec::new();
+        fragments(&root, &mut out);
+        assert_eq!(
+            out,
+            vec![("hello world", 0.0, 0.0, 55.0), ("foo", 0.0, 12.0, 15.0)]
+        );
+        assert_eq!(root.dimensions.content.height, 24.0);
+    }
+
+    #[test]
+    fn auto_margins_center_fixed_width_blocks() {
+        let dom = elem("div", vec![elem("p", vec![]), elem("span", vec![])]);
+        let css = "p { display: block; width: 200px; margin: 0 auto; } \
+                   span { display: block; width: 100px; margin: 0 10px 0 auto; padding: 0 5px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, viewport(800.0));
+
+        let p = &root.children[0].dimensions;
+        assert_eq!((p.margin.left, p.margin.right), (300.0, 300.0));
+        assert_eq!(p.content.x, 300.0);
+
+        let span = &root.children[1].dimensions;
+        assert_eq!((span.margin.left, span.margin.right), (680.0, 10.0));
+        assert_eq!(span.content.x, 685.0);
+    }
+
+    fn margin_boxes(b: &LayoutBox) -> Vec<(f32, f32, f32, f32)> {
+        b.children
+            .iter()
+            .map(|c| {
+                let m = c.dimensions.margin_box();
+                let round = |v: f32| (v * 100.0).round() / 100.0;
+                (round(m.x), round(m.y), round(m.width), round(m.height))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flex_row_grows_items_and_aligns_them() {
+        let dom = elem(
+            "div",
+            vec![
+                elem("a", vec![]),
+                text("\n  "),
+                elem("b", vec![]),
+                elem("i", vec![]),
+            ],
+        );
+        let css =
+            "div { display: flex; width: 300px; height: 50px; gap: 10px; align-items: center; } \
+                   a { width: 40px; height: 20px; } \
+                   b { flex: 1; height: 10px; margin: 0 5px; } \
+                   i { flex: 2 1 20px; height: 30px; padding-left: 4px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, viewport(300.0));
+
+        // 300 - 40 - 10 - 24 - 20 = 206 free: b takes a third, i two thirds
+        let expected = 206.0 / 3.0;
+        assert_eq!(root.children.len(), 3);
+        let boxes = margin_boxes(&root);
+        assert_eq!(boxes[0], (0.0, 15.0, 40.0, 20.0));
+        assert_eq!(boxes[1].1, 20.0);
+        assert!((boxes[1].0 - 50.0).abs() < 0.01);
+        assert!((boxes[1].2 - (expected + 10.0)).abs() < 0.01);
+        assert!((boxes[2].2 - (2.0 * expected + 24.0)).abs() < 0.01);
+        assert!((boxes[2].0 + boxes[2].2 - 300.0).abs() < 0.01);
+        assert_eq!(root.dimensions.content.height, 50.0);
+    }
+
+    #[test]
+    fn flex_row_shrinks_and_stretches_items() {
+        let dom = elem("div", vec![elem("a", vec![]), elem("b", vec![text("hi")])]);
+        let css = "div { display: flex; width: 100px; font-size: 10px; } \
+                   a { width: 100px; height: 40px; } \
+                   b { flex-basis: 50px; flex-shrink: 3; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, viewport(100.0));
+
+        // 50px of overflow, taken back in proportion to shrink * basis
+        let boxes = margin_boxes(&root);
+        assert_eq!(boxes[0], (0.0, 0.0, 80.0, 40.0));
+        assert_eq!(boxes[1], (80.0, 0.0, 20.0, 40.0));
+        assert_eq!(root.dimensions.content.height, 40.0);
+    }
+
+    #[test]
+    fn flex_column_justifies_along_the_height() {
+        let dom = elem(
+            "div",
+            vec![elem("a", vec![]), elem("b", vec![]), text("text")],
+        );
+        let css = "div { display: flex; flex-direction: column-reverse; height: 100px; \
+                   justify-content: space-between; align-items: flex-end; font-size: 10px; } \
+                   a { width: 20px; height: 10px; } \
+                   b { height: 30px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, viewport(200.0));
+
+        let boxes = margin_boxes(&root);
+        assert_eq!(boxes.len(), 3);
+        assert_eq!(boxes[2], (180.0, 0.0, 20.0, 12.0));
+        assert_eq!(boxes[1], (200.0, 36.0, 0.0, 30.0));
+        assert_eq!(boxes[0], (180.0, 90.0, 20.0, 10.0));
+        assert_eq!(root.dimensions.content.height, 100.0);
+    }
+
+    #[test]
+    fn relative_boxes_are_offset_without_moving_siblings() {
+        let dom = elem("div", vec![elem("a", vec![]), elem("b", vec![])]);
+        let css = "div { width: 200px; } \
+                   a { display: block; position: relative; top: 5px; left: 10%; height: 10px; } \
+                   b { display: block; position: relative; bottom: 3px; right: 4px; height: 10px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, viewport(200.0));
+
+        let boxes = margin_boxes(&root);
+        assert_eq!(boxes[0], (20.0, 5.0, 200.0, 10.0));
+        assert_eq!(boxes[1], (-4.0, 7.0, 200.0, 10.0));
+        assert_eq!(root.dimensions.content.height, 20.0);
+    }
+
+    #[test]
+    fn absolute_boxes_use_the_nearest_positioned_ancestor() {
+        let dom = elem(
+            "body",
+            vec![elem(
+                "div",
+                vec![
+                    elem("s", vec![]),
+                    elem("a", vec![]),
+                    elem("b", vec![]),
+                    elem("i", vec![text("hey")]),
+                    elem("f", vec![]),
+                ],
+            )],
+        );
+        let css = "body { margin: 0; } \
+                   div { display: block; position: relative; width: 200px; padding: 10px; font-size: 10px; } \
+                   s { display: block; height: 30px; } \
+                   a, b, f { display: block; } \
+                   a { position: absolute; right: 5px; bottom: 5px; width: 30px; height: 20px; } \
+                   b { position: absolute; left: 0; right: 0; top: 0; height: 10px; } \
+                   i { position: absolute; } \
+                   f { position: fixed; left: 0; bottom: 0; width: 10px; height: 10px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, Dimensions::viewport(800.0, 600.0));
+
+        // out-of-flow boxes don't count towards the container's height
+        let div = &root.children[0];
+        assert_eq!(div.dimensions.content.height, 30.0);
+
+        let boxes = margin_boxes(div);
+        assert_eq!(boxes[1], (185.0, 25.0, 30.0, 20.0));
+        assert_eq!(boxes[2], (0.0, 0.0, 220.0, 10.0));
+        assert_eq!(boxes[4], (0.0, 590.0, 10.0, 10.0));
+
+        // no offsets: the static position in its line, shrunk to fit its text
+        assert_eq!(boxes[3], (10.0, 40.0, 200.0, 0.0));
+        assert_eq!(margin_boxes(&div.children[3]), [(10.0, 40.0, 15.0, 12.0)]);
+    }
+
+    #[test]
+    fn inline_elements_flow_and_set_line_height() {
+        let dom = elem(
+            "p",
+            vec![
+                text("ab "),
+                elem("span", vec![text("cd")]),
+                elem("br", vec![]),
+                text("ef"),
+            ],
+        );
+        let css = "p { font-size: 10px; margin-top: 0px; margin-bottom: 0px; } \
+                   span { padding-left: 4px; line-height: 30px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, viewport(800.0));
+
+        let mut out = Vec::new();
+        fragments(&root, &mut out);
+        // the span's taller line box pushes the baseline down by (30 - 12) / 2
+        assert_eq!(
+            out,
+            vec![
+                ("ab", 0.0, 9.0, 10.0),
+                ("cd", 19.0, 0.0, 10.0),
+                ("ef", 0.0, 30.0, 10.0)
+            ]
+        );
+        let span = &root.children[0].children[1];
+        assert_eq!(span.dimensions.border_box().x, 15.0);
+        assert_eq!(root.dimensions.content.height, 42.0);
+    }
+
+    #[test]
+    fn empty_inline_boxes_sit_on_the_line() {
+        let dom = elem(
+            "p",
+            vec![
+                text("ab"),
+                elem("span", vec![]),
+                text(" cd"),
+                elem("br", vec![]),
+                text("ef"),
+            ],
+        );
+        let css = "p { font-size: 10px; margin-top: 0px; margin-bottom: 0px; } \
+                   span { padding-left: 4px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, viewport(800.0));
+
+        // both sit on the first line's baseline, where the cursor was
+        let line = &root.children[0];
+        let position = |i: usize| {
+            let c = line.children[i].dimensions.content;
+            (c.x, c.y, c.width, c.height)
+        };
+        assert_eq!(position(1), (14.0, 9.0, 0.0, 0.0));
+        assert_eq!(position(3), (29.0, 9.0, 0.0, 0.0));
+
+        let mut out = Vec::new();
+        fragments(&root, &mut out);
+        assert_eq!(
+            out,
+            vec![
+                ("ab", 0.0, 0.0, 10.0),
+                ("cd", 19.0, 0.0, 10.0),
+                ("ef", 0.0, 12.0, 10.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn block_and_inline_children_get_anonymous_boxes() {
+        let dom = elem(
+            "div",
+            vec![text("one"), elem("p", vec![text("two")]), text("three")],
+        );
+        let css = "div, p { font-size: 10px; } p { margin-top: 0px; margin-bottom: 0px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let root = layout_tree(&styled, viewport(800.0));
+
+        let types: Vec<String> = root
+            .children
+            .iter()
+            .map(|c| format!("{:?}", c.box_type))
+            .collect();
+        assert_eq!(types, vec!["anonymous", "block", "anonymous"]);
+        assert_eq!(root.children[2].dimensions.content.y, 24.0);
+        assert_eq!(root.dimensions.content.height, 36.0);
+    }
+
+    #[test]
+    fn cached_boxes_are_reused_until_invalidated() {
+        let dom = elem("div", vec![elem("a", vec![]), elem("b", vec![text("hi")])]);
+        let css = "div { font-size: 10px; } a, b { display: block; } a { height: 10px; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let mut cache = LayoutCache::default();
+        let root = layout_tree_cached(&styled, viewport(100.0), &mut cache);
+        assert_eq!(root.dimensions.content.height, 22.0);
+
+        // doctor the snapshot of <b> to tell a reused box from a fresh one
+        let b = cache.entries[&2];
+        cache.boxes[b].dimensions.content.height = 30.0;
+        cache.invalidate(0);
+        let root = layout_tree_cached(&styled, viewport(100.0), &mut cache);
+        assert_eq!(root.children[1].dimensions.content.height, 30.0);
+        assert_eq!(root.dimensions.content.height, 40.0);
+
+        // a containing block of another width means laying out again
+        cache.invalidate(0);
+        let root = layout_tree_cached(&styled, viewport(50.0), &mut cache);
+        assert_eq!(root.children[1].dimensions.content.height, 12.0);
+        assert_eq!(root.dimensions.content.height, 22.0);
+    }
+
+    fn hit_id(document: &mut Document, x: f32, y: f32) -> Option<String> {
+        let id = document.layout(100, 100).hit_test(x, y)?.node_id();
+        Some(match document.node(id).unwrap().node_type {
+            NodeType::Element(ref e) => e.get_id().map_or(e.tag_name.clone(), |id| id.clone()),
+            NodeType::Text(ref t) => format!("{:?}", t),
+            NodeType::Comment(_) => unreachable!(),
+        })
+    }
+
+    #[test]
+    fn hit_test_finds_the_deepest_box() {
+        let html = "<style>#outer { height: 40px; padding: 5px; font-size: 10px; } \
+                    #inner { width: 20px; height: 10px; border: 2px solid; } \
+                    #gone { display: none; height: 50px; }</style>\
+                    <div id=outer><div id=inner></div>hi there</div><div id=gone></div>";
+        let mut document = Document::parse(html, Path::new("."));
+        let mut hit = |x, y| hit_id(&mut document, x, y);
+
+        assert_eq!(hit(6.0, 6.0).as_deref(), Some("inner"));
+        assert_eq!(hit(30.0, 6.0).as_deref(), Some("outer"));
+        // the text box covers its words, not the rest of the line
+        assert_eq!(hit(6.0, 20.0).as_deref(), Some("\"hi there\""));
+        assert_eq!(hit(80.0, 20.0).as_deref(), Some("outer"));
+        // display: none leaves nothing behind to hit
+        assert_eq!(hit(50.0, 60.0), None);
+        assert_eq!(hit(150.0, 10.0), None);
+    }
+
+    #[test]
+    fn hit_test_follows_painting_order() {
+        let html = "<style>\
+                    .bg { height: 50px; } \
+                    #a { position: absolute; top: 10px; left: 10px; width: 20px; height: 20px; \
+                         z-index: 2; } \
+                    #b { position: absolute; top: 20px; left: 20px; width: 20px; height: 20px; \
+                         z-index: 1; } \
+                    #c { position: absolute; top: 0; left: 60px; width: 20px; height: 20px; \
+                         z-index: -1; } \
+                    #r { position: relative; left: 50px; height: 10px; }</style>\
+                    <div id=a></div><div id=b></div><div id=c></div><div class=bg></div>\
+                    <div id=r></div>";
+        let mut document = Document::parse(html, Path::new("."));
+        let mut hit = |x, y| hit_id(&mut document, x, y);
+
+        assert_eq!(hit(25.0, 25.0).as_deref(), Some("a"));
+        assert_eq!(hit(35.0, 35.0).as_deref(), Some("b"));
+        // negative z-index stacks below in-flow blocks
+        assert_eq!(hit(65.0, 5.0).as_deref(), Some("div"));
+        assert_eq!(hit(55.0, 55.0).as_deref(), Some("r"));
+        assert_eq!(hit(5.0, 55.0).as_deref(), Some("html"));
+    }
+}
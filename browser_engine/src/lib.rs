@@ -1,8 +1,10 @@
 pub mod css;
 pub mod css_parser;
+pub mod document;
 pub mod dom;
 pub mod html_parser;
 pub mod layout;
+pub mod painting;
 pub mod style;
 #[cfg(test)]
 mod tests {
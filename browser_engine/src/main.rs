@@ -0,0 +1,48 @@
+use std::env;
+use std::process;
+
+use browser_engine::document::Document;
+
+const USAGE: &str = "usage: browser_engine <input.html> [-o output.png] [-w width] [-h height]";
+
+fn main() {
+    let mut input = None;
+    let mut output = String::from("output.png");
+    let mut width = 800;
+    let mut height = 600;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = args.next().unwrap_or_else(|| exit_with(USAGE)),
+            "-w" | "--width" => width = parse_size(args.next()),
+            "-h" | "--height" => height = parse_size(args.next()),
+            _ if input.is_none() => input = Some(arg),
+            _ => exit_with(USAGE),
+        }
+    }
+    let input = input.unwrap_or_else(|| exit_with(USAGE));
+
+    let mut document = Document::open(&input)
+        .unwrap_or_else(|e| exit_with(&format!("failed to read {}: {}", input, e)));
+    let canvas = document.render(width, height);
+
+    image::save_buffer(
+        &output,
+        &canvas.to_rgba8(),
+        width as u32,
+        height as u32,
+        image::ColorType::Rgba8,
+    )
+    .unwrap_or_else(|e| exit_with(&format!("failed to write {}: {}", output, e)));
+}
+
+fn parse_size(arg: Option<String>) -> usize {
+    arg.and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| exit_with(USAGE))
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
@@ -0,0 +1,274 @@
+use crate::css::{Color, Value};
+use crate::layout::{font_size, BoxType, LayoutBox, Rectangle, GLYPH_ADVANCE, GLYPH_ASCENT};
+use crate::style::{Position, StyleIndex, StyledNode};
+
+pub enum DisplayCommand {
+    SolidColor(Color, Rectangle),
+}
+
+pub type DisplayList = Vec<DisplayCommand>;
+
+pub struct Canvas {
+    pub pixels: Vec<Color>,
+    pub width: usize,
+    pub height: usize,
+}
+
+// `styled` is the style tree `layout_root` was laid out from
+pub fn paint(layout_root: &LayoutBox, styled: &StyledNode, bounds: Rectangle) -> Canvas {
+    let display_list = build_display_list(layout_root, styled);
+    let mut canvas = Canvas::new(bounds.width as usize, bounds.height as usize);
+    for item in display_list {
+        canvas.paint_item(&item);
+    }
+    canvas
+}
+
+pub fn build_display_list(layout_root: &LayoutBox, styled: &StyledNode) -> DisplayList {
+    let mut list = Vec::new();
+    render_stacking_context(&styled.index(), &mut list, layout_root);
+    list
+}
+
+// A simplified CSS 2.1 Appendix E: every positioned box is painted as one
+// layer, after the in-flow content of its stacking context, or before it when
+// its z-index is negative. Layers with the same z-index keep tree order, and
+// z-index: auto paints like 0.
+fn render_stacking_context(styles: &StyleIndex, list: &mut DisplayList, root: &LayoutBox) {
+    let layers = root.layers();
+
+    render_layout_box(styles, list, root);
+    for layer in layers.iter().filter(|l| l.z_index() < 0) {
+        render_stacking_context(styles, list, layer);
+    }
+    for child in &root.children {
+        render_in_flow(styles, list, child);
+    }
+    for layer in layers.iter().filter(|l| l.z_index() >= 0) {
+        render_stacking_context(styles, list, layer);
+    }
+}
+
+fn render_in_flow(styles: &StyleIndex, list: &mut DisplayList, layout_box: &LayoutBox) {
+    if !matches!(layout_box.position(), Position::Static) {
+        return;
+    }
+    render_layout_box(styles, list, layout_box);
+    for child in &layout_box.children {
+        render_in_flow(styles, list, child);
+    }
+}
+
+fn render_layout_box(styles: &StyleIndex, list: &mut DisplayList, layout_box: &LayoutBox) {
+    // anonymous boxes borrow their parent's style, which was painted already
+    if !matches!(layout_box.box_type, BoxType::Anonymous) {
+        render_background(styles, list, layout_box);
+        render_borders(styles, list, layout_box);
+        render_text(styles, list, layout_box);
+    }
+}
+
+fn render_background(styles: &StyleIndex, list: &mut DisplayList, layout_box: &LayoutBox) {
+    if let Some(color) = get_color(styles, layout_box, "background-color") {
+        list.push(DisplayCommand::SolidColor(
+            color,
+            layout_box.dimensions.border_box(),
+        ));
+    }
+}
+
+fn render_borders(styles: &StyleIndex, list: &mut DisplayList, layout_box: &LayoutBox) {
+    let d = &layout_box.dimensions;
+    let border_box = d.border_box();
+
+    let sides = [
+        (
+            "left",
+            Rectangle {
+                x: border_box.x,
+                y: border_box.y,
+                width: d.border.left,
+                height: border_box.height,
+            },
+        ),
+        (
+            "right",
+            Rectangle {
+                x: border_box.x + border_box.width - d.border.right,
+                y: border_box.y,
+                width: d.border.right,
+                height: border_box.height,
+            },
+        ),
+        (
+            "top",
+            Rectangle {
+                x: border_box.x,
+                y: border_box.y,
+                width: border_box.width,
+                height: d.border.top,
+            },
+        ),
+        (
+            "bottom",
+            Rectangle {
+                x: border_box.x,
+                y: border_box.y + border_box.height - d.border.bottom,
+                width: border_box.width,
+                height: d.border.bottom,
+            },
+        ),
+    ];
+
+    for (side, rect) in sides {
+        if rect.width <= 0.0 || rect.height <= 0.0 {
+            continue;
+        }
+        let style = layout_box
+            .style(styles)
+            .value(&format!("border-{}-style", side));
+        if matches!(style, Some(Value::Other(s)) if s == "none" || s == "hidden") {
+            continue;
+        }
+        if let Some(color) = border_color(styles, layout_box, side) {
+            list.push(DisplayCommand::SolidColor(color, rect));
+        }
+    }
+}
+
+// `border-<side>-color` wins over `border-color`; a side styled without
+// any color uses the text color
+fn border_color(styles: &StyleIndex, layout_box: &LayoutBox, side: &str) -> Option<Color> {
+    let node = layout_box.style(styles);
+    let value = node
+        .value(&format!("border-{}-color", side))
+        .or_else(|| node.value("border-color"));
+    let has_style = node.value(&format!("border-{}-style", side)).is_some();
+    match value {
+        Some(Value::Color(c)) => Some(c.clone()),
+        None if has_style => Some(text_color(styles, layout_box)),
+        _ => None,
+    }
+}
+
+fn text_color(styles: &StyleIndex, layout_box: &LayoutBox) -> Color {
+    get_color(styles, layout_box, "color").unwrap_or_else(|| Color::new(0.0, 0.0, 0.0, 1.0))
+}
+
+// There are no glyph outlines to draw, so text is "greeked": every
+// non-blank character becomes a cap-height block inside its advance.
+fn render_text(styles: &StyleIndex, list: &mut DisplayList, layout_box: &LayoutBox) {
+    if layout_box.fragments.is_empty() {
+        return;
+    }
+    let color = text_color(styles, layout_box);
+    let size = font_size(layout_box.style(styles));
+    let advance = size * GLYPH_ADVANCE;
+    let cap_height = size * 0.7;
+
+    for fragment in &layout_box.fragments {
+        let baseline = fragment.rect.y + (fragment.rect.height - size) / 2.0 + size * GLYPH_ASCENT;
+        for (i, c) in fragment.text.chars().enumerate() {
+            if c.is_whitespace() {
+                continue;
+            }
+            list.push(DisplayCommand::SolidColor(
+                color.clone(),
+                Rectangle {
+                    x: fragment.rect.x + i as f32 * advance + advance * 0.1,
+                    y: baseline - cap_height,
+                    width: advance * 0.8,
+                    height: cap_height,
+                },
+            ));
+        }
+    }
+}
+
+fn get_color(styles: &StyleIndex, layout_box: &LayoutBox, name: &str) -> Option<Color> {
+    match layout_box.style(styles).value(name) {
+        Some(v) => match *v {
+            Value::Color(ref c) => Some(c.clone()),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Canvas {
+        let white = Color::new(1.0, 1.0, 1.0, 1.0);
+        Canvas {
+            pixels: vec![white; width * height],
+            width,
+            height,
+        }
+    }
+
+    fn paint_item(&mut self, item: &DisplayCommand) {
+        match *item {
+            DisplayCommand::SolidColor(ref color, rect) => {
+                let x0 = rect.x.clamp(0.0, self.width as f32) as usize;
+                let y0 = rect.y.clamp(0.0, self.height as f32) as usize;
+                let x1 = (rect.x + rect.width).clamp(0.0, self.width as f32) as usize;
+                let y1 = (rect.y + rect.height).clamp(0.0, self.height as f32) as usize;
+
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        let pixel = &mut self.pixels[y * self.width + x];
+                        *pixel = blend(pixel, color);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        self.pixels
+            .iter()
+            .flat_map(|c| [channel(c.r), channel(c.g), channel(c.b), channel(c.a)])
+            .collect()
+    }
+}
+
+fn blend(dst: &Color, src: &Color) -> Color {
+    let a = src.a.clamp(0.0, 1.0);
+    Color::new(
+        src.r * a + dst.r * (1.0 - a),
+        src.g * a + dst.g * (1.0 - a),
+        src.b * a + dst.b * (1.0 - a),
+        a + dst.a * (1.0 - a),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::Document;
+    use std::path::Path;
+
+    #[test]
+    fn positioned_boxes_paint_in_z_index_order() {
+        let html = "<style> \
+            body { margin: 0; } \
+            .bg { height: 50px; background-color: #00ff00; } \
+            #a { position: absolute; top: 10px; left: 10px; width: 20px; height: 20px; \
+                 background-color: red; z-index: 2; } \
+            #b { position: absolute; top: 20px; left: 20px; width: 20px; height: 20px; \
+                 background-color: blue; z-index: 1; } \
+            #c { position: absolute; top: 0; left: 60px; width: 20px; height: 20px; \
+                 background-color: black; z-index: -1; } \
+            </style><div id=a></div><div id=b></div><div id=c></div><div class=bg></div>";
+        let canvas = Document::parse(html, Path::new(".")).render(100, 60);
+        let pixel = |x: usize, y: usize| {
+            let c = &canvas.pixels[y * canvas.width + x];
+            (c.r, c.g, c.b)
+        };
+
+        assert_eq!(pixel(25, 25), (1.0, 0.0, 0.0));
+        assert_eq!(pixel(35, 35), (0.0, 0.0, 1.0));
+        // below the in-flow background
+        assert_eq!(pixel(65, 5), (0.0, 1.0, 0.0));
+        assert_eq!(pixel(90, 55), (1.0, 1.0, 1.0));
+    }
+}
@@ -1,71 +1,417 @@
 use std::collections::HashMap;
+use std::sync::OnceLock;
 use std::{fmt, str};
 
-use crate::css::{Selector, Stylesheet, Value};
-use crate::dom::{ElementData, Node, NodeType};
+use crate::css::{Color, Selector, SimpleSelector, Stylesheet, Unit, Value};
+use crate::css_parser::CssParser;
+use crate::dom::{Node, NodeId, NodeType};
+use crate::layout::GLYPH_ADVANCE;
 
-type PropertyMap<'a> = HashMap<&'a str, &'a Value>;
+type PropertyMap = HashMap<String, Value>;
 
-pub struct StyledNode<'a> {
-    node: &'a Node,
-    styles: PropertyMap<'a>,
-    pub children: Vec<StyledNode<'a>>,
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+// CSS reference pixels per inch
+const PX_PER_IN: f32 = 96.0;
+
+const INHERITED_PROPERTIES: &[&str] = &[
+    "color",
+    "cursor",
+    "direction",
+    "font-family",
+    "font-size",
+    "font-style",
+    "font-variant",
+    "font-weight",
+    "letter-spacing",
+    "line-height",
+    "list-style-type",
+    "text-align",
+    "text-indent",
+    "text-transform",
+    "visibility",
+    "white-space",
+    "word-spacing",
+];
+
+const USER_AGENT_CSS: &str = "
+html, body, div, p, h1, h2, h3, h4, h5, h6, ul, ol, li, dl, dt, dd,
+section, article, header, footer, nav, main, aside, address, blockquote,
+figure, figcaption, form, fieldset, hr, pre, table { display: block; }
+head, script, style, title, meta, link, template { display: none; }
+body { margin-top: 8px; margin-right: 8px; margin-bottom: 8px; margin-left: 8px; }
+p, blockquote, figure, ul, ol, dl, pre { margin-top: 1em; margin-bottom: 1em; }
+h1 { font-size: 2em; font-weight: bold; margin-top: 0.67em; margin-bottom: 0.67em; }
+h2 { font-size: 1.5em; font-weight: bold; margin-top: 0.83em; margin-bottom: 0.83em; }
+h3 { font-size: 1.17em; font-weight: bold; margin-top: 1em; margin-bottom: 1em; }
+h4 { font-size: 1em; font-weight: bold; margin-top: 1.33em; margin-bottom: 1.33em; }
+h5 { font-size: 0.83em; font-weight: bold; margin-top: 1.67em; margin-bottom: 1.67em; }
+h6 { font-size: 0.67em; font-weight: bold; margin-top: 2.33em; margin-bottom: 2.33em; }
+ul, ol { padding-left: 40px; }
+pre { font-family: monospace; white-space: pre; }
+";
+
+pub fn user_agent_stylesheet() -> &'static Stylesheet {
+    static STYLESHEET: OnceLock<Stylesheet> = OnceLock::new();
+    STYLESHEET.get_or_init(|| CssParser::new(USER_AGENT_CSS).parse_stylesheet())
+}
+
+// The style tree owns what it took from the DOM, so the DOM can change
+// underneath it: mutated elements are marked dirty and `restyle` recomputes
+// just those subtrees.
+pub struct StyledNode {
+    id: NodeId,
+    tag_name: Option<String>,
+    text: Option<String>,
+    styles: PropertyMap,
+    dirty: bool,
+    dirty_descendants: bool,
+    pub children: Vec<StyledNode>,
+}
+
+// The nodes of a style tree by id. Layout boxes only keep the id of their
+// node and look its style up here while they are laid out or painted.
+pub struct StyleIndex<'s> {
+    nodes: Vec<Option<&'s StyledNode>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub width: f32,
+    pub height: f32,
+}
+
+// what relative lengths of one element resolve against
+#[derive(Clone, Copy)]
+struct ComputeContext {
+    viewport: Viewport,
+    root_font_size: f32,
+    parent_font_size: f32,
 }
 
 pub enum Display {
     Block,
     Inline,
     InlineBlock,
+    Flex,
     None,
 }
 
-impl<'a> StyledNode<'a> {
-    pub fn new(node: &'a Node, stylesheet: &'a Stylesheet) -> StyledNode<'a> {
+#[derive(Clone, Copy)]
+pub enum Position {
+    Static,
+    Relative,
+    Absolute,
+    Fixed,
+}
+
+impl StyledNode {
+    pub fn new(node: &Node, stylesheet: &Stylesheet) -> StyledNode {
+        StyledNode::with_viewport(node, stylesheet, Viewport::default())
+    }
+
+    pub fn with_viewport(node: &Node, stylesheet: &Stylesheet, viewport: Viewport) -> StyledNode {
+        StyledNode::build(
+            node,
+            0,
+            &mut Vec::new(),
+            stylesheet,
+            None,
+            ComputeContext::new(viewport),
+            &mut 0,
+        )
+    }
+
+    fn build<'n>(
+        node: &'n Node,
+        index: usize,
+        ancestors: &mut Vec<(&'n Node, usize)>,
+        stylesheet: &Stylesheet,
+        parent_styles: Option<&PropertyMap>,
+        context: ComputeContext,
+        next_id: &mut NodeId,
+    ) -> StyledNode {
+        let id = *next_id;
+        *next_id += 1;
+
+        let (tag_name, text, styles) = match node.node_type {
+            NodeType::Element(ref e) => {
+                let mut styles =
+                    StyledNode::get_styles(node, index, ancestors, stylesheet, parent_styles);
+                compute_values(&mut styles, &context);
+                (Some(e.tag_name.clone()), None, styles)
+            }
+            NodeType::Text(ref t) => (
+                None,
+                Some(t.clone()),
+                StyledNode::inherit_styles(parent_styles),
+            ),
+            NodeType::Comment(_) => (None, None, PropertyMap::new()),
+        };
+        let context = context.for_children(&styles, ancestors.is_empty());
+
         let mut style_children = Vec::new();
-        for child in &node.children {
+        ancestors.push((node, index));
+        for (i, child) in node.children.iter().enumerate() {
             match child.node_type {
-                NodeType::Element(_) => style_children.push(StyledNode::new(&child, stylesheet)),
-                _ => {}
+                NodeType::Element(_) | NodeType::Text(_) => style_children.push(StyledNode::build(
+                    child,
+                    i,
+                    ancestors,
+                    stylesheet,
+                    Some(&styles),
+                    context,
+                    next_id,
+                )),
+                // comments aren't styled but still take up an id
+                NodeType::Comment(_) => *next_id += 1,
             }
         }
+        ancestors.pop();
 
         StyledNode {
-            node,
-            styles: match node.node_type {
-                NodeType::Element(ref e) => StyledNode::get_styles(e, stylesheet),
-                _ => PropertyMap::new(),
-            },
+            id,
+            tag_name,
+            text,
+            styles,
+            dirty: false,
+            dirty_descendants: false,
             children: style_children,
         }
     }
 
-    fn get_styles(element: &'a ElementData, stylesheet: &'a Stylesheet) -> PropertyMap<'a> {
+    // Marks the element with `id` for restyling. Its later siblings are
+    // marked too, since `+` and `~` selectors reach them; descendants are
+    // always restyled along with a dirty node. Returns false if no styled
+    // node has that id.
+    pub fn invalidate(&mut self, id: NodeId) -> bool {
+        if self.id == id {
+            self.dirty = true;
+            return true;
+        }
+        // children are in id order: the one to descend into is the last that
+        // starts at or before `id`
+        let i = match self.children.iter().rposition(|c| c.id <= id) {
+            Some(i) => i,
+            None => return false,
+        };
+        let found = if self.children[i].id == id {
+            for sibling in &mut self.children[i..] {
+                sibling.dirty = true;
+            }
+            true
+        } else {
+            self.children[i].invalidate(id)
+        };
+        self.dirty_descendants |= found;
+        found
+    }
+
+    // Brings the tree up to date with `root`, the DOM it was built from,
+    // recomputing only the subtrees marked dirty. Returns the ids whose
+    // boxes need laying out again: every node whose computed values changed
+    // and their ancestors.
+    pub fn restyle(
+        &mut self,
+        root: &Node,
+        stylesheet: &Stylesheet,
+        viewport: Viewport,
+    ) -> Vec<NodeId> {
+        self.restyle_node(
+            root,
+            0,
+            &mut Vec::new(),
+            stylesheet,
+            None,
+            ComputeContext::new(viewport),
+        )
+    }
+
+    fn restyle_node<'n>(
+        &mut self,
+        node: &'n Node,
+        index: usize,
+        ancestors: &mut Vec<(&'n Node, usize)>,
+        stylesheet: &Stylesheet,
+        parent_styles: Option<&PropertyMap>,
+        context: ComputeContext,
+    ) -> Vec<NodeId> {
+        let mut changed = Vec::new();
+        if self.dirty {
+            let fresh = StyledNode::build(
+                node,
+                index,
+                ancestors,
+                stylesheet,
+                parent_styles,
+                context,
+                &mut self.id.clone(),
+            );
+            fresh.diff(self, &mut changed);
+            *self = fresh;
+            return changed;
+        }
+        if !self.dirty_descendants {
+            return changed;
+        }
+        self.dirty_descendants = false;
+
+        let context = context.for_children(&self.styles, ancestors.is_empty());
+        ancestors.push((node, index));
+        let dom_children = node
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !matches!(c.node_type, NodeType::Comment(_)));
+        for ((i, dom_child), child) in dom_children.zip(&mut self.children) {
+            changed.append(&mut child.restyle_node(
+                dom_child,
+                i,
+                ancestors,
+                stylesheet,
+                Some(&self.styles),
+                context,
+            ));
+        }
+        ancestors.pop();
+
+        if !changed.is_empty() {
+            changed.push(self.id);
+        }
+        changed
+    }
+
+    // Pushes the ids in this subtree whose styles or content differ from
+    // `old`, children before parents; a parent counts as changed whenever
+    // one of its descendants did.
+    fn diff(&self, old: &StyledNode, changed: &mut Vec<NodeId>) -> bool {
+        let mut differs = self.children.len() != old.children.len();
+        for (child, old_child) in self.children.iter().zip(&old.children) {
+            differs |= child.diff(old_child, changed);
+        }
+        differs |=
+            self.tag_name != old.tag_name || self.text != old.text || self.styles != old.styles;
+        if differs {
+            changed.push(self.id);
+        }
+        differs
+    }
+
+    fn get_styles(
+        node: &Node,
+        index: usize,
+        ancestors: &[(&Node, usize)],
+        stylesheet: &Stylesheet,
+        parent_styles: Option<&PropertyMap>,
+    ) -> PropertyMap {
         let mut styles = PropertyMap::new();
-        for rule in &stylesheet.rules {
-            for selector in &rule.selectors {
-                if selector_matches(element, &selector) {
-                    for declar in &rule.declarations {
-                        styles.insert(&declar.property, &declar.value);
+        for sheet in [user_agent_stylesheet(), stylesheet] {
+            for rule in &sheet.rules {
+                for selector in &rule.selectors {
+                    if selector_matches(node, index, ancestors, selector) {
+                        for declar in &rule.declarations {
+                            styles.insert(declar.property.clone(), declar.value.clone());
+                        }
+                        break;
                     }
-                    break;
                 }
             }
         }
 
+        let inherited = |name: &str| parent_styles.and_then(|p| p.get(name).cloned());
+
+        for name in INHERITED_PROPERTIES {
+            if !styles.contains_key(*name) {
+                if let Some(v) = inherited(name) {
+                    styles.insert(name.to_string(), v);
+                }
+            }
+        }
+
+        let keywords: Vec<(String, String)> = styles
+            .iter()
+            .filter_map(|(name, v)| match *v {
+                Value::Other(ref k) if matches!(k.as_str(), "inherit" | "initial" | "unset") => {
+                    Some((name.clone(), k.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        for (name, keyword) in keywords {
+            let inherit = match keyword.as_str() {
+                "inherit" => true,
+                "unset" => INHERITED_PROPERTIES.contains(&name.as_str()),
+                _ => false,
+            };
+            match inherited(&name) {
+                Some(v) if inherit => styles.insert(name, v),
+                _ => styles.remove(&name),
+            };
+        }
+
+        // `color: currentcolor` means the parent's color, everywhere else it
+        // is this element's own color
+        let is_current_color = |v: &Value| matches!(v, Value::Other(k) if k == "currentcolor");
+        if styles.get("color").is_some_and(is_current_color) {
+            match inherited("color") {
+                Some(v) => styles.insert("color".to_string(), v),
+                None => styles.remove("color"),
+            };
+        }
+        let color = match styles.get("color") {
+            Some(v) => v.clone(),
+            None => Value::Color(Color::new(0.0, 0.0, 0.0, 1.0)),
+        };
+        for value in styles.values_mut() {
+            if is_current_color(value) {
+                *value = color.clone();
+            }
+        }
+
+        styles
+    }
+
+    fn inherit_styles(parent_styles: Option<&PropertyMap>) -> PropertyMap {
+        let mut styles = PropertyMap::new();
+        if let Some(parent) = parent_styles {
+            for name in INHERITED_PROPERTIES {
+                if let Some(v) = parent.get(*name) {
+                    styles.insert(name.to_string(), v.clone());
+                }
+            }
+        }
         styles
     }
 
-    pub fn value(&self, name: &str) -> Option<&&Value> {
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn index(&self) -> StyleIndex<'_> {
+        let mut index = StyleIndex { nodes: Vec::new() };
+        index.insert(self);
+        index
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    pub fn tag_name(&self) -> Option<&str> {
+        self.tag_name.as_deref()
+    }
+
+    pub fn value(&self, name: &str) -> Option<&Value> {
         self.styles.get(name)
     }
 
     pub fn get_display(&self) -> Display {
-        match self.value("disaply") {
-            Some(s) => match **s {
+        match self.value("display") {
+            Some(s) => match *s {
                 Value::Other(ref v) => match v.as_ref() {
                     "block" => Display::Block,
                     "none" => Display::None,
                     "inline-block" => Display::InlineBlock,
+                    "flex" => Display::Flex,
                     _ => Display::Inline,
                 },
                 _ => Display::Inline,
@@ -74,9 +420,21 @@ impl<'a> StyledNode<'a> {
         }
     }
 
+    pub fn get_position(&self) -> Position {
+        match self.value("position") {
+            Some(Value::Other(p)) => match p.as_ref() {
+                "relative" => Position::Relative,
+                "absolute" => Position::Absolute,
+                "fixed" => Position::Fixed,
+                _ => Position::Static,
+            },
+            _ => Position::Static,
+        }
+    }
+
     pub fn num_or(&self, name: &str, default: f32) -> f32 {
         match self.value(name) {
-            Some(v) => match **v {
+            Some(v) => match *v {
                 Value::Length(n, _) => n,
                 _ => default,
             },
@@ -85,52 +443,233 @@ impl<'a> StyledNode<'a> {
     }
 }
 
-impl<'a> fmt::Debug for StyledNode<'a> {
+impl<'s> StyleIndex<'s> {
+    fn insert(&mut self, node: &'s StyledNode) {
+        if self.nodes.len() <= node.id {
+            self.nodes.resize(node.id + 1, None);
+        }
+        self.nodes[node.id] = Some(node);
+        for child in &node.children {
+            self.insert(child);
+        }
+    }
+
+    // Panics if `id` has no styled node: only ids taken from boxes laid out
+    // from the same style tree are looked up.
+    pub fn get(&self, id: NodeId) -> &'s StyledNode {
+        self.nodes[id].expect("no styled node with that id")
+    }
+}
+
+impl fmt::Debug for StyledNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}: {:?}", self.node, self.styles)
+        match (&self.tag_name, &self.text) {
+            (Some(tag_name), _) => write!(f, "<{}>: {:?}", tag_name, self.styles),
+            (_, Some(text)) => write!(f, "{:?}: {:?}", text, self.styles),
+            _ => write!(f, "{:?}", self.styles),
+        }
     }
 }
 
-fn selector_matches(element: &ElementData, selector: &Selector) -> bool {
-    for simple in &selector.simple {
-        let mut selector_match = true;
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            width: 800.0,
+            height: 600.0,
+        }
+    }
+}
 
-        match simple.tag_name {
-            Some(ref t) => {
-                if *t != element.tag_name {
-                    continue;
-                }
-            }
-            None => {}
-        };
+impl ComputeContext {
+    fn new(viewport: Viewport) -> ComputeContext {
+        ComputeContext {
+            viewport,
+            root_font_size: DEFAULT_FONT_SIZE,
+            parent_font_size: DEFAULT_FONT_SIZE,
+        }
+    }
 
-        match element.get_id() {
-            Some(i) => match simple.id {
-                Some(ref id) => {
-                    if *i != *id {
-                        continue;
-                    }
-                }
-                None => {}
-            },
-            None => match simple.id {
-                Some(_) => {
-                    continue;
-                }
-                _ => {}
-            },
+    // the context the children of an element with `styles` resolve against
+    fn for_children(mut self, styles: &PropertyMap, is_root: bool) -> ComputeContext {
+        if let Some(Value::Length(size, Unit::Px)) = styles.get("font-size") {
+            self.parent_font_size = *size;
+            if is_root {
+                self.root_font_size = *size;
+            }
         }
-        let element_classes = element.get_classes();
+        self
+    }
+}
 
-        for class in &simple.classes {
-            selector_match &= element_classes.contains::<str>(class);
+// Turns every length into px, except percentages that depend on the
+// containing block, which layout resolves. `font-size` goes first since
+// `em` and `ex` in the other properties are relative to it.
+fn compute_values(styles: &mut PropertyMap, context: &ComputeContext) {
+    let parent = context.parent_font_size;
+    let font_size = match styles.get("font-size") {
+        Some(Value::Length(n, Unit::Pct)) => Some(n * parent / 100.0),
+        Some(Value::Length(n, unit)) => Some(to_px(*n, *unit, parent, context)),
+        Some(Value::Other(keyword)) => font_size_keyword(keyword, parent),
+        _ => None,
+    };
+    if let Some(size) = font_size {
+        styles.insert("font-size".to_string(), Value::Length(size, Unit::Px));
+    }
+    let font_size = font_size.unwrap_or(parent);
+
+    for (name, value) in styles.iter_mut() {
+        match *value {
+            Value::Length(_, Unit::Px) => {}
+            Value::Length(n, Unit::Pct) if *name == "line-height" => {
+                *value = Value::Length(n * font_size / 100.0, Unit::Px)
+            }
+            Value::Length(_, Unit::Pct) => {}
+            Value::Length(n, unit) => {
+                *value = Value::Length(to_px(n, unit, font_size, context), Unit::Px)
+            }
+            _ => {}
         }
+    }
+}
 
-        if selector_match {
-            return true;
+fn to_px(n: f32, unit: Unit, font_size: f32, context: &ComputeContext) -> f32 {
+    let viewport = context.viewport;
+    match unit {
+        Unit::Px => n,
+        Unit::Em => n * font_size,
+        // the fixed-advance font has no x-height of its own; CSS falls back
+        // to half an em in that case
+        Unit::Ex => n * font_size * 0.5,
+        Unit::Ch => n * font_size * GLYPH_ADVANCE,
+        Unit::Rem => n * context.root_font_size,
+        Unit::Vw => n * viewport.width / 100.0,
+        Unit::Vh => n * viewport.height / 100.0,
+        Unit::Vmin => n * viewport.width.min(viewport.height) / 100.0,
+        Unit::Vmax => n * viewport.width.max(viewport.height) / 100.0,
+        Unit::In => n * PX_PER_IN,
+        Unit::Cm => n * PX_PER_IN / 2.54,
+        Unit::Mm => n * PX_PER_IN / 25.4,
+        Unit::Q => n * PX_PER_IN / 101.6,
+        Unit::Pt => n * PX_PER_IN / 72.0,
+        Unit::Pc => n * PX_PER_IN / 6.0,
+        Unit::Pct => n * font_size / 100.0,
+    }
+}
+
+fn font_size_keyword(keyword: &str, parent: f32) -> Option<f32> {
+    match keyword {
+        "xx-small" => Some(9.0),
+        "x-small" => Some(10.0),
+        "small" => Some(13.0),
+        "medium" => Some(DEFAULT_FONT_SIZE),
+        "large" => Some(18.0),
+        "x-large" => Some(24.0),
+        "xx-large" => Some(32.0),
+        "xxx-large" => Some(48.0),
+        "smaller" => Some(parent / 1.2),
+        "larger" => Some(parent * 1.2),
+        _ => None,
+    }
+}
+
+// `index` is the position of `node` among its parent's children and
+// `ancestors` runs from the root down to that parent, each entry paired with
+// its own index, so combinators can walk up and sideways without parent links.
+pub(crate) fn selector_matches(
+    node: &Node,
+    index: usize,
+    ancestors: &[(&Node, usize)],
+    selector: &Selector,
+) -> bool {
+    match selector.simple.last() {
+        Some(simple) => {
+            simple_selector_matches(node, simple)
+                && combinators_match(selector, selector.simple.len() - 1, index, ancestors)
         }
+        None => false,
+    }
+}
+
+// `selector.simple[matched]` has matched the node at `index`; check the
+// compound selectors to its left against the nodes its combinator points to.
+fn combinators_match(
+    selector: &Selector,
+    matched: usize,
+    index: usize,
+    ancestors: &[(&Node, usize)],
+) -> bool {
+    if matched == 0 {
+        return true;
+    }
+    let simple = &selector.simple[matched - 1];
+
+    match selector.combinators.get(matched - 1) {
+        Some(' ') => (0..ancestors.len()).rev().any(|i| {
+            let (ancestor, ancestor_index) = ancestors[i];
+            simple_selector_matches(ancestor, simple)
+                && combinators_match(selector, matched - 1, ancestor_index, &ancestors[..i])
+        }),
+        Some('>') => match ancestors.split_last() {
+            Some((&(parent, parent_index), rest)) => {
+                simple_selector_matches(parent, simple)
+                    && combinators_match(selector, matched - 1, parent_index, rest)
+            }
+            None => false,
+        },
+        Some('+') => match previous_siblings(index, ancestors).next() {
+            Some((i, sibling)) => {
+                simple_selector_matches(sibling, simple)
+                    && combinators_match(selector, matched - 1, i, ancestors)
+            }
+            None => false,
+        },
+        Some('~') => previous_siblings(index, ancestors).any(|(i, sibling)| {
+            simple_selector_matches(sibling, simple)
+                && combinators_match(selector, matched - 1, i, ancestors)
+        }),
+        _ => false,
+    }
+}
+
+// element siblings before `index`, nearest first
+fn previous_siblings<'n>(
+    index: usize,
+    ancestors: &[(&'n Node, usize)],
+) -> impl Iterator<Item = (usize, &'n Node)> {
+    let siblings: &'n [Node] = match ancestors.last() {
+        Some(&(parent, _)) => &parent.children[..index],
+        None => &[],
+    };
+    siblings
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, n)| matches!(n.node_type, NodeType::Element(_)))
+}
+
+fn simple_selector_matches(node: &Node, simple: &SimpleSelector) -> bool {
+    let element = match node.node_type {
+        NodeType::Element(ref e) => e,
+        _ => return false,
+    };
+
+    if simple
+        .tag_name
+        .as_ref()
+        .is_some_and(|t| *t != element.tag_name)
+    {
+        return false;
+    }
+
+    if simple.id.is_some() && element.get_id() != simple.id.as_ref() {
+        return false;
     }
-    false
+
+    let element_classes = element.get_classes();
+    simple
+        .classes
+        .iter()
+        .all(|class| element_classes.contains::<str>(class))
 }
 
 fn pretty_print(node: &StyledNode, indent_size: usize) {
@@ -141,3 +680,198 @@ fn pretty_print(node: &StyledNode, indent_size: usize) {
         pretty_print(&child, indent_size + 2);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css_parser::CssParser;
+    use crate::dom::{AttrMap, ElementData};
+
+    fn elem(tag: &str, class: Option<&str>, children: Vec<Node>) -> Node {
+        let mut attributes = AttrMap::new();
+        if let Some(class) = class {
+            attributes.insert("class".to_string(), class.to_string());
+        }
+        Node::new(
+            NodeType::Element(ElementData::new(tag.to_string(), attributes)),
+            children,
+        )
+    }
+
+    fn content_of(node: &StyledNode) -> Option<&str> {
+        node.value("content").map(|v| match *v {
+            Value::Other(ref s) => s.as_str(),
+            _ => "",
+        })
+    }
+
+    // <div>
+    //   <p class="a"></p>
+    //   <section><p></p></section>
+    //   <span></span>
+    // </div>
+    fn fixture() -> Node {
+        elem(
+            "div",
+            None,
+            vec![
+                elem("p", Some("a"), vec![]),
+                elem("section", None, vec![elem("p", None, vec![])]),
+                elem("span", None, vec![]),
+            ],
+        )
+    }
+
+    #[test]
+    fn descendant_and_child_combinators() {
+        let dom = fixture();
+        let css = "div p { content: x; } div > p { content: y; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+
+        assert_eq!(content_of(&styled), None);
+        assert_eq!(content_of(&styled.children[0]), Some("y"));
+        assert_eq!(content_of(&styled.children[1]), None);
+        assert_eq!(content_of(&styled.children[1].children[0]), Some("x"));
+    }
+
+    #[test]
+    fn sibling_combinators() {
+        let dom = fixture();
+        let css = "p.a ~ span { content: x; } p + section { content: y; } p + span { content: z; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+
+        assert_eq!(content_of(&styled.children[0]), None);
+        assert_eq!(content_of(&styled.children[1]), Some("y"));
+        assert_eq!(content_of(&styled.children[2]), Some("x"));
+    }
+
+    #[test]
+    fn restyle_recomputes_only_dirty_subtrees() {
+        let mut dom = fixture();
+        let css = "p.a ~ span { content: x; } .b p { content: y; } .c { content: z; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let mut styled = StyledNode::new(&dom, &stylesheet);
+        assert_eq!(content_of(&styled.children[2]), Some("x"));
+
+        let mut set_class = |id, class| {
+            let element = dom.get_mut(id).unwrap().element_mut().unwrap();
+            element.set_attribute("class", class);
+        };
+        // the first <p> changes without being invalidated, so it keeps its
+        // old styles, but the <span> after the <section> is restyled with it
+        set_class(1, "c");
+        set_class(2, "b");
+        assert!(styled.invalidate(2));
+        assert!(!styled.invalidate(9));
+
+        let changed = styled.restyle(&dom, &stylesheet, Viewport::default());
+        assert_eq!(changed, [3, 2, 4, 0]);
+        assert_eq!(content_of(&styled.children[0]), None);
+        assert_eq!(content_of(&styled.children[1].children[0]), Some("y"));
+        assert_eq!(content_of(&styled.children[2]), None);
+
+        assert!(styled
+            .restyle(&dom, &stylesheet, Viewport::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn inherited_properties_and_keywords() {
+        let dom = fixture();
+        let css = "div { font-family: serif; content: x; } \
+                   section { font-family: initial; content: inherit; } \
+                   span { font-family: unset; content: unset; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let font_of = |n: &StyledNode| n.value("font-family").map(|v| format!("{:?}", v));
+
+        assert_eq!(font_of(&styled.children[0]), Some("\"serif\"".to_string()));
+        assert_eq!(content_of(&styled.children[0]), None);
+        assert_eq!(font_of(&styled.children[1]), None);
+        assert_eq!(content_of(&styled.children[1]), Some("x"));
+        assert_eq!(font_of(&styled.children[1].children[0]), None);
+        assert_eq!(font_of(&styled.children[2]), Some("\"serif\"".to_string()));
+        assert_eq!(content_of(&styled.children[2]), None);
+    }
+
+    #[test]
+    fn current_color_resolves_to_the_color_property() {
+        let dom = elem("div", None, vec![elem("p", None, vec![])]);
+        let css = "div { color: rgba(255, 0, 0, 0.5); } \
+                   p { color: currentColor; border: 1px solid; background-color: currentcolor; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+        let p = &styled.children[0];
+
+        let red = Value::Color(Color::new(1.0, 0.0, 0.0, 0.5));
+        assert!(p.value("color") == Some(&red));
+        assert!(p.value("border-top-color") == Some(&red));
+        assert!(p.value("background-color") == Some(&red));
+    }
+
+    #[test]
+    fn user_agent_defaults() {
+        let dom = elem(
+            "div",
+            None,
+            vec![elem("h1", None, vec![]), elem("span", None, vec![])],
+        );
+        let stylesheet = CssParser::new("h1 { margin-top: 0px; }").parse_stylesheet();
+        let styled = StyledNode::new(&dom, &stylesheet);
+
+        assert!(matches!(styled.get_display(), Display::Block));
+        assert!(matches!(styled.children[0].get_display(), Display::Block));
+        assert!(matches!(styled.children[1].get_display(), Display::Inline));
+        assert_eq!(styled.children[0].num_or("margin-top", -1.0), 0.0);
+        assert!((styled.children[0].num_or("margin-bottom", -1.0) - 21.44).abs() < 1e-4);
+        assert!(styled.children[1].value("font-size").is_none());
+    }
+
+    #[test]
+    fn lengths_compute_to_pixels() {
+        let dom = elem(
+            "div",
+            None,
+            vec![elem("p", None, vec![elem("span", None, vec![])])],
+        );
+        let css = "div { font-size: 20px; width: 50vw; height: 10vmin; margin-top: 1in; } \
+                   p { font-size: 150%; width: 2em; height: 1rem; margin-top: 6pt; \
+                       padding-top: 2.5mm; padding-left: 10%; line-height: 1.5em; } \
+                   span { font-size: larger; width: 2ch; height: 1ex; margin-top: 1pc; }";
+        let stylesheet = CssParser::new(css).parse_stylesheet();
+        let viewport = Viewport {
+            width: 1000.0,
+            height: 500.0,
+        };
+        let styled = StyledNode::with_viewport(&dom, &stylesheet, viewport);
+        let px = |n: &StyledNode, name: &str| match n.value(name) {
+            Some(Value::Length(v, Unit::Px)) => *v,
+            _ => panic!("{} is not in px", name),
+        };
+
+        assert_eq!(px(&styled, "width"), 500.0);
+        assert_eq!(px(&styled, "height"), 50.0);
+        assert_eq!(px(&styled, "margin-top"), 96.0);
+
+        let p = &styled.children[0];
+        assert_eq!(px(p, "font-size"), 30.0);
+        assert_eq!(px(p, "width"), 60.0);
+        assert_eq!(px(p, "height"), 20.0);
+        assert_eq!(px(p, "margin-top"), 8.0);
+        assert!((px(p, "padding-top") - 9.448819).abs() < 1e-4);
+        assert!(matches!(
+            p.value("padding-left"),
+            Some(Value::Length(_, Unit::Pct))
+        ));
+        assert_eq!(px(p, "line-height"), 45.0);
+
+        let span = &p.children[0];
+        assert_eq!(px(span, "font-size"), 36.0);
+        assert_eq!(px(span, "width"), 36.0);
+        assert_eq!(px(span, "height"), 18.0);
+        assert_eq!(px(span, "margin-top"), 16.0);
+        assert_eq!(px(span, "line-height"), 45.0);
+    }
+}
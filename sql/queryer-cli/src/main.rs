@@ -0,0 +1,243 @@
+use anyhow::{anyhow, Result};
+use queryer::{query, schema, DataSet, TyrDialect};
+use rustyline::{error::ReadlineError, Editor};
+use sqlparser::keywords::{Keyword, RESERVED_FOR_TABLE_ALIAS};
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const HELP: &str = r"\register <name> <source>  给数据源起一个短名字，不带参数时列出所有名字
+\schema <source>            显示数据源每一列的类型
+\format table|csv|json|markdown
+                            切换输出格式
+\help                       显示帮助
+\quit                       退出
+其它输入都当作 SQL 执行";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Table,
+    Csv,
+    Json,
+    Markdown,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => Err(anyhow!("unknown format {}", s)),
+        }
+    }
+}
+
+struct Repl {
+    // 注册的名字 -> 数据源
+    sources: HashMap<String, String>,
+    format: Format,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self {
+            sources: HashMap::new(),
+            format: Format::Table,
+        }
+    }
+}
+
+impl Repl {
+    /// 执行一行输入，返回要打印的内容
+    async fn execute(&mut self, line: &str) -> Result<String> {
+        let command = match line.strip_prefix('\\') {
+            Some(command) => command,
+            None => {
+                let sql = self.resolve(line.trim_end_matches(';'))?;
+                return self.render(&query(sql).await?);
+            }
+        };
+
+        let args: Vec<&str> = command.split_whitespace().collect();
+        match args.as_slice() {
+            ["register"] => {
+                let mut names: Vec<_> = self.sources.iter().collect();
+                names.sort();
+                let lines: Vec<String> = names
+                    .into_iter()
+                    .map(|(name, source)| format!("{} => {}", name, source))
+                    .collect();
+                Ok(lines.join("\n"))
+            }
+            ["register", name, source] => {
+                self.sources.insert(name.to_string(), source.to_string());
+                Ok(format!("registered {}", name))
+            }
+            ["schema", source] => {
+                let source = self.sources.get(*source).map_or(*source, |s| s.as_str());
+                self.render(&schema(source).await?)
+            }
+            ["format", format] => {
+                self.format = format.parse()?;
+                Ok(format!("output format: {:?}", self.format))
+            }
+            ["help"] => Ok(HELP.to_owned()),
+            _ => Err(anyhow!("unknown command \\{}, try \\help", command)),
+        }
+    }
+
+    /// 把 FROM / JOIN 后面注册过的名字替换成对应的数据源
+    fn resolve(&self, sql: &str) -> Result<String> {
+        let dialect = TyrDialect::default();
+        let tokens = Tokenizer::new(&dialect, sql)
+            .tokenize()
+            .map_err(|e| anyhow!("{:?}", e))?;
+
+        let mut resolved = String::with_capacity(sql.len());
+        let mut after_from = false;
+        for (i, token) in tokens.iter().enumerate() {
+            if let Token::Word(w) = token {
+                let source = self.sources.get(&w.value);
+                if let (true, None, Some(source)) = (after_from, w.quote_style, source) {
+                    resolved.push_str(source);
+                    // 没有别名时用注册的名字做别名，`名字.列名` 才能继续使用
+                    if !has_alias(&tokens[i + 1..]) {
+                        resolved.push(' ');
+                        resolved.push_str(&w.value);
+                    }
+                    after_from = false;
+                    continue;
+                }
+            }
+            if !matches!(token, Token::Whitespace(_)) {
+                after_from = matches!(token, Token::Word(w) if w.keyword == Keyword::FROM || w.keyword == Keyword::JOIN);
+            }
+            resolved.push_str(&to_sql(token));
+        }
+        Ok(resolved)
+    }
+
+    fn render(&self, ds: &DataSet) -> Result<String> {
+        match self.format {
+            Format::Table => Ok(ds.to_table()),
+            Format::Csv => ds.to_csv(),
+            Format::Json => ds.to_json(),
+            Format::Markdown => Ok(ds.to_markdown()),
+        }
+    }
+}
+
+// 数据源后面是否跟着别名，判断的方法和 sqlparser 一样
+fn has_alias(rest: &[Token]) -> bool {
+    match rest.iter().find(|t| !matches!(t, Token::Whitespace(_))) {
+        Some(Token::Word(w)) => {
+            w.keyword == Keyword::AS || !RESERVED_FOR_TABLE_ALIAS.contains(&w.keyword)
+        }
+        _ => false,
+    }
+}
+
+// sqlparser 输出字符串时不会把 `'` 转义回 `''`
+fn to_sql(token: &Token) -> String {
+    match token {
+        Token::SingleQuotedString(s) => format!("'{}'", s.replace('\'', "''")),
+        Token::NationalStringLiteral(s) => format!("N'{}'", s.replace('\'', "''")),
+        token => token.to_string(),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut repl = Repl::default();
+    let mut rl = Editor::<()>::new();
+    println!("queryer, type \\help for help");
+
+    loop {
+        let line = match rl.readline("queryer> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        rl.add_history_entry(line);
+        if line == "\\quit" || line == "\\q" {
+            break;
+        }
+
+        match repl.execute(line).await {
+            Ok(output) => println!("{}", output.trim_end()),
+            Err(e) => eprintln!("error: {:#}", e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_registered_sources() {
+        let mut repl = Repl::default();
+        repl.sources
+            .insert("covid".into(), "https://abc.xyz/covid.csv".into());
+        repl.sources.insert("c".into(), "file:///tmp/c.csv".into());
+
+        let sql = repl
+            .resolve("SELECT covid.id, c.name FROM covid JOIN c ON covid.id = c.id WHERE c.name = 'O''Brien'")
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT covid.id, c.name FROM https://abc.xyz/covid.csv covid JOIN file:///tmp/c.csv c ON covid.id = c.id WHERE c.name = 'O''Brien'"
+        );
+
+        let sql = repl
+            .resolve("SELECT x.id FROM covid AS x LEFT JOIN c y ON x.id = y.id")
+            .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT x.id FROM https://abc.xyz/covid.csv AS x LEFT JOIN file:///tmp/c.csv y ON x.id = y.id"
+        );
+    }
+
+    #[tokio::test]
+    async fn registered_sources_can_be_queried() {
+        let dir = std::env::temp_dir();
+        let people = dir.join("queryer_cli_people.csv");
+        let orders = dir.join("queryer_cli_orders.csv");
+        std::fs::write(&people, "id,name\n1,O'Brien\n2,bob\n").unwrap();
+        std::fs::write(&orders, "id,person_id,amount\n1,1,10\n2,2,20\n").unwrap();
+
+        let mut repl = Repl::default();
+        repl.format = Format::Csv;
+        repl.sources
+            .insert("people".into(), format!("file://{}", people.display()));
+        repl.sources
+            .insert("orders".into(), format!("file://{}", orders.display()));
+
+        let output = repl
+            .execute(
+                "SELECT people.name, amount FROM people JOIN orders ON people.id = orders.person_id \
+                 WHERE people.name = 'O''Brien'",
+            )
+            .await
+            .unwrap();
+        assert_eq!(output, "people.name,amount\nO'Brien,10\n");
+    }
+
+    #[test]
+    fn parse_format_works() {
+        assert_eq!("JSON".parse::<Format>().unwrap(), Format::Json);
+        assert_eq!("md".parse::<Format>().unwrap(), Format::Markdown);
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,22 @@
+import { fileURLToPath } from 'url'
+
+import test from 'ava'
+
+import { query } from '../index.js'
+
+const source = `file://${fileURLToPath(new URL('./fixtures/covid.csv', import.meta.url))}`
+
+test('query returns csv by default', async (t) => {
+  const csv = await query(`SELECT location FROM ${source} WHERE new_cases > 20 ORDER BY new_cases DESC`)
+  t.is(csv, 'location\nUSA\nUK\n')
+})
+
+test('query returns json', async (t) => {
+  const json = await query(`SELECT location, new_cases FROM ${source} LIMIT 1`, 'json')
+  t.deepEqual(JSON.parse(json), [{ location: 'China', new_cases: 10 }])
+})
+
+test('query rejects on errors', async (t) => {
+  await t.throwsAsync(() => query(`SELECT * FROM ${source}`, 'xml'), { message: /unknown format/ })
+  await t.throwsAsync(() => query('SELECT * FROM ftp://abc.xyz/data.csv'))
+})
@@ -0,0 +1,25 @@
+use napi::{Error, Result, Status};
+use napi_derive::napi;
+
+fn to_napi_error(e: anyhow::Error) -> Error {
+    Error::new(Status::GenericFailure, format!("{:#}", e))
+}
+
+/// 执行 SQL，按 format 返回 CSV（默认）或者 JSON 字符串
+#[napi]
+pub async fn query(sql: String, format: Option<String>) -> Result<String> {
+    let format = format.unwrap_or_else(|| "csv".into());
+    if format != "csv" && format != "json" {
+        return Err(Error::new(
+            Status::InvalidArg,
+            format!("unknown format {}, expect csv or json", format),
+        ));
+    }
+
+    let ds = queryer::query(sql).await.map_err(to_napi_error)?;
+    match format.as_str() {
+        "json" => ds.to_json(),
+        _ => ds.to_csv(),
+    }
+    .map_err(to_napi_error)
+}
@@ -0,0 +1,54 @@
+use once_cell::sync::Lazy;
+use polars::prelude::*;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use queryer::DataSet;
+use tokio::runtime::Runtime;
+
+// python 这边是同步调用，所有的查询都在这个 runtime 上执行
+static RUNTIME: Lazy<Runtime> =
+    Lazy::new(|| Runtime::new().expect("failed to start tokio runtime"));
+
+fn run(py: Python, sql: String) -> PyResult<DataSet> {
+    // 查询期间释放 GIL，不阻塞其它 python 线程
+    py.allow_threads(|| RUNTIME.block_on(queryer::query(sql)))
+        .map_err(|e| PyRuntimeError::new_err(format!("{:#}", e)))
+}
+
+/// 执行 SQL，每一行返回一个 dict
+#[pyfunction]
+fn query(py: Python, sql: String) -> PyResult<PyObject> {
+    let json = run(py, sql)?
+        .to_json()
+        .map_err(|e| PyRuntimeError::new_err(format!("{:#}", e)))?;
+    let rows = py.import("json")?.call_method1("loads", (json,))?;
+    Ok(rows.into())
+}
+
+/// 执行 SQL，返回 CSV 或者 Arrow IPC 格式的 bytes
+#[pyfunction]
+#[pyo3(text_signature = "(sql, format=\"csv\")")]
+fn query_bytes(py: Python, sql: String, format: Option<&str>) -> PyResult<Py<PyBytes>> {
+    let ds = run(py, sql)?;
+    let data = match format.unwrap_or("csv") {
+        "csv" => ds.to_csv().map(String::into_bytes),
+        "ipc" | "arrow" => {
+            let mut buf = Vec::new();
+            match IpcWriter::new(&mut buf).finish(&ds) {
+                Ok(_) => Ok(buf),
+                Err(e) => Err(e.into()),
+            }
+        }
+        format => return Err(PyValueError::new_err(format!("unknown format {}", format))),
+    };
+    let data = data.map_err(|e| PyRuntimeError::new_err(format!("{:#}", e)))?;
+    Ok(PyBytes::new(py, &data).into())
+}
+
+#[pymodule]
+fn queryer_py(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(query, m)?)?;
+    m.add_function(wrap_pyfunction!(query_bytes, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,36 @@
+# 先在 queryer-py 目录下执行 `maturin develop --extras test`，再运行 `pytest tests`
+import io
+
+import pytest
+
+import queryer_py
+
+
+@pytest.fixture
+def source(tmp_path):
+    path = tmp_path / "data.csv"
+    path.write_text("name,v\nalice,1\nbob,2\ncarol,3\n")
+    return f"file://{path}"
+
+
+def test_query_returns_rows(source):
+    rows = queryer_py.query(f"SELECT name, v FROM {source} WHERE v >= 2")
+    assert rows == [{"name": "bob", "v": 2}, {"name": "carol", "v": 3}]
+
+
+def test_query_bytes_csv(source):
+    data = queryer_py.query_bytes(f"SELECT name FROM {source} LIMIT 1")
+    assert data == b"name\nalice\n"
+
+
+def test_query_bytes_ipc(source):
+    pa = pytest.importorskip("pyarrow")
+    data = queryer_py.query_bytes(f"SELECT * FROM {source}", "ipc")
+    table = pa.ipc.open_file(io.BytesIO(data)).read_all()
+    assert table.column_names == ["name", "v"]
+    assert table.num_rows == 3
+
+
+def test_query_error(source):
+    with pytest.raises(RuntimeError):
+        queryer_py.query(f"SELECT missing FROM {source}")
@@ -0,0 +1,339 @@
+use crate::fetcher::{retrieve_data, Validator};
+use crate::loader::detect_content;
+use crate::DataSet;
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tempfile::NamedTempFile;
+use tracing::{info, warn};
+
+const DEFAULT_CAPACITY: usize = 16;
+
+static CACHE: Lazy<Cache> = Lazy::new(|| Cache::new(DEFAULT_CAPACITY));
+
+/// query 使用的全局缓存
+pub fn cache() -> &'static Cache {
+    &CACHE
+}
+
+/// 按数据源缓存解析好的 DataFrame。
+/// 内存里是一个 LRU，设置了目录的话同时存到磁盘上，重启之后还能用。
+/// 每次使用前都会用 ETag / Last-Modified 或者文件的修改时间确认数据没有变化
+pub struct Cache {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    capacity: usize,
+    dir: Option<PathBuf>,
+    entries: HashMap<String, Entry>,
+    // 最近使用的数据源排在最后
+    order: VecDeque<String>,
+}
+
+#[derive(Clone)]
+struct Entry {
+    validator: Validator,
+    frame: DataFrame,
+}
+
+// 磁盘上和 parquet 文件放在一起的元数据
+#[derive(Serialize, Deserialize)]
+struct Meta {
+    source: String,
+    validator: Validator,
+}
+
+impl Cache {
+    /// 内存里最多保存 capacity 个数据源，为 0 时只用磁盘缓存
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                capacity,
+                dir: None,
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.capacity = capacity;
+        inner.evict();
+    }
+
+    /// 设置磁盘缓存的目录，目录不存在时会创建。None 表示不使用磁盘缓存
+    pub fn set_dir(&self, dir: Option<PathBuf>) -> Result<()> {
+        if let Some(dir) = &dir {
+            fs::create_dir_all(dir)?;
+        }
+        self.inner.lock().unwrap().dir = dir;
+        Ok(())
+    }
+
+    /// 清空内存里的缓存，磁盘上的文件保持不变
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.order.clear();
+    }
+
+    /// 内存里缓存的数据源个数
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 读取数据源，缓存的数据还有效时直接使用缓存
+    pub(crate) async fn load(&self, source: &str) -> Result<DataSet> {
+        let cached = self.get(source).await;
+        let data = match (
+            retrieve_data(source, cached.as_ref().map(|e| &e.validator)).await?,
+            cached,
+        ) {
+            (Some(data), _) => data,
+            (None, Some(entry)) => {
+                info!("{} is not modified, use cached data", source);
+                return Ok(DataSet(entry.frame));
+            }
+            (None, None) => return Err(anyhow!("{} is not modified", source)),
+        };
+
+        let validator = data.validator.clone();
+        let ds = detect_content(data).load()?;
+        // 没有办法确认数据有没有变化的数据源就不缓存了
+        if !validator.is_empty() {
+            self.put(
+                source,
+                Entry {
+                    validator,
+                    frame: ds.0.clone(),
+                },
+            )
+            .await;
+        }
+        Ok(ds)
+    }
+
+    async fn get(&self, source: &str) -> Option<Entry> {
+        // 锁不能跨过 await
+        let dir = {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(entry) = inner.entries.get(source).cloned() {
+                inner.touch(source);
+                return Some(entry);
+            }
+            inner.dir.clone()?
+        };
+
+        // 读写磁盘缓存都是阻塞的，放到阻塞线程里执行
+        let key = source.to_owned();
+        let read: Result<Option<Entry>> =
+            tokio::task::spawn_blocking(move || read_entry(&dir, &key))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|r| r);
+        let entry = match read {
+            Ok(entry) => entry?,
+            Err(e) => {
+                warn!("failed to read cache of {}: {:?}", source, e);
+                return None;
+            }
+        };
+        self.inner.lock().unwrap().insert(source, entry.clone());
+        Some(entry)
+    }
+
+    async fn put(&self, source: &str, entry: Entry) {
+        let dir = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.insert(source, entry.clone());
+            inner.dir.clone()
+        };
+
+        if let Some(dir) = dir {
+            let key = source.to_owned();
+            let written = tokio::task::spawn_blocking(move || write_entry(&dir, &key, &entry))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|r| r);
+            if let Err(e) = written {
+                warn!("failed to write cache of {}: {:?}", source, e);
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn touch(&mut self, source: &str) {
+        if let Some(i) = self.order.iter().position(|s| s == source) {
+            let source = self.order.remove(i).unwrap();
+            self.order.push_back(source);
+        }
+    }
+
+    fn insert(&mut self, source: &str, entry: Entry) {
+        if self.entries.insert(source.to_owned(), entry).is_some() {
+            self.touch(source);
+        } else {
+            self.order.push_back(source.to_owned());
+        }
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.order.len() > self.capacity {
+            if let Some(source) = self.order.pop_front() {
+                self.entries.remove(&source);
+            }
+        }
+    }
+}
+
+// 数据源对应的元数据文件和 parquet 文件
+fn paths(dir: &Path, source: &str) -> (PathBuf, PathBuf) {
+    let name = format!("{:016x}", fnv1a(source));
+    (
+        dir.join(format!("{}.json", name)),
+        dir.join(format!("{}.parquet", name)),
+    )
+}
+
+// 文件名用的 hash 不能随 Rust 版本变化，否则升级之后磁盘缓存就都找不到了
+fn fnv1a(source: &str) -> u64 {
+    source.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn read_entry(dir: &Path, source: &str) -> Result<Option<Entry>> {
+    let (meta, data) = paths(dir, source);
+    if !meta.exists() {
+        return Ok(None);
+    }
+
+    let meta: Meta = serde_json::from_reader(File::open(meta)?)?;
+    // hash 冲突时当作没有缓存
+    if meta.source != source {
+        return Ok(None);
+    }
+    let frame = ParquetReader::new(File::open(data)?).finish()?;
+    Ok(Some(Entry {
+        validator: meta.validator,
+        frame,
+    }))
+}
+
+fn write_entry(dir: &Path, source: &str, entry: &Entry) -> Result<()> {
+    let (meta, data) = paths(dir, source);
+    // 都先写到临时文件再 rename 过去，所以文件总是完整的。
+    // 先换数据再换元数据，中途失败时留下的旧元数据只会让下次重新获取数据
+    let tmp = NamedTempFile::new_in(dir)?;
+    ParquetWriter::new(tmp.reopen()?).finish(&entry.frame)?;
+    tmp.persist(data)?;
+
+    let meta_data = Meta {
+        source: source.to_owned(),
+        validator: entry.validator.clone(),
+    };
+    let tmp = NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(tmp.as_file(), &meta_data)?;
+    tmp.persist(meta)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{serve, State};
+    use std::env;
+    use std::sync::atomic::Ordering;
+
+    fn entry(v: i64) -> Entry {
+        Entry {
+            validator: Validator::default(),
+            frame: df!("v" => &[v]).unwrap(),
+        }
+    }
+
+    #[test]
+    fn cache_file_names_are_stable() {
+        let (meta, data) = paths(Path::new("/cache"), "");
+        assert_eq!(meta, Path::new("/cache/cbf29ce484222325.json"));
+        assert_eq!(data, Path::new("/cache/cbf29ce484222325.parquet"));
+        assert_eq!(fnv1a("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[tokio::test]
+    async fn lru_evicts_least_recently_used() {
+        let cache = Cache::new(2);
+        cache.put("a", entry(1)).await;
+        cache.put("b", entry(2)).await;
+        assert!(cache.get("a").await.is_some());
+        cache.put("c", entry(3)).await;
+        assert!(cache.get("b").await.is_none());
+        assert!(cache.get("a").await.is_some());
+        assert!(cache.get("c").await.is_some());
+
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get("c").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn file_source_is_invalidated_by_mtime() {
+        let path = env::temp_dir().join("queryer_file_source_is_invalidated_by_mtime.csv");
+        std::fs::write(&path, "v\n1\n").unwrap();
+        let source = format!("file://{}", path.display());
+
+        let cache = Cache::new(4);
+        let ds = cache.load(&source).await.unwrap();
+        assert_eq!(ds.shape(), (1, 1));
+        assert_eq!(cache.len(), 1);
+        let ds = cache.load(&source).await.unwrap();
+        assert_eq!(ds.shape(), (1, 1));
+
+        std::fs::write(&path, "v\n1\n2\n").unwrap();
+        let ds = cache.load(&source).await.unwrap();
+        assert_eq!(ds.shape(), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn url_source_is_revalidated() {
+        let state = State::new("\"v1\"", "v\n1\n2\n");
+        let addr = serve(state.clone()).await;
+        let source = format!("http://{}/data.csv", addr);
+
+        let dir =
+            env::temp_dir().join(format!("queryer_url_source_is_revalidated_{}", addr.port()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = Cache::new(4);
+        cache.set_dir(Some(dir.clone())).unwrap();
+
+        assert_eq!(cache.load(&source).await.unwrap().shape(), (2, 1));
+        assert_eq!(cache.load(&source).await.unwrap().shape(), (2, 1));
+        assert_eq!(state.requests.load(Ordering::SeqCst), 2);
+        assert_eq!(state.not_modified.load(Ordering::SeqCst), 1);
+
+        // 新的缓存从磁盘读出数据，同样只需要确认数据没有变化
+        let cache = Cache::new(4);
+        cache.set_dir(Some(dir)).unwrap();
+        assert_eq!(cache.load(&source).await.unwrap().shape(), (2, 1));
+        assert_eq!(state.not_modified.load(Ordering::SeqCst), 2);
+
+        *state.content.lock().unwrap() = ("\"v2\"".into(), "v\n1\n2\n3\n".into());
+        assert_eq!(cache.load(&source).await.unwrap().shape(), (3, 1));
+        assert_eq!(state.requests.load(Ordering::SeqCst), 4);
+        assert_eq!(state.not_modified.load(Ordering::SeqCst), 2);
+    }
+}
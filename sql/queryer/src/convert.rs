@@ -1,18 +1,33 @@
 use anyhow::{anyhow, Result};
 use polars::prelude::*;
 use sqlparser::ast::{
-    BinaryOperator as SqlBinaryOperator, Expr as SqlExpr, Offset as SqlOffset, OrderByExpr, Select,
-    SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Value as SqlValue,
+    BinaryOperator as SqlBinaryOperator, DataType as SqlDataType, Expr as SqlExpr, Function,
+    FunctionArg, Ident, JoinConstraint, JoinOperator, Offset as SqlOffset, OrderByExpr, Select,
+    SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, UnaryOperator, Value as SqlValue,
 };
 
+/// COUNT(*) 数的是这一列，query 在聚合前会补上它
+pub(crate) const COUNT_ALL: &str = "__count_all";
+
+const AGGREGATES: [&str; 5] = ["COUNT", "SUM", "AVG", "MIN", "MAX"];
+
 /// 解析出来的 SQL
+#[derive(Debug)]
 pub struct Sql<'a> {
     pub(crate) selection: Vec<Expr>,
     pub(crate) condition: Option<Expr>,
+    pub(crate) group_by: Vec<Expr>,
+    // 聚合表达式，都已经 alias 成输出的列名
+    pub(crate) aggregation: Vec<Expr>,
+    pub(crate) having: Option<Expr>,
     pub(crate) source: &'a str,
+    pub(crate) alias: Option<&'a str>,
+    pub(crate) joins: Vec<Join<'a>>,
     pub(crate) order_by: Vec<(String, bool)>,
     pub(crate) offset: Option<i64>,
     pub(crate) limit: Option<usize>,
+    // 表达式里出现过的所有列名，EXPLAIN 用它们构造不带数据的数据源
+    pub(crate) identifiers: Vec<&'a str>,
 }
 
 // 因为 Rust trait 的孤儿规则，我们如果要想对已有的类型实现已有的 trait，
@@ -20,7 +35,27 @@ pub struct Sql<'a> {
 
 pub struct Expression(pub(crate) Box<SqlExpr>);
 pub struct Operation(pub(crate) SqlBinaryOperator);
+/// JOIN 进来的数据源
+#[derive(Debug, PartialEq)]
+pub struct Join<'a> {
+    pub(crate) source: &'a str,
+    pub(crate) alias: Option<&'a str>,
+    pub(crate) kind: JoinKind,
+    // ON 两边的列名，可以带上表的别名，比如 `a.id`
+    pub(crate) on: (String, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+}
+
 pub struct Projection<'a>(pub(crate) &'a SelectItem);
+pub struct Aggregation<'a>(pub(crate) &'a Function);
+pub struct Scalar(pub(crate) Function);
+pub struct Type(pub(crate) SqlDataType);
 pub struct Source<'a>(pub(crate) &'a [TableWithJoins]);
 pub struct Order<'a>(pub(crate) &'a OrderByExpr);
 pub struct Offset<'a>(pub(crate) &'a SqlOffset);
@@ -42,27 +77,106 @@ impl<'a> TryFrom<&'a Statement> for Sql<'a> {
                     from: table_with_joins,
                     selection: where_clause,
                     projection,
-
-                    group_by: _,
+                    group_by,
+                    having,
                     ..
                 } = match &q.body {
                     SetExpr::Select(statement) => statement.as_ref(),
                     _ => return Err(anyhow!("We only support Select Query at the moment")),
                 };
 
-                let source = Source(table_with_joins).try_into()?;
+                let ((source, alias), joins) = Source(table_with_joins).try_into()?;
 
                 let condition = match where_clause {
                     Some(expr) => Some(Expression(Box::new(expr.to_owned())).try_into()?),
                     None => None,
                 };
 
+                let mut group = Vec::with_capacity(group_by.len());
+                for expr in group_by {
+                    group.push(Expression(Box::new(expr.to_owned())).try_into()?);
+                }
+
+                // 有 GROUP BY 或者聚合函数时，聚合函数先算出来，
+                // 投影和 HAVING 里再按列名引用它们
+                let aggregate = !group.is_empty()
+                    || having.is_some()
+                    || projection.iter().any(|p| match p {
+                        SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                            has_aggregate(expr)
+                        }
+                        _ => false,
+                    });
+
+                let mut aggregation = Vec::new();
                 let mut selection = Vec::with_capacity(8);
                 for p in projection {
-                    let expr = Projection(p).try_into()?;
+                    let expr = match p {
+                        SelectItem::UnnamedExpr(SqlExpr::Function(f))
+                            if aggregate && is_aggregate(f) =>
+                        {
+                            let name = f.to_string();
+                            add_aggregation(&mut aggregation, f, &name)?;
+                            col(&name)
+                        }
+                        SelectItem::ExprWithAlias {
+                            expr: SqlExpr::Function(f),
+                            alias,
+                        } if aggregate && is_aggregate(f) => {
+                            add_aggregation(&mut aggregation, f, &alias.value)?;
+                            col(&alias.value)
+                        }
+                        // 包含聚合函数的表达式，比如 SUM(a) / COUNT(*)
+                        SelectItem::UnnamedExpr(expr) if aggregate && has_aggregate(expr) => {
+                            let name = expr.to_string();
+                            let expr = extract_aggregation(expr, &mut aggregation)?;
+                            Expr::try_from(Expression(Box::new(expr)))?.alias(&name)
+                        }
+                        SelectItem::ExprWithAlias { expr, alias }
+                            if aggregate && has_aggregate(expr) =>
+                        {
+                            let expr = extract_aggregation(expr, &mut aggregation)?;
+                            Expr::try_from(Expression(Box::new(expr)))?.alias(&alias.value)
+                        }
+                        p => Projection(p).try_into()?,
+                    };
                     selection.push(expr);
                 }
 
+                let mut identifiers = Vec::new();
+                let mut aliases = Vec::new();
+                for p in projection {
+                    match p {
+                        SelectItem::UnnamedExpr(expr) => {
+                            collect_identifiers(expr, &mut identifiers)
+                        }
+                        SelectItem::ExprWithAlias { expr, alias } => {
+                            collect_identifiers(expr, &mut identifiers);
+                            aliases.push(alias.value.as_str());
+                        }
+                        _ => {}
+                    }
+                }
+                // 其它地方引用的投影别名是算出来的列，不是数据源里的列
+                let mut referenced = Vec::new();
+                let exprs = where_clause.iter().chain(group_by).chain(having);
+                for expr in exprs.chain(orders.iter().map(|o| &o.expr)) {
+                    collect_identifiers(expr, &mut referenced);
+                }
+                for name in referenced {
+                    if !aliases.contains(&name) && !identifiers.contains(&name) {
+                        identifiers.push(name);
+                    }
+                }
+
+                let having = match having {
+                    Some(expr) => {
+                        let expr = extract_aggregation(expr, &mut aggregation)?;
+                        Some(Expression(Box::new(expr)).try_into()?)
+                    }
+                    None => None,
+                };
+
                 let mut order_by = Vec::new();
                 for expr in orders {
                     order_by.push(Order(expr).try_into()?);
@@ -74,10 +188,16 @@ impl<'a> TryFrom<&'a Statement> for Sql<'a> {
                 Ok(Sql {
                     selection,
                     condition,
+                    group_by: group,
+                    aggregation,
+                    having,
                     source,
+                    alias,
+                    joins,
                     order_by,
                     offset,
                     limit,
+                    identifiers,
                 })
             }
             _ => Err(anyhow!("We only support Query at the moment")),
@@ -85,17 +205,224 @@ impl<'a> TryFrom<&'a Statement> for Sql<'a> {
     }
 }
 
+fn collect_identifiers<'a>(expr: &'a SqlExpr, identifiers: &mut Vec<&'a str>) {
+    let mut children: Vec<&SqlExpr> = Vec::new();
+    match expr {
+        SqlExpr::Identifier(id) => {
+            if !identifiers.contains(&id.value.as_str()) {
+                identifiers.push(&id.value);
+            }
+        }
+        SqlExpr::BinaryOp { left, right, .. } => children.extend([left.as_ref(), right.as_ref()]),
+        SqlExpr::UnaryOp { expr, .. }
+        | SqlExpr::Nested(expr)
+        | SqlExpr::IsNull(expr)
+        | SqlExpr::IsNotNull(expr)
+        | SqlExpr::Cast { expr, .. } => children.push(expr),
+        SqlExpr::InList { expr, list, .. } => {
+            children.push(expr);
+            children.extend(list);
+        }
+        SqlExpr::Between {
+            expr, low, high, ..
+        } => children.extend([expr.as_ref(), low.as_ref(), high.as_ref()]),
+        SqlExpr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            children.extend(operand.as_deref());
+            children.extend(conditions);
+            children.extend(results);
+            children.extend(else_result.as_deref());
+        }
+        SqlExpr::Function(f) => children.extend(f.args.iter().map(|arg| match arg {
+            FunctionArg::Named { arg, .. } | FunctionArg::Unnamed(arg) => arg,
+        })),
+        _ => {}
+    }
+    for child in children {
+        collect_identifiers(child, identifiers);
+    }
+}
+
+fn is_aggregate(f: &Function) -> bool {
+    AGGREGATES.contains(&f.name.to_string().to_uppercase().as_str())
+}
+
+fn has_aggregate(expr: &SqlExpr) -> bool {
+    match expr {
+        SqlExpr::Function(f) => {
+            is_aggregate(f)
+                || f.args.iter().any(|arg| match arg {
+                    FunctionArg::Named { arg, .. } | FunctionArg::Unnamed(arg) => {
+                        has_aggregate(arg)
+                    }
+                })
+        }
+        SqlExpr::BinaryOp { left, right, .. } => has_aggregate(left) || has_aggregate(right),
+        SqlExpr::Nested(expr)
+        | SqlExpr::IsNull(expr)
+        | SqlExpr::IsNotNull(expr)
+        | SqlExpr::UnaryOp { expr, .. }
+        | SqlExpr::Cast { expr, .. } => has_aggregate(expr),
+        _ => false,
+    }
+}
+
+/// 聚合函数以 `name` 为列名加入 aggregation，同名的只算一次
+fn add_aggregation(aggregation: &mut Vec<Expr>, f: &Function, name: &str) -> Result<()> {
+    let expr = Expr::try_from(Aggregation(f))?.alias(name);
+    if !aggregation.contains(&expr) {
+        aggregation.push(expr);
+    }
+    Ok(())
+}
+
+/// 把表达式里的聚合函数换成对应输出列的名字，聚合函数本身加入 aggregation
+fn extract_aggregation(expr: &SqlExpr, aggregation: &mut Vec<Expr>) -> Result<SqlExpr> {
+    let expr = match expr {
+        SqlExpr::Function(f) if is_aggregate(f) => {
+            let name = f.to_string();
+            add_aggregation(aggregation, f, &name)?;
+            SqlExpr::Identifier(Ident::new(name))
+        }
+        SqlExpr::BinaryOp { left, op, right } => SqlExpr::BinaryOp {
+            left: Box::new(extract_aggregation(left, aggregation)?),
+            op: op.clone(),
+            right: Box::new(extract_aggregation(right, aggregation)?),
+        },
+        SqlExpr::Function(f) => {
+            let mut args = Vec::with_capacity(f.args.len());
+            for arg in &f.args {
+                args.push(match arg {
+                    FunctionArg::Named { name, arg } => FunctionArg::Named {
+                        name: name.clone(),
+                        arg: extract_aggregation(arg, aggregation)?,
+                    },
+                    FunctionArg::Unnamed(arg) => {
+                        FunctionArg::Unnamed(extract_aggregation(arg, aggregation)?)
+                    }
+                });
+            }
+            SqlExpr::Function(Function { args, ..f.clone() })
+        }
+        SqlExpr::UnaryOp { op, expr } => SqlExpr::UnaryOp {
+            op: op.clone(),
+            expr: Box::new(extract_aggregation(expr, aggregation)?),
+        },
+        SqlExpr::Cast { expr, data_type } => SqlExpr::Cast {
+            expr: Box::new(extract_aggregation(expr, aggregation)?),
+            data_type: data_type.clone(),
+        },
+        SqlExpr::Nested(expr) => extract_aggregation(expr, aggregation)?,
+        SqlExpr::IsNull(expr) => SqlExpr::IsNull(Box::new(extract_aggregation(expr, aggregation)?)),
+        SqlExpr::IsNotNull(expr) => {
+            SqlExpr::IsNotNull(Box::new(extract_aggregation(expr, aggregation)?))
+        }
+        expr => expr.clone(),
+    };
+    Ok(expr)
+}
+
 /// 把 SqlParser 的 Expr 转换成 DataFrame 的 Expr
 impl TryFrom<Expression> for Expr {
     type Error = anyhow::Error;
 
     fn try_from(expr: Expression) -> Result<Self, Self::Error> {
         match *expr.0 {
+            SqlExpr::BinaryOp {
+                left,
+                op: op @ (SqlBinaryOperator::Like | SqlBinaryOperator::NotLike),
+                right,
+            } => {
+                let pattern = match *right {
+                    SqlExpr::Value(SqlValue::SingleQuotedString(pattern)) => like_regex(&pattern),
+                    right => return Err(anyhow!("LIKE pattern must be a string, got {}", right)),
+                };
+                let expr = Expr::try_from(Expression(left))?.map(
+                    move |s| Ok(s.utf8()?.contains(&pattern)?.into_series()),
+                    Some(DataType::Boolean),
+                );
+                match op {
+                    SqlBinaryOperator::NotLike => Ok(expr.not()),
+                    _ => Ok(expr),
+                }
+            }
             SqlExpr::BinaryOp { left, op, right } => Ok(Expr::BinaryExpr {
                 left: Box::new(Expression(left).try_into()?),
                 op: Operation(op).try_into()?,
                 right: Box::new(Expression(right).try_into()?),
             }),
+            SqlExpr::UnaryOp { op, expr } => {
+                let expr: Expr = Expression(expr).try_into()?;
+                match op {
+                    UnaryOperator::Not => Ok(expr.not()),
+                    UnaryOperator::Minus => Ok(lit(0) - expr),
+                    UnaryOperator::Plus => Ok(expr),
+                    op => Err(anyhow!("Operator {} is not supported", op)),
+                }
+            }
+            SqlExpr::Nested(expr) => Expression(expr).try_into(),
+            SqlExpr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let expr: Expr = Expression(expr).try_into()?;
+                let mut matched = lit(false);
+                for item in list {
+                    let item: Expr = Expression(Box::new(item)).try_into()?;
+                    matched = matched.or(expr.clone().eq(item));
+                }
+                Ok(if negated { matched.not() } else { matched })
+            }
+            SqlExpr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => {
+                let expr: Expr = Expression(expr).try_into()?;
+                let low: Expr = Expression(low).try_into()?;
+                let high: Expr = Expression(high).try_into()?;
+                let between = expr.clone().gt_eq(low).and(expr.lt_eq(high));
+                Ok(if negated { between.not() } else { between })
+            }
+            SqlExpr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                let operand = match operand {
+                    Some(operand) => Some(Expr::try_from(Expression(operand))?),
+                    None => None,
+                };
+                let mut expr = match else_result {
+                    Some(expr) => Expression(expr).try_into()?,
+                    None => Self::Literal(LiteralValue::Null),
+                };
+                // 从最后一个分支开始往前嵌套 when / then / otherwise
+                for (condition, result) in conditions.into_iter().zip(results).rev() {
+                    let mut condition: Expr = Expression(Box::new(condition)).try_into()?;
+                    if let Some(operand) = &operand {
+                        condition = operand.clone().eq(condition);
+                    }
+                    let result: Expr = Expression(Box::new(result)).try_into()?;
+                    expr = when(condition).then(result).otherwise(expr);
+                }
+                Ok(expr)
+            }
+            SqlExpr::Cast { expr, data_type } => {
+                let expr: Expr = Expression(expr).try_into()?;
+                Ok(expr.cast(Type(data_type).try_into()?))
+            }
+            SqlExpr::Function(f) if is_aggregate(&f) => {
+                Err(anyhow!("aggregation {} is not allowed here", f))
+            }
+            SqlExpr::Function(f) => Scalar(f).try_into(),
             SqlExpr::Wildcard => Ok(Self::Wildcard),
             SqlExpr::IsNull(expr) => Ok(Self::IsNull(Box::new(Expression(expr).try_into()?))),
             SqlExpr::IsNotNull(expr) => Ok(Self::IsNotNull(Box::new(Expression(expr).try_into()?))),
@@ -106,6 +433,111 @@ impl TryFrom<Expression> for Expr {
     }
 }
 
+/// 把 LIKE 的模式转换成正则表达式，`%` 匹配任意字符串，`_` 匹配单个字符
+fn like_regex(pattern: &str) -> String {
+    let mut regex = String::with_capacity(pattern.len() + 2);
+    regex.push('^');
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => {
+                if "\\.+*?()|[]{}^$".contains(c) {
+                    regex.push('\\');
+                }
+                regex.push(c);
+            }
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// 把 SqlParser 的标量函数转换成 DataFrame 的 Expr
+impl TryFrom<Scalar> for Expr {
+    type Error = anyhow::Error;
+
+    fn try_from(s: Scalar) -> Result<Self, Self::Error> {
+        let name = s.0.name.to_string().to_uppercase();
+        let mut args = Vec::with_capacity(s.0.args.len());
+        for arg in &s.0.args {
+            match arg {
+                FunctionArg::Unnamed(arg) => args.push(arg.clone()),
+                FunctionArg::Named { .. } => {
+                    return Err(anyhow!("named argument is not supported in {}", s.0))
+                }
+            }
+        }
+
+        let (arg, decimals) = match (name.as_str(), args.as_slice()) {
+            ("COALESCE", [.., last]) => {
+                // 从最后一个参数开始往前，取第一个不为 NULL 的值
+                let mut expr: Expr = Expression(Box::new(last.clone())).try_into()?;
+                for arg in args.iter().rev().skip(1) {
+                    let arg: Expr = Expression(Box::new(arg.clone())).try_into()?;
+                    expr = when(arg.clone().is_not_null()).then(arg).otherwise(expr);
+                }
+                return Ok(expr);
+            }
+            ("ROUND", [arg, SqlExpr::Value(SqlValue::Number(v, _))]) => (arg, v.parse()?),
+            (_, [arg]) => (arg, 0),
+            _ => return Err(anyhow!("function {} is not supported", s.0)),
+        };
+        let arg: Expr = Expression(Box::new(arg.clone())).try_into()?;
+
+        match name.as_str() {
+            "LOWER" => Ok(arg.map(
+                |s| Ok(s.utf8()?.to_lowercase().into_series()),
+                Some(DataType::Utf8),
+            )),
+            "UPPER" => Ok(arg.map(
+                |s| Ok(s.utf8()?.to_uppercase().into_series()),
+                Some(DataType::Utf8),
+            )),
+            "LENGTH" => Ok(arg.map(
+                |s| Ok(s.utf8()?.str_lengths().into_series()),
+                Some(DataType::UInt32),
+            )),
+            "ABS" => Ok(when(arg.clone().lt(lit(0)))
+                .then(lit(0) - arg.clone())
+                .otherwise(arg)),
+            "ROUND" => {
+                let scale = 10f64.powi(decimals);
+                Ok(arg.cast(DataType::Float64).map(
+                    move |s| {
+                        Ok(s.f64()?
+                            .apply(|v| (v * scale).round() / scale)
+                            .into_series())
+                    },
+                    Some(DataType::Float64),
+                ))
+            }
+            _ => Err(anyhow!("function {} is not supported", s.0)),
+        }
+    }
+}
+
+/// 把 SqlParser 的 DataType 转换成 DataFrame 的 DataType
+impl TryFrom<Type> for DataType {
+    type Error = anyhow::Error;
+
+    fn try_from(t: Type) -> Result<Self, Self::Error> {
+        match t.0 {
+            SqlDataType::SmallInt | SqlDataType::Int | SqlDataType::BigInt => Ok(Self::Int64),
+            SqlDataType::Float(_)
+            | SqlDataType::Real
+            | SqlDataType::Double
+            | SqlDataType::Decimal(_, _) => Ok(Self::Float64),
+            SqlDataType::Boolean => Ok(Self::Boolean),
+            SqlDataType::Char(_)
+            | SqlDataType::Varchar(_)
+            | SqlDataType::Text
+            | SqlDataType::String => Ok(Self::Utf8),
+            t => Err(anyhow!("cast to {} is not supported", t)),
+        }
+    }
+}
+
 /// 把 SqlParser 的 BinaryOperator 转换成 DataFrame 的 Operator
 impl TryFrom<Operation> for Operator {
     type Error = anyhow::Error;
@@ -144,30 +576,104 @@ impl<'a> TryFrom<Projection<'a>> for Expr {
                 Box::new(Expr::Column(Arc::new(id.to_string()))),
                 Arc::new(alias.to_string()),
             )),
+            // 其它表达式没有别名时，用表达式本身作为列名
+            SelectItem::UnnamedExpr(expr) => {
+                let name = expr.to_string();
+                Ok(Expr::try_from(Expression(Box::new(expr.to_owned())))?.alias(&name))
+            }
+            SelectItem::ExprWithAlias { expr, alias } => {
+                Ok(Expr::try_from(Expression(Box::new(expr.to_owned())))?.alias(&alias.value))
+            }
             SelectItem::QualifiedWildcard(v) => Ok(col(&v.to_string())),
             SelectItem::Wildcard => Ok(col("*")),
-            item => Err(anyhow!("projection {} not supported", item)),
         }
     }
 }
 
-impl<'a> TryFrom<Source<'a>> for &'a str {
+/// 把 SqlParser 的聚合函数转换成 DataFrame 的聚合 Expr
+impl<'a> TryFrom<Aggregation<'a>> for Expr {
+    type Error = anyhow::Error;
+
+    fn try_from(a: Aggregation<'a>) -> Result<Self, Self::Error> {
+        let f = a.0;
+        let name = f.name.to_string().to_uppercase();
+        let arg = match f.args.as_slice() {
+            [FunctionArg::Unnamed(arg)] => arg,
+            _ => return Err(anyhow!("{} takes exactly one argument", f)),
+        };
+
+        if let SqlExpr::Wildcard = arg {
+            return match (name.as_str(), f.distinct) {
+                ("COUNT", false) => Ok(col(COUNT_ALL).count()),
+                _ => Err(anyhow!("aggregation {} is not supported", f)),
+            };
+        }
+
+        let expr: Expr = Expression(Box::new(arg.to_owned())).try_into()?;
+        match (name.as_str(), f.distinct) {
+            // 和 SQL 一样，COUNT(col) 和 COUNT(DISTINCT col) 都不算 NULL
+            ("COUNT", true) => Ok(expr.clone().filter(expr.is_not_null()).n_unique()),
+            ("COUNT", false) => Ok(expr.is_not_null().cast(DataType::UInt32).sum()),
+            ("SUM", false) => Ok(expr.sum()),
+            ("AVG", false) => Ok(expr.mean()),
+            ("MIN", false) => Ok(expr.min()),
+            ("MAX", false) => Ok(expr.max()),
+            _ => Err(anyhow!("aggregation {} is not supported", f)),
+        }
+    }
+}
+
+/// 把 SqlParser 的 from 转换成 ((数据源, 别名), joins)
+impl<'a> TryFrom<Source<'a>> for ((&'a str, Option<&'a str>), Vec<Join<'a>>) {
     type Error = anyhow::Error;
 
     fn try_from(source: Source<'a>) -> Result<Self, Self::Error> {
         if source.0.len() != 1 {
-            return Err(anyhow!("We only support single data source at the moment"));
+            return Err(anyhow!("Use JOIN ... ON to query multiple data sources"));
         }
 
         let table = &source.0[0];
-        if !table.joins.is_empty() {
-            return Err(anyhow!("We do not support joint data source at the moment"));
+        let mut joins = Vec::with_capacity(table.joins.len());
+        for join in &table.joins {
+            let (kind, constraint) = match &join.join_operator {
+                JoinOperator::Inner(c) => (JoinKind::Inner, c),
+                JoinOperator::LeftOuter(c) => (JoinKind::Left, c),
+                JoinOperator::RightOuter(c) => (JoinKind::Right, c),
+                op => return Err(anyhow!("join {:?} is not supported", op)),
+            };
+            let on = match constraint {
+                JoinConstraint::On(SqlExpr::BinaryOp {
+                    left,
+                    op: SqlBinaryOperator::Eq,
+                    right,
+                }) => match (left.as_ref(), right.as_ref()) {
+                    (SqlExpr::Identifier(l), SqlExpr::Identifier(r)) => {
+                        (l.value.clone(), r.value.clone())
+                    }
+                    _ => return Err(anyhow!("We only support ON a.col = b.col for join")),
+                },
+                _ => return Err(anyhow!("We only support ON a.col = b.col for join")),
+            };
+            let (source, alias) = table_factor(&join.relation)?;
+            joins.push(Join {
+                source,
+                alias,
+                kind,
+                on,
+            });
         }
 
-        match &table.relation {
-            TableFactor::Table { name, .. } => Ok(&name.0.first().unwrap().value),
-            _ => Err(anyhow!("We only support table")),
-        }
+        Ok((table_factor(&table.relation)?, joins))
+    }
+}
+
+fn table_factor(relation: &TableFactor) -> Result<(&str, Option<&str>)> {
+    match relation {
+        TableFactor::Table { name, alias, .. } => Ok((
+            &name.0.first().unwrap().value,
+            alias.as_ref().map(|a| a.name.value.as_str()),
+        )),
+        _ => Err(anyhow!("We only support table")),
     }
 }
 
@@ -219,6 +725,7 @@ impl TryFrom<Value> for LiteralValue {
     fn try_from(v: Value) -> Result<Self, Self::Error> {
         match v.0 {
             SqlValue::Number(v, _) => Ok(LiteralValue::Float64(v.parse().unwrap())),
+            SqlValue::SingleQuotedString(v) => Ok(LiteralValue::Utf8(v)),
             SqlValue::Boolean(v) => Ok(LiteralValue::Boolean(v)),
             SqlValue::Null => Ok(LiteralValue::Null),
             v => Err(anyhow!("Value {} is not supported", v)),
@@ -247,4 +754,96 @@ mod tests {
         assert_eq!(sql.order_by, vec![("c".into(), true)]);
         assert_eq!(sql.selection, vec![col("a"), col("b"), col("c")]);
     }
+
+    #[test]
+    fn parse_join_works() {
+        let sql = "select p.name, o.amount from file://people.csv p \
+                   inner join file://orders.csv o on p.id = o.person_id \
+                   right join http://abc.xyz/city.csv on city_id = id";
+        let statement = &Parser::parse_sql(&TyrDialect::default(), sql).unwrap()[0];
+        let sql: Sql = statement.try_into().unwrap();
+        assert_eq!(sql.source, "file://people.csv");
+        assert_eq!(sql.alias, Some("p"));
+        assert_eq!(
+            sql.joins,
+            vec![
+                Join {
+                    source: "file://orders.csv",
+                    alias: Some("o"),
+                    kind: JoinKind::Inner,
+                    on: ("p.id".into(), "o.person_id".into()),
+                },
+                Join {
+                    source: "http://abc.xyz/city.csv",
+                    alias: None,
+                    kind: JoinKind::Right,
+                    on: ("city_id".into(), "id".into()),
+                },
+            ]
+        );
+        assert_eq!(sql.selection, vec![col("p.name"), col("o.amount")]);
+    }
+
+    #[test]
+    fn parse_expressions_works() {
+        let sql = "select upper(name), new_cases * 100 / population as pct, \
+                   case when new_cases > 10 then 'high' else 'low' end level \
+                   from file://data.csv \
+                   where iso_code in ('CHN', 'USA') and new_cases between 1 and 100 \
+                   and name not like 'A%'";
+        let statement = &Parser::parse_sql(&TyrDialect::default(), sql).unwrap()[0];
+        let sql: Sql = statement.try_into().unwrap();
+        assert_eq!(sql.selection.len(), 3);
+        assert_eq!(
+            sql.selection[1],
+            (col("new_cases") * lit(100f64) / col("population")).alias("pct")
+        );
+        assert_eq!(
+            sql.selection[2],
+            when(col("new_cases").gt(lit(10f64)))
+                .then(lit("high"))
+                .otherwise(lit("low"))
+                .alias("level")
+        );
+        assert!(sql.condition.is_some());
+        assert_eq!(like_regex("a_b%.c"), "^a.b.*\\.c$");
+    }
+
+    #[test]
+    fn parse_group_by_works() {
+        let sql = "select location, COUNT(*), SUM(new_cases) total, COUNT(DISTINCT iso_code) \
+                   from file://data.csv group by location having total > 100 and MAX(new_cases) > 5";
+        let statement = &Parser::parse_sql(&TyrDialect::default(), sql).unwrap()[0];
+        let sql: Sql = statement.try_into().unwrap();
+        assert_eq!(sql.group_by, vec![col("location")]);
+        assert_eq!(
+            sql.aggregation,
+            vec![
+                col(COUNT_ALL).count().alias("COUNT(*)"),
+                col("new_cases").sum().alias("total"),
+                col("iso_code")
+                    .filter(col("iso_code").is_not_null())
+                    .n_unique()
+                    .alias("COUNT(DISTINCT iso_code)"),
+                col("new_cases").max().alias("MAX(new_cases)"),
+            ]
+        );
+        assert_eq!(
+            sql.selection,
+            vec![
+                col("location"),
+                col("COUNT(*)"),
+                col("total"),
+                col("COUNT(DISTINCT iso_code)")
+            ]
+        );
+        assert_eq!(
+            sql.having,
+            Some(
+                col("total")
+                    .gt(lit(100f64))
+                    .and(col("MAX(new_cases)").gt(lit(5f64)))
+            )
+        );
+    }
 }
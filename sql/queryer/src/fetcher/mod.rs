@@ -0,0 +1,253 @@
+mod s3;
+mod sqlite;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+pub use s3::S3Fetcher;
+pub use sqlite::SqliteFetcher;
+
+// Rust 的 async trait 还没有稳定，可以用 async_trait 宏
+#[async_trait]
+pub trait Fetch {
+    type Error;
+    /// 获取 source 的数据，数据和缓存的 validator 一致时返回 None
+    async fn fetch(
+        &self,
+        source: &str,
+        validator: Option<&Validator>,
+    ) -> Result<Option<RawData>, Self::Error>;
+}
+
+type Fetcher = Arc<dyn Fetch<Error = anyhow::Error> + Send + Sync>;
+
+// scheme -> Fetch
+static FETCHERS: Lazy<RwLock<HashMap<String, Fetcher>>> = Lazy::new(|| {
+    let mut fetchers: HashMap<String, Fetcher> = HashMap::new();
+    fetchers.insert("http".into(), Arc::new(UrlFetcher));
+    fetchers.insert("https".into(), Arc::new(UrlFetcher));
+    fetchers.insert("file".into(), Arc::new(FileFetcher));
+    fetchers.insert("stdin".into(), Arc::new(StdinFetcher));
+    fetchers.insert("s3".into(), Arc::new(S3Fetcher::default()));
+    fetchers.insert("sqlite".into(), Arc::new(SqliteFetcher));
+    RwLock::new(fetchers)
+});
+
+/// 为 scheme 注册一个 Fetch，已经注册过的 scheme 会被替换
+pub fn register_fetcher<F>(scheme: &str, fetcher: F)
+where
+    F: Fetch<Error = anyhow::Error> + Send + Sync + 'static,
+{
+    FETCHERS
+        .write()
+        .unwrap()
+        .insert(scheme.to_ascii_lowercase(), Arc::new(fetcher));
+}
+
+/// 获取到的原始数据。parquet 这样的格式不是文本，所以保留字节，
+/// 同时带上数据源和 content type，方便 loader 判断数据格式
+#[derive(Debug)]
+pub struct RawData {
+    pub(crate) source: String,
+    pub(crate) content_type: Option<String>,
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) validator: Validator,
+}
+
+impl RawData {
+    pub fn new(source: impl Into<String>, content_type: Option<String>, bytes: Vec<u8>) -> Self {
+        Self {
+            source: source.into(),
+            content_type,
+            bytes,
+            validator: Validator::default(),
+        }
+    }
+}
+
+/// 判断缓存的数据是否还有效。http 源用 ETag / Last-Modified，文件源用修改时间和大小
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Validator {
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+    pub(crate) mtime: Option<SystemTime>,
+    pub(crate) size: Option<u64>,
+}
+
+impl Validator {
+    /// 没有任何可以用来确认数据没有变化的信息
+    pub(crate) fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none() && self.mtime.is_none()
+    }
+
+    // 文件的修改时间和大小
+    async fn from_file(path: &str) -> Result<Self> {
+        let metadata = fs::metadata(path).await?;
+        Ok(Self {
+            mtime: metadata.modified().ok(),
+            size: Some(metadata.len()),
+            ..Default::default()
+        })
+    }
+
+    // 和缓存的 validator 一致，说明数据没有变化
+    fn matches(&self, cached: Option<&Validator>) -> bool {
+        !self.is_empty() && cached == Some(self)
+    }
+}
+
+/// 根据 source 的 scheme 找到对应的 Fetch 获取数据。
+/// 带上缓存的 validator 时，数据没有变化就返回 None
+pub async fn retrieve_data(
+    source: impl AsRef<str>,
+    validator: Option<&Validator>,
+) -> Result<Option<RawData>> {
+    let source = source.as_ref();
+    let (scheme, _) = source
+        .split_once("://")
+        .ok_or_else(|| anyhow!("invalid source {}, expect <scheme>://...", source))?;
+    let fetcher = FETCHERS
+        .read()
+        .unwrap()
+        .get(&scheme.to_ascii_lowercase())
+        .cloned()
+        .ok_or_else(|| anyhow!("unsupported scheme {} in {}", scheme, source))?;
+    fetcher.fetch(source, validator).await
+}
+
+// `scheme://` 后面的部分
+fn location(source: &str) -> &str {
+    source
+        .split_once("://")
+        .map_or(source, |(_, location)| location)
+}
+
+/// http / https
+struct UrlFetcher;
+/// file://<filename>
+struct FileFetcher;
+/// stdin://，可以带上文件名来指定格式，比如 stdin://data.json
+struct StdinFetcher;
+
+#[async_trait]
+impl Fetch for UrlFetcher {
+    type Error = anyhow::Error;
+
+    async fn fetch(
+        &self,
+        source: &str,
+        validator: Option<&Validator>,
+    ) -> Result<Option<RawData>, Self::Error> {
+        let mut req = reqwest::Client::new().get(source);
+        if let Some(validator) = validator {
+            if let Some(etag) = &validator.etag {
+                req = req.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &validator.last_modified {
+                req = req.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let resp = req.send().await?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let resp = resp.error_for_status()?;
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        let content_type = header(CONTENT_TYPE);
+        let validator = Validator {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            ..Default::default()
+        };
+        Ok(Some(RawData {
+            source: source.to_string(),
+            content_type,
+            bytes: resp.bytes().await?.to_vec(),
+            validator,
+        }))
+    }
+}
+
+#[async_trait]
+impl Fetch for FileFetcher {
+    type Error = anyhow::Error;
+
+    async fn fetch(
+        &self,
+        source: &str,
+        cached: Option<&Validator>,
+    ) -> Result<Option<RawData>, Self::Error> {
+        let path = location(source);
+        let validator = Validator::from_file(path).await?;
+        if validator.matches(cached) {
+            return Ok(None);
+        }
+
+        Ok(Some(RawData {
+            source: source.to_string(),
+            content_type: None,
+            bytes: fs::read(path).await?,
+            validator,
+        }))
+    }
+}
+
+#[async_trait]
+impl Fetch for StdinFetcher {
+    type Error = anyhow::Error;
+
+    async fn fetch(&self, source: &str, _: Option<&Validator>) -> Result<Option<RawData>> {
+        let mut bytes = Vec::new();
+        tokio::io::stdin().read_to_end(&mut bytes).await?;
+        // 标准输入只能读一次，不设置 validator，也就不会被缓存
+        Ok(Some(RawData::new(source, None, bytes)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MemoryFetcher;
+
+    #[async_trait]
+    impl Fetch for MemoryFetcher {
+        type Error = anyhow::Error;
+
+        async fn fetch(&self, source: &str, _: Option<&Validator>) -> Result<Option<RawData>> {
+            Ok(Some(RawData::new(source, None, b"a,b\n1,2\n".to_vec())))
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_sources_are_errors() {
+        for source in ["", "ftp", "abc", "file:/tmp/a.csv", "ftp://abc.xyz/a.csv"] {
+            assert!(retrieve_data(source, None).await.is_err(), "{}", source);
+        }
+    }
+
+    #[tokio::test]
+    async fn registered_fetcher_is_used() {
+        register_fetcher("MEM", MemoryFetcher);
+        let data = retrieve_data("mem://data", None).await.unwrap().unwrap();
+        assert_eq!(data.bytes, b"a,b\n1,2\n");
+
+        let ds = crate::query("SELECT a FROM mem://data").await.unwrap();
+        assert_eq!(ds.shape(), (1, 1));
+    }
+}
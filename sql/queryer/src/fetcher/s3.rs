@@ -0,0 +1,156 @@
+use super::{location, Fetch, RawData, Validator};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use once_cell::sync::OnceCell;
+use rusoto_core::credential::StaticProvider;
+use rusoto_core::{HttpClient, Region, RusotoError};
+use rusoto_s3::{GetObjectRequest, S3Client, S3};
+use std::fmt;
+use tokio::io::AsyncReadExt;
+
+/// 获取 `s3://bucket/key`。
+/// 默认访问 AWS，认证信息使用 AWS 的标准配置（环境变量、~/.aws/credentials 等）。
+/// 要连接 MinIO 这样兼容 S3 的服务，用 `with_endpoint` 创建之后通过 `register_fetcher("s3", ..)` 注册
+#[derive(Default, Clone)]
+pub struct S3Fetcher {
+    endpoint: Option<String>,
+    region: Option<String>,
+    // (access key, secret key)
+    credentials: Option<(String, String)>,
+    // 第一次 fetch 时创建，之后的请求共用同一个连接池
+    client: OnceCell<S3Client>,
+}
+
+impl S3Fetcher {
+    /// 访问 endpoint 上兼容 S3 的服务
+    pub fn with_endpoint(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: Some(endpoint.into()),
+            ..Default::default()
+        }
+    }
+
+    /// 设置 region，使用自定义 endpoint 时默认是 us-east-1
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self.client = OnceCell::new();
+        self
+    }
+
+    /// 使用固定的认证信息，不再读取 AWS 的标准配置
+    pub fn credentials(
+        mut self,
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+    ) -> Self {
+        self.credentials = Some((access_key.into(), secret_key.into()));
+        self.client = OnceCell::new();
+        self
+    }
+
+    fn client(&self) -> Result<&S3Client> {
+        self.client.get_or_try_init(|| self.new_client())
+    }
+
+    fn new_client(&self) -> Result<S3Client> {
+        let region = match (&self.endpoint, &self.region) {
+            (Some(endpoint), region) => Region::Custom {
+                name: region.clone().unwrap_or_else(|| "us-east-1".into()),
+                endpoint: endpoint.clone(),
+            },
+            (None, Some(region)) => region.parse()?,
+            (None, None) => Region::default(),
+        };
+        Ok(match &self.credentials {
+            Some((key, secret)) => S3Client::new_with(
+                HttpClient::new()?,
+                StaticProvider::new_minimal(key.clone(), secret.clone()),
+                region,
+            ),
+            None => S3Client::new(region),
+        })
+    }
+}
+
+// 不打印认证信息
+impl fmt::Debug for S3Fetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Fetcher")
+            .field("endpoint", &self.endpoint)
+            .field("region", &self.region)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Fetch for S3Fetcher {
+    type Error = anyhow::Error;
+
+    async fn fetch(&self, source: &str, cached: Option<&Validator>) -> Result<Option<RawData>> {
+        let (bucket, key) = location(source)
+            .split_once('/')
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or_else(|| anyhow!("invalid source {}, expect s3://bucket/key", source))?;
+
+        let req = GetObjectRequest {
+            bucket: bucket.into(),
+            key: key.into(),
+            if_none_match: cached.and_then(|v| v.etag.clone()),
+            if_modified_since: cached.and_then(|v| v.last_modified.clone()),
+            ..Default::default()
+        };
+        let output = match self.client()?.get_object(req).await {
+            Ok(output) => output,
+            // 304 不是 GetObjectError 里定义的错误
+            Err(RusotoError::Unknown(resp)) if resp.status.as_u16() == 304 => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut bytes = Vec::new();
+        if let Some(body) = output.body {
+            body.into_async_read().read_to_end(&mut bytes).await?;
+        }
+        Ok(Some(RawData {
+            source: source.into(),
+            content_type: output.content_type,
+            bytes,
+            validator: Validator {
+                etag: output.e_tag,
+                last_modified: output.last_modified,
+                ..Default::default()
+            },
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{serve, State};
+
+    #[tokio::test]
+    async fn s3_source_works() {
+        let state = State::new("\"v1\"", "a,b\n1,2\n3,4\n");
+        let addr = serve(state.clone()).await;
+
+        // 不通过环境变量配置，避免影响同时运行的其它测试
+        let fetcher = S3Fetcher::with_endpoint(format!("http://{}", addr))
+            .credentials("minioadmin", "minioadmin");
+        let source = "s3://bucket/dir/data.csv";
+        let data = fetcher.fetch(source, None).await.unwrap().unwrap();
+        assert_eq!(data.bytes, b"a,b\n1,2\n3,4\n");
+        assert_eq!(data.validator.etag.as_deref(), Some("\"v1\""));
+        assert!(fetcher
+            .fetch(source, Some(&data.validator))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            *state.paths.lock().unwrap(),
+            vec!["/bucket/dir/data.csv", "/bucket/dir/data.csv"]
+        );
+
+        assert!(fetcher.fetch("s3://bucket", None).await.is_err());
+    }
+}
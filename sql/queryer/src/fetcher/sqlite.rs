@@ -0,0 +1,141 @@
+use super::{location, Fetch, RawData, Validator};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use serde_json::Value as Json;
+
+/// 读取 sqlite 数据库里的一张表：`sqlite://path/to/db?table=name`
+#[derive(Debug, Default)]
+pub struct SqliteFetcher;
+
+#[async_trait]
+impl Fetch for SqliteFetcher {
+    type Error = anyhow::Error;
+
+    async fn fetch(&self, source: &str, cached: Option<&Validator>) -> Result<Option<RawData>> {
+        let (path, table) = parse(source)?;
+        let validator = Validator::from_file(path).await?;
+        if validator.matches(cached) {
+            return Ok(None);
+        }
+
+        let (path, table) = (path.to_owned(), table.to_owned());
+        // rusqlite 是同步的，放到阻塞线程里执行
+        let (content_type, bytes) =
+            tokio::task::spawn_blocking(move || read_table(&path, &table)).await??;
+        Ok(Some(RawData {
+            source: source.into(),
+            content_type: Some(content_type.into()),
+            bytes,
+            validator,
+        }))
+    }
+}
+
+fn parse(source: &str) -> Result<(&str, &str)> {
+    let err = || anyhow!("invalid source {}, expect sqlite://path?table=name", source);
+    let (path, query) = location(source).split_once('?').ok_or_else(err)?;
+    let table = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("table="))
+        .filter(|table| !path.is_empty() && !table.is_empty())
+        .ok_or_else(err)?;
+    Ok((path, table))
+}
+
+// 每一行转换成一个 JSON 对象，交给 ndjson loader 解析，返回 (content type, 数据)
+fn read_table(path: &str, table: &str) -> Result<(&'static str, Vec<u8>)> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let sql = format!("SELECT * FROM \"{}\"", table.replace('"', "\"\""));
+    let mut stmt = conn.prepare(&sql)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+
+    let mut bytes = Vec::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let mut object = serde_json::Map::with_capacity(columns.len());
+        for (i, name) in columns.iter().enumerate() {
+            let value = match row.get_ref(i)? {
+                ValueRef::Null => Json::Null,
+                ValueRef::Integer(v) => v.into(),
+                ValueRef::Real(v) => v.into(),
+                ValueRef::Text(v) => String::from_utf8_lossy(v).into(),
+                ValueRef::Blob(_) => return Err(anyhow!("blob column {} is not supported", name)),
+            };
+            object.insert(name.clone(), value);
+        }
+        serde_json::to_writer(&mut bytes, &object)?;
+        bytes.push(b'\n');
+    }
+    if !bytes.is_empty() {
+        return Ok(("application/x-ndjson", bytes));
+    }
+
+    // 空表的 ndjson 也是空的，推断不出有哪些列，改成只有表头的 csv
+    let header: Vec<String> = columns
+        .iter()
+        .map(|name| {
+            if name.contains(|c| matches!(c, ',' | '"' | '\r' | '\n')) {
+                format!("\"{}\"", name.replace('"', "\"\""))
+            } else {
+                name.clone()
+            }
+        })
+        .collect();
+    Ok(("text/csv", format!("{}\n", header.join(",")).into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn parse_works() {
+        assert_eq!(
+            parse("sqlite:///tmp/a.db?mode=ro&table=people").unwrap(),
+            ("/tmp/a.db", "people")
+        );
+        assert!(parse("sqlite:///tmp/a.db").is_err());
+        assert!(parse("sqlite:///tmp/a.db?table=").is_err());
+        assert!(parse("sqlite://?table=people").is_err());
+    }
+
+    #[tokio::test]
+    async fn sqlite_source_works() {
+        let path = env::temp_dir().join("queryer_sqlite_source_works.db");
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE people (name TEXT, age INTEGER, score REAL);
+             INSERT INTO people VALUES ('alice', 25, 1.5), ('bob', 35, NULL), ('carol', 45, 3.5);",
+        )
+        .unwrap();
+        drop(conn);
+
+        let sql = format!(
+            "SELECT name, score FROM sqlite://{}?table=people WHERE age > 30",
+            path.display()
+        );
+        let ds = crate::query(sql).await.unwrap();
+        assert_eq!(ds.shape(), (2, 2));
+        assert_eq!(ds.column("score").unwrap().null_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn empty_sqlite_table_keeps_its_columns() {
+        let path = env::temp_dir().join("queryer_empty_sqlite_table_keeps_its_columns.db");
+        let _ = std::fs::remove_file(&path);
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch("CREATE TABLE people (name TEXT, age INTEGER);")
+            .unwrap();
+        drop(conn);
+
+        let sql = format!("SELECT * FROM sqlite://{}?table=people", path.display());
+        let ds = crate::query(sql).await.unwrap();
+        assert_eq!(ds.shape(), (0, 2));
+        assert_eq!(ds.get_column_names(), vec!["name", "age"]);
+    }
+}
@@ -1,19 +1,32 @@
+mod cache;
 mod convert;
 mod dialect;
 mod fetcher;
 mod loader;
+mod table;
+#[cfg(test)]
+mod test_server;
+mod writer;
 use anyhow::{anyhow, Result};
+use futures::future::try_join_all;
+use sqlparser::ast::Statement;
 use sqlparser::parser::Parser;
+use std::collections::HashMap;
+use std::iter;
 use std::ops::{Deref, DerefMut};
 use tracing::info;
 
+pub use cache::{cache, Cache};
 pub use dialect::example_sql;
 pub use dialect::TyrDialect;
+pub use fetcher::{register_fetcher, Fetch, RawData, S3Fetcher, SqliteFetcher, Validator};
+pub use table::{drop_table, register_table, tables};
 
 use polars::prelude::*;
 
-use crate::loader::detect_content;
-use crate::{convert::Sql, fetcher::retrieve_data};
+use crate::convert::{Join, JoinKind, Sql, COUNT_ALL};
+use crate::table::table;
+use crate::writer::{parse_copy, write};
 
 #[derive(Debug)]
 pub struct DataSet(DataFrame);
@@ -38,31 +51,347 @@ impl DataSet {
         writer.finish(self)?;
         Ok(String::from_utf8(buf)?)
     }
+
+    /// 转换成 JSON 数组，每行是一个对象
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.rows())?)
+    }
+
+    /// 转换成 ndjson，每行一个 JSON 对象
+    pub fn to_ndjson(&self) -> Result<String> {
+        let mut ndjson = String::new();
+        for row in self.rows() {
+            ndjson.push_str(&serde_json::to_string(&row)?);
+            ndjson.push('\n');
+        }
+        Ok(ndjson)
+    }
+
+    /// 转换成列对齐的文本表格
+    pub fn to_table(&self) -> String {
+        let rows = self.cells();
+        let widths: Vec<usize> = (0..self.width())
+            .map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0))
+            .collect();
+
+        let mut table = String::new();
+        for (n, row) in rows.iter().enumerate() {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{:width$}", cell, width = width))
+                .collect();
+            table.push_str(line.join(" | ").trim_end());
+            table.push('\n');
+            if n == 0 {
+                let line: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+                table.push_str(&line.join("-+-"));
+                table.push('\n');
+            }
+        }
+        table
+    }
+
+    /// 转换成 Markdown 表格
+    pub fn to_markdown(&self) -> String {
+        let rows = self.cells();
+        let mut table = String::new();
+        for (n, row) in rows.iter().enumerate() {
+            let line: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            table.push_str(&format!("| {} |\n", line.join(" | ")));
+            if n == 0 {
+                table.push_str(&format!("|{}\n", " --- |".repeat(row.len())));
+            }
+        }
+        table
+    }
+
+    // 每一行转换成一个 JSON 对象
+    fn rows(&self) -> Vec<serde_json::Value> {
+        let columns = self.get_columns();
+        (0..self.height())
+            .map(|i| {
+                columns
+                    .iter()
+                    .map(|s| (s.name().to_owned(), json_value(s.get(i))))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            })
+            .collect()
+    }
+
+    // 表头和每一行的每一格转换成字符串
+    fn cells(&self) -> Vec<Vec<String>> {
+        let columns = self.get_columns();
+        let mut rows = Vec::with_capacity(self.height() + 1);
+        rows.push(columns.iter().map(|s| s.name().to_owned()).collect());
+        for i in 0..self.height() {
+            rows.push(
+                columns
+                    .iter()
+                    .map(|s| match s.get(i) {
+                        AnyValue::Utf8(v) => v.to_owned(),
+                        v => v.to_string(),
+                    })
+                    .collect(),
+            );
+        }
+        rows
+    }
+}
+
+fn json_value(v: AnyValue) -> serde_json::Value {
+    use serde_json::Value as Json;
+    match v {
+        AnyValue::Null => Json::Null,
+        AnyValue::Boolean(v) => v.into(),
+        AnyValue::Utf8(v) => v.into(),
+        AnyValue::UInt32(v) => v.into(),
+        AnyValue::UInt64(v) => v.into(),
+        AnyValue::Int32(v) => v.into(),
+        AnyValue::Int64(v) => v.into(),
+        AnyValue::Float32(v) => (v as f64).into(),
+        AnyValue::Float64(v) => v.into(),
+        v => v.to_string().into(),
+    }
+}
+
+/// 读取数据源，返回每一列的列名和推断出来的类型
+pub async fn schema<T: AsRef<str>>(source: T) -> Result<DataSet> {
+    let source = source.as_ref();
+    let ds = match table(source) {
+        Some(ds) => ds,
+        None => {
+            info!("retrieving data from source: {}", source);
+            cache().load(source).await?
+        }
+    };
+
+    let columns = ds.get_column_names();
+    let types: Vec<String> = ds.dtypes().iter().map(|t| t.to_string()).collect();
+    let types: Vec<&str> = types.iter().map(|t| t.as_str()).collect();
+    Ok(DataSet(DataFrame::new(vec![
+        Series::new("column", &columns),
+        Series::new("type", &types),
+    ])?))
 }
 
 pub async fn query<T: AsRef<str>>(sql: T) -> Result<DataSet> {
-    let ast = Parser::parse_sql(&TyrDialect::default(), sql.as_ref())?;
+    let sql = sql.as_ref();
+    // COPY (SELECT ...) TO 'file://...' 把查询结果写到文件里
+    if let Some((select, target)) = parse_copy(sql)? {
+        let ds = execute(&parse(&select)?).await?;
+        return affected(write(&ds, &target).await?);
+    }
+
+    match parse(sql)? {
+        Statement::Explain { statement, .. } => explain(&statement),
+        // CREATE TABLE name AS SELECT ... 把查询结果注册成命名的表
+        Statement::CreateTable {
+            name,
+            query: Some(select),
+            or_replace,
+            if_not_exists,
+            ..
+        } => {
+            let name = name.to_string();
+            if table(&name).is_some() && !or_replace {
+                if if_not_exists {
+                    return affected(0);
+                }
+                return Err(anyhow!("table {} already exists", name));
+            }
+            let ds = execute(&Statement::Query(select)).await?;
+            let rows = ds.height();
+            register_table(&name, ds);
+            affected(rows)
+        }
+        // INSERT INTO name SELECT ... 把查询结果追加到命名的表里
+        Statement::Insert {
+            table_name,
+            columns,
+            source,
+            ..
+        } => {
+            if !columns.is_empty() {
+                return Err(anyhow!("INSERT INTO with column list is not supported"));
+            }
+            let name = table_name.to_string();
+            let existing = table(&name).ok_or_else(|| anyhow!("table {} not found", name))?;
+            let ds = execute(&Statement::Query(source)).await?;
+            let rows = ds.height();
+            register_table(&name, DataSet(existing.vstack(&ds)?));
+            affected(rows)
+        }
+        statement => execute(&statement).await,
+    }
+}
+
+fn parse(sql: &str) -> Result<Statement> {
+    let mut ast = Parser::parse_sql(&TyrDialect::default(), sql)?;
     if ast.len() != 1 {
         return Err(anyhow!("Only support single sql at the moment"));
     }
-    let sql = &ast[0];
+    Ok(ast.remove(0))
+}
+
+/// 执行 SELECT
+async fn execute(statement: &Statement) -> Result<DataSet> {
+    let sql: Sql = statement.try_into()?;
+    let frames = load_sources(&sql).await?;
+    Ok(DataSet(plan(sql, frames)?.collect()?))
+}
+
+// 写入数据的语句返回写入的行数
+fn affected(rows: usize) -> Result<DataSet> {
+    Ok(DataSet(DataFrame::new(vec![Series::new(
+        "rows",
+        &[rows as u64],
+    )])?))
+}
+
+/// EXPLAIN 不获取数据，返回解析出来的 Sql 以及 polars 优化前后的逻辑计划
+fn explain(statement: &Statement) -> Result<DataSet> {
+    let sql: Sql = statement.try_into()?;
+    let mut lines = vec![
+        format!("source: {}", sql.source),
+        format!("alias: {:?}", sql.alias),
+        format!("joins: {:?}", sql.joins),
+        format!("condition: {:?}", sql.condition),
+        format!("selection: {:?}", sql.selection),
+        format!("group by: {:?}", sql.group_by),
+        format!("aggregation: {:?}", sql.aggregation),
+        format!("having: {:?}", sql.having),
+        format!("order by: {:?}", sql.order_by),
+        format!("offset: {:?}", sql.offset),
+        format!("limit: {:?}", sql.limit),
+    ];
+
+    let frames = placeholders(&sql)?;
+    let lazy = plan(sql, frames)?;
+    lines.push("unoptimized plan:".into());
+    lines.extend(lazy.describe_plan().lines().map(|l| format!("  {}", l)));
+    lines.push("optimized plan:".into());
+    let optimized = lazy.describe_optimized_plan()?;
+    lines.extend(optimized.lines().map(|l| format!("  {}", l)));
+
+    let lines: Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+    Ok(DataSet(DataFrame::new(vec![Series::new("plan", &lines)])?))
+}
+
+// EXPLAIN 用只有列名、没有数据的 DataFrame 代替数据源。
+// 带别名的列放到对应的数据源里，JOIN 条件右边不带别名的列放到 JOIN 进来的数据源里，
+// 其余的列都放到第一个数据源里
+fn placeholders(sql: &Sql) -> Result<Vec<DataSet>> {
+    fn split<'a>(qualifiers: &[&str], name: &'a str, default: usize) -> (usize, &'a str) {
+        for (i, q) in qualifiers.iter().enumerate() {
+            if let Some(c) = name.strip_prefix(q).and_then(|c| c.strip_prefix('.')) {
+                return (i, c);
+            }
+        }
+        (default, name)
+    }
+
+    let mut qualifiers = vec![sql.alias.unwrap_or(sql.source)];
+    qualifiers.extend(sql.joins.iter().map(|j| j.alias.unwrap_or(j.source)));
+
+    let on = sql
+        .joins
+        .iter()
+        .enumerate()
+        .flat_map(|(i, j)| [(j.on.0.as_str(), 0), (j.on.1.as_str(), i + 1)]);
+    let rest = sql.identifiers.iter().map(|name| (*name, 0));
+
+    let mut names: Vec<Vec<&str>> = vec![Vec::new(); qualifiers.len()];
+    for (name, default) in on.chain(rest) {
+        let (table, column) = split(&qualifiers, name, default);
+        // 不带别名的列只要在任意一个数据源里就行
+        let exists = if column.len() == name.len() {
+            names.iter().flatten().any(|n| *n == column)
+        } else {
+            names[table].contains(&column)
+        };
+        if !exists {
+            names[table].push(column);
+        }
+    }
+
+    names
+        .into_iter()
+        .map(|columns| {
+            let columns = columns
+                .into_iter()
+                .map(|c| Series::new(c, &[] as &[f64]))
+                .collect();
+            Ok(DataSet(DataFrame::new(columns)?))
+        })
+        .collect()
+}
+
+/// 并发获取 FROM 和 JOIN 里所有的数据源
+async fn load_sources(sql: &Sql<'_>) -> Result<Vec<DataSet>> {
+    let sources = iter::once(sql.source).chain(sql.joins.iter().map(|j| j.source));
+    try_join_all(sources.map(|source| async move {
+        // CREATE TABLE 创建的表直接使用
+        if let Some(ds) = table(source) {
+            return Ok(ds);
+        }
+        info!("retrieving data from source: {}", source);
+        // 从 source 读入一个 DataSet，数据没有变化时直接用缓存
+        cache().load(source).await
+    }))
+    .await
+}
+
+/// 连接各个数据源，然后依次 filter / groupby / sort / slice / select
+fn plan(sql: Sql, frames: Vec<DataSet>) -> Result<LazyFrame> {
     let Sql {
         source,
+        alias,
+        joins,
         condition,
-        selection,
+        mut selection,
+        group_by,
+        aggregation,
+        having,
         offset,
         limit,
         order_by,
-    } = sql.try_into()?;
-    info!("retrieving data from source: {}", source);
-    // 从 source 读入一个 DataSet
-    let ds = detect_content(retrieve_data(source).await?).load()?;
+        ..
+    } = sql;
+    let (lazy, columns) = join_sources(source, alias, &joins, frames)?;
+    // JOIN 之后 `SELECT *` 只展开各个数据源原有的列
+    if let Some(columns) = columns {
+        selection = selection
+            .into_iter()
+            .flat_map(|expr| match expr {
+                Expr::Wildcard => columns.iter().map(|c| col(c)).collect(),
+                expr => vec![expr],
+            })
+            .collect();
+    }
 
     let mut filtered = match condition {
-        Some(expr) => ds.0.lazy().filter(expr),
-        None => ds.0.lazy(),
+        Some(expr) => lazy.filter(expr),
+        None => lazy,
     };
 
+    if !aggregation.is_empty() {
+        filtered = filtered.with_column(lit(1).alias(COUNT_ALL));
+        // 没有 GROUP BY 时整张表聚合成一行
+        filtered = if group_by.is_empty() {
+            filtered.select(aggregation)
+        } else {
+            filtered.groupby(group_by).agg(aggregation)
+        };
+    } else if !group_by.is_empty() {
+        filtered = filtered.groupby(group_by).agg(vec![]);
+    }
+
+    if let Some(expr) = having {
+        filtered = filtered.filter(expr);
+    }
+
     filtered = order_by
         .into_iter()
         .fold(filtered, |acc, (col, desc)| acc.sort(&col, desc));
@@ -71,14 +400,338 @@ pub async fn query<T: AsRef<str>>(sql: T) -> Result<DataSet> {
         filtered = filtered.slice(offset.unwrap_or(0), limit.unwrap_or(usize::MAX));
     }
 
-    Ok(DataSet(filtered.select(selection).collect()?))
+    Ok(filtered.select(selection))
+}
+
+/// 按 JOIN 的顺序把数据源连接起来。有 JOIN 时同时返回 `SELECT *` 对应的列
+fn join_sources(
+    source: &str,
+    alias: Option<&str>,
+    joins: &[Join<'_>],
+    frames: Vec<DataSet>,
+) -> Result<(LazyFrame, Option<Vec<String>>)> {
+    if joins.is_empty() {
+        let ds = frames.into_iter().next().unwrap();
+        return Ok((ds.0.lazy(), None));
+    }
+
+    let mut tables = vec![(source, alias)];
+    tables.extend(joins.iter().map(|j| (j.source, j.alias)));
+
+    // 没有别名的数据源用 source 本身来限定列名
+    let qualifiers: Vec<&str> = tables
+        .iter()
+        .map(|(source, alias)| alias.unwrap_or(source))
+        .collect();
+    let names: Vec<Vec<String>> = frames
+        .iter()
+        .map(|ds| {
+            ds.get_column_names()
+                .into_iter()
+                .map(String::from)
+                .collect()
+        })
+        .collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for name in names.iter().flatten() {
+        *counts.entry(name.as_str()).or_default() += 1;
+    }
+
+    // 重名的列改成 `别名.列名`，其余的列保持原名，同时可以用 `别名.列名` 访问
+    let qualify = |table: usize, name: &str| match counts[name] {
+        1 => name.to_owned(),
+        _ => format!("{}.{}", qualifiers[table], name),
+    };
+    let resolve = |name: &str| -> Result<(usize, String)> {
+        for (i, q) in qualifiers.iter().enumerate() {
+            let column = name.strip_prefix(q).and_then(|c| c.strip_prefix('.'));
+            if let Some(c) = column.filter(|c| names[i].iter().any(|n| n == c)) {
+                return Ok((i, qualify(i, c)));
+            }
+        }
+        let tables: Vec<usize> = (0..names.len())
+            .filter(|&i| names[i].iter().any(|n| n == name))
+            .collect();
+        match tables.as_slice() {
+            [i] => Ok((*i, name.to_owned())),
+            [] => Err(anyhow!("column {} not found", name)),
+            _ => Err(anyhow!("column {} is ambiguous", name)),
+        }
+    };
+
+    let mut columns = Vec::new();
+    let mut aliases = Vec::new();
+    let mut lazy = Vec::with_capacity(frames.len());
+    for (i, mut ds) in frames.into_iter().enumerate() {
+        let qualified: Vec<String> = names[i].iter().map(|n| qualify(i, n)).collect();
+        for n in names[i].iter().filter(|n| counts[n.as_str()] == 1) {
+            aliases.push(col(n).alias(&format!("{}.{}", qualifiers[i], n)));
+        }
+        ds.set_column_names(&qualified)?;
+        columns.extend(qualified);
+        lazy.push(ds.0.lazy());
+    }
+
+    let mut lazy = lazy.into_iter();
+    let mut joined = lazy.next().unwrap();
+    for (i, (join, right)) in joins.iter().zip(lazy).enumerate() {
+        let table = i + 1;
+        let (l, r) = (resolve(&join.on.0)?, resolve(&join.on.1)?);
+        let (left_on, right_on) = match (l, r) {
+            (l, r) if l.0 < table && r.0 == table => (l.1, r.1),
+            (l, r) if r.0 < table && l.0 == table => (r.1, l.1),
+            _ => {
+                return Err(anyhow!(
+                    "join condition {} = {} must compare {} with a previous table",
+                    join.on.0,
+                    join.on.1,
+                    join.source
+                ))
+            }
+        };
+
+        // 两边的连接列各复制一份出来做 key，这样原来的列都会保留下来
+        let key = format!("__join_key_{}", table);
+        let left = joined.with_column(col(&left_on).alias(&key));
+        let right = right.with_column(col(&right_on).alias(&key));
+        let on = || vec![col(&key)];
+        joined = match join.kind {
+            JoinKind::Inner => left.join(right, on(), on(), JoinType::Inner),
+            JoinKind::Left => left.join(right, on(), on(), JoinType::Left),
+            // polars 没有 right join，交换两边做 left join
+            JoinKind::Right => right.join(left, on(), on(), JoinType::Left),
+        };
+    }
+
+    Ok((joined.with_columns(aliases), Some(columns)))
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::env;
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[tokio::test]
+    async fn query_json_file_works() {
+        let path = env::temp_dir().join("queryer_query_json_file_works.json");
+        std::fs::write(
+            &path,
+            r#"[{"a": 1, "b": 10}, {"a": 2, "b": 20}, {"a": 3, "b": 30}]"#,
+        )
+        .unwrap();
+
+        let sql = format!("SELECT a FROM file://{} WHERE b > 15", path.display());
+        let ds = query(sql).await.unwrap();
+        assert_eq!(ds.shape(), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn query_join_works() {
+        let people = env::temp_dir().join("queryer_query_join_works_people.csv");
+        let orders = env::temp_dir().join("queryer_query_join_works_orders.csv");
+        std::fs::write(&people, "id,name\n1,alice\n2,bob\n3,carol\n").unwrap();
+        std::fs::write(&orders, "id,person_id,amount\n1,1,10\n2,1,20\n3,3,30\n").unwrap();
+        let (people, orders) = (people.display(), orders.display());
+
+        let sql = format!(
+            "SELECT name, o.id, amount FROM file://{} p \
+             INNER JOIN file://{} o ON p.id = o.person_id",
+            people, orders
+        );
+        let ds = query(sql).await.unwrap();
+        assert_eq!(ds.get_column_names(), vec!["name", "o.id", "amount"]);
+        assert_eq!(ds.height(), 3);
+
+        let sql = format!(
+            "SELECT * FROM file://{} p LEFT JOIN file://{} o ON o.person_id = p.id",
+            people, orders
+        );
+        let ds = query(sql).await.unwrap();
+        assert_eq!(
+            ds.get_column_names(),
+            vec!["p.id", "name", "o.id", "person_id", "amount"]
+        );
+        assert_eq!(ds.height(), 4);
+
+        let sql = format!(
+            "SELECT p.name, amount FROM file://{} o RIGHT JOIN file://{} p ON person_id = p.id",
+            orders, people
+        );
+        let ds = query(sql).await.unwrap();
+        assert_eq!(ds.height(), 4);
+        assert_eq!(ds.column("amount").unwrap().null_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn query_expressions_works() {
+        let path = env::temp_dir().join("queryer_query_expressions_works.csv");
+        std::fs::write(&path, "name,v\nAlice,1.25\nbob,-2.5\ncarol,3.75\n").unwrap();
+
+        let sql = format!(
+            "SELECT UPPER(name) n, LENGTH(name) len, ROUND(ABS(v) * 2, 1) r, \
+             CASE WHEN v > 0 THEN 'pos' ELSE 'neg' END sign FROM file://{} \
+             WHERE name LIKE '%o%' OR name IN ('Alice')",
+            path.display()
+        );
+        let ds = query(sql).await.unwrap();
+        assert_eq!(ds.get_column_names(), vec!["n", "len", "r", "sign"]);
+        assert_eq!(ds.height(), 3);
+        assert_eq!(ds.column("n").unwrap().utf8().unwrap().get(1), Some("BOB"));
+        assert_eq!(ds.column("r").unwrap().f64().unwrap().get(1), Some(5.0));
+        assert_eq!(
+            ds.column("sign").unwrap().utf8().unwrap().get(1),
+            Some("neg")
+        );
+
+        let sql = format!(
+            "SELECT name FROM file://{} WHERE v BETWEEN 0 AND 2 AND name NOT LIKE 'b%'",
+            path.display()
+        );
+        let ds = query(sql).await.unwrap();
+        assert_eq!(ds.height(), 1);
+    }
+
+    #[tokio::test]
+    async fn output_formats_work() {
+        let path = env::temp_dir().join("queryer_output_formats_work.csv");
+        std::fs::write(&path, "name,v\na|b,1\nlonger,22\n").unwrap();
+
+        let ds = query(format!("SELECT * FROM file://{}", path.display()))
+            .await
+            .unwrap();
+        assert_eq!(
+            ds.to_table(),
+            "name   | v\n-------+---\na|b    | 1\nlonger | 22\n"
+        );
+        assert_eq!(
+            ds.to_markdown(),
+            "| name | v |\n| --- | --- |\n| a\\|b | 1 |\n| longer | 22 |\n"
+        );
+        assert_eq!(
+            ds.to_json().unwrap(),
+            r#"[{"name":"a|b","v":1},{"name":"longer","v":22}]"#
+        );
+
+        let ds = schema(format!("file://{}", path.display())).await.unwrap();
+        assert_eq!(ds.to_csv().unwrap(), "column,type\nname,str\nv,i64\n");
+    }
+
+    #[tokio::test]
+    async fn explain_does_not_fetch_data() {
+        let sql = "EXPLAIN SELECT name, SUM(v) total FROM http://127.0.0.1:1/data.csv d \
+                   JOIN file:///not/exist.csv e ON d.id = e.id \
+                   WHERE v > 1 GROUP BY name ORDER BY total DESC LIMIT 3";
+        let ds = query(sql).await.unwrap();
+        assert_eq!(ds.get_column_names(), vec!["plan"]);
+
+        let lines: Vec<&str> = ds
+            .column("plan")
+            .unwrap()
+            .utf8()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        assert_eq!(lines[0], "source: http://127.0.0.1:1/data.csv");
+        assert_eq!(lines[9], "offset: None");
+        assert_eq!(lines[10], "limit: Some(3)");
+        assert_eq!(lines[11], "unoptimized plan:");
+        assert!(lines.contains(&"optimized plan:"));
+        assert!(lines.len() > 13);
+    }
+
+    #[tokio::test]
+    async fn explain_leaves_out_aliases() {
+        let sql = "SELECT name, SUM(v) total, AVG(w) avg_w FROM http://127.0.0.1:1/data.csv \
+                   GROUP BY name HAVING total > 1 ORDER BY avg_w DESC";
+        let statement = parse(sql).unwrap();
+        let frames = placeholders(&(&statement).try_into().unwrap()).unwrap();
+        assert_eq!(frames[0].get_column_names(), vec!["name", "v", "w"]);
+        assert!(query(format!("EXPLAIN {}", sql)).await.is_ok());
+
+        // 和别名同名的列在投影里用到了，还是数据源里的列
+        let statement = parse("SELECT SUM(v) v FROM file:///a.csv ORDER BY v").unwrap();
+        let frames = placeholders(&(&statement).try_into().unwrap()).unwrap();
+        assert_eq!(frames[0].get_column_names(), vec!["v"]);
+    }
+
+    #[tokio::test]
+    async fn query_group_by_works() {
+        let path = env::temp_dir().join("queryer_query_group_by_works.csv");
+        std::fs::write(&path, "k,v\na,1\na,2\nb,3\nc,4\nc,5\nc,6\n").unwrap();
+
+        let sql = format!(
+            "SELECT k, COUNT(*) n, SUM(v) total FROM file://{} \
+             GROUP BY k HAVING n > 1 ORDER BY total DESC",
+            path.display()
+        );
+        let ds = query(sql).await.unwrap();
+        assert_eq!(ds.shape(), (2, 3));
+        assert_eq!(ds.get_column_names(), vec!["k", "n", "total"]);
+        assert_eq!(ds.column("k").unwrap().utf8().unwrap().get(0), Some("c"));
+    }
+
+    #[tokio::test]
+    async fn count_skips_nulls() {
+        let path = env::temp_dir().join("queryer_count_skips_nulls.csv");
+        std::fs::write(&path, "k,v\na,1\na,\na,1\nb,\n").unwrap();
+
+        let sql = format!(
+            "SELECT k, COUNT(*) n, COUNT(v) c, COUNT(DISTINCT v) d FROM file://{} \
+             GROUP BY k ORDER BY k",
+            path.display()
+        );
+        let ds = query(sql).await.unwrap();
+        assert_eq!(ds.to_csv().unwrap(), "k,n,c,d\na,3,2,1\nb,1,0,0\n");
+
+        let sql = format!(
+            "SELECT COUNT(v) c, COUNT(DISTINCT v) d FROM file://{}",
+            path.display()
+        );
+        let ds = query(sql).await.unwrap();
+        assert_eq!(ds.to_csv().unwrap(), "c,d\n2,1\n");
+    }
+
+    #[tokio::test]
+    async fn write_back_works() {
+        let path = env::temp_dir().join("queryer_write_back_works.csv");
+        std::fs::write(&path, "k,v\na,1\nb,2\nc,3\nd,4\n").unwrap();
+
+        let rows = |ds: DataSet| ds.column("rows").unwrap().u64().unwrap().get(0);
+        let sql = format!(
+            "CREATE TABLE write_back AS SELECT k, v FROM file://{} WHERE v > 2",
+            path.display()
+        );
+        assert_eq!(rows(query(sql).await.unwrap()), Some(2));
+        let sql = format!(
+            "INSERT INTO write_back SELECT k, v FROM file://{} WHERE v = 1",
+            path.display()
+        );
+        assert_eq!(rows(query(sql).await.unwrap()), Some(1));
+        assert!(tables().contains(&"write_back".to_owned()));
+
+        for ext in ["csv", "json", "ndjson", "parquet"] {
+            let out = env::temp_dir().join(format!("queryer_write_back_works.{}", ext));
+            let sql = format!(
+                "COPY (SELECT k, v FROM Write_Back ORDER BY v) TO 'file://{}'",
+                out.display()
+            );
+            assert_eq!(rows(query(sql).await.unwrap()), Some(3));
+
+            let sql = format!("SELECT k FROM file://{} WHERE v >= 3", out.display());
+            let ds = query(sql).await.unwrap();
+            assert_eq!(ds.height(), 2, "{}", ext);
+        }
+
+        let sql = "COPY (SELECT k FROM write_back) TO 'file:///tmp/out.txt'";
+        assert!(query(sql).await.is_err());
+        let sql = "INSERT INTO not_exist SELECT k FROM write_back";
+        assert!(query(sql).await.is_err());
+        assert!(drop_table("write_back").is_some());
+    }
 }
@@ -1,8 +1,9 @@
+use crate::fetcher::RawData;
 use crate::DataSet;
 
 use anyhow::Result;
 use polars::prelude::*;
-use std::io::Cursor;
+use std::io::{Cursor, Seek, SeekFrom, Write};
 
 pub trait Load {
     type Error;
@@ -13,21 +14,101 @@ pub trait Load {
 #[non_exhaustive]
 pub enum Loader {
     Csv(CsvLoader),
+    Json(JsonLoader),
+    Ndjson(NdjsonLoader),
+    Parquet(ParquetLoader),
 }
 
 #[derive(Default, Debug)]
-pub struct CsvLoader(pub(crate) String);
+pub struct CsvLoader(pub(crate) Vec<u8>);
+
+/// 顶层是数组的 JSON 文档，每个元素是一行
+#[derive(Default, Debug)]
+pub struct JsonLoader(pub(crate) Vec<u8>);
+
+/// 每行一个 JSON 对象
+#[derive(Default, Debug)]
+pub struct NdjsonLoader(pub(crate) Vec<u8>);
+
+#[derive(Default, Debug)]
+pub struct ParquetLoader(pub(crate) Vec<u8>);
 
 impl Loader {
     pub fn load(self) -> Result<DataSet> {
         match self {
             Loader::Csv(csv) => csv.load(),
+            Loader::Json(json) => json.load(),
+            Loader::Ndjson(ndjson) => ndjson.load(),
+            Loader::Parquet(parquet) => parquet.load(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Format {
+    Csv,
+    Json,
+    Ndjson,
+    Parquet,
+}
+
+/// 依次根据扩展名、content type 和数据开头的几个字节判断数据格式，
+/// 都判断不出来就当作 csv
+pub fn detect_content(data: RawData) -> Loader {
+    let format = from_extension(&data.source)
+        .or_else(|| data.content_type.as_deref().and_then(from_content_type))
+        .unwrap_or_else(|| sniff(&data.bytes));
+
+    let bytes = data.bytes;
+    match format {
+        Format::Csv => Loader::Csv(CsvLoader(bytes)),
+        Format::Json => Loader::Json(JsonLoader(bytes)),
+        Format::Ndjson => Loader::Ndjson(NdjsonLoader(bytes)),
+        Format::Parquet => Loader::Parquet(ParquetLoader(bytes)),
+    }
+}
+
+pub(crate) fn from_extension(source: &str) -> Option<Format> {
+    // 去掉 url 里的 query 和 fragment
+    let path = source.split(|c| c == '?' || c == '#').next()?;
+    let name = path.rsplit('/').next()?;
+    let (_, ext) = name.rsplit_once('.')?;
+    match ext.to_ascii_lowercase().as_str() {
+        "csv" => Some(Format::Csv),
+        "json" => Some(Format::Json),
+        "ndjson" | "jsonl" => Some(Format::Ndjson),
+        "parquet" => Some(Format::Parquet),
+        _ => None,
+    }
+}
+
+fn from_content_type(content_type: &str) -> Option<Format> {
+    // 去掉 `; charset=utf-8` 之类的参数
+    let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+    match mime.as_str() {
+        "text/csv" | "application/csv" => Some(Format::Csv),
+        "application/json" | "text/json" => Some(Format::Json),
+        "application/x-ndjson"
+        | "application/ndjson"
+        | "application/jsonlines"
+        | "application/x-jsonlines" => Some(Format::Ndjson),
+        "application/vnd.apache.parquet" | "application/x-parquet" | "application/parquet" => {
+            Some(Format::Parquet)
         }
+        _ => None,
     }
 }
 
-pub fn detect_content(data: String) -> Loader {
-    Loader::Csv(CsvLoader(data))
+fn sniff(bytes: &[u8]) -> Format {
+    // parquet 文件以 magic number `PAR1` 开头
+    if bytes.starts_with(b"PAR1") {
+        return Format::Parquet;
+    }
+    match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'[') => Format::Json,
+        Some(b'{') => Format::Ndjson,
+        _ => Format::Csv,
+    }
 }
 
 impl Load for CsvLoader {
@@ -39,3 +120,112 @@ impl Load for CsvLoader {
         Ok(DataSet(df))
     }
 }
+
+impl Load for JsonLoader {
+    type Error = anyhow::Error;
+    fn load(self) -> Result<DataSet, Self::Error> {
+        // polars 的 JsonReader 只认一行一个对象，先把数组展开成 ndjson
+        let rows: Vec<serde_json::Value> = serde_json::from_slice(&self.0)?;
+        let mut ndjson = Vec::with_capacity(self.0.len());
+        for row in rows {
+            serde_json::to_writer(&mut ndjson, &row)?;
+            ndjson.push(b'\n');
+        }
+        NdjsonLoader(ndjson).load()
+    }
+}
+
+impl Load for NdjsonLoader {
+    type Error = anyhow::Error;
+    fn load(self) -> Result<DataSet, Self::Error> {
+        let df = JsonReader::new(Cursor::new(self.0))
+            .infer_schema(Some(16))
+            .finish()?;
+        Ok(DataSet(df))
+    }
+}
+
+impl Load for ParquetLoader {
+    type Error = anyhow::Error;
+    fn load(self) -> Result<DataSet, Self::Error> {
+        // ParquetReader 要能随机读取的文件，内存里的数据先写到临时文件
+        let mut file = tempfile::tempfile()?;
+        file.write_all(&self.0)?;
+        file.seek(SeekFrom::Start(0))?;
+        let df = ParquetReader::new(file).finish()?;
+        Ok(DataSet(df))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(source: &str, content_type: Option<&str>, bytes: &[u8]) -> RawData {
+        RawData {
+            source: source.to_string(),
+            content_type: content_type.map(|v| v.to_string()),
+            bytes: bytes.to_vec(),
+            validator: Default::default(),
+        }
+    }
+
+    #[test]
+    fn detect_content_works() {
+        assert!(matches!(
+            detect_content(raw("file://data.json", None, b"a,b\n1,2\n")),
+            Loader::Json(_)
+        ));
+        assert!(matches!(
+            detect_content(raw("https://abc.xyz/data.JSONL?token=1", None, b"")),
+            Loader::Ndjson(_)
+        ));
+        assert!(matches!(
+            detect_content(raw(
+                "https://abc.xyz/data",
+                Some("application/vnd.apache.parquet"),
+                b""
+            )),
+            Loader::Parquet(_)
+        ));
+        assert!(matches!(
+            detect_content(raw(
+                "https://abc.xyz/data",
+                Some("application/json; charset=utf-8"),
+                b""
+            )),
+            Loader::Json(_)
+        ));
+        assert!(matches!(
+            detect_content(raw(
+                "https://abc.xyz/data",
+                Some("text/plain"),
+                b"  [{\"a\": 1}]"
+            )),
+            Loader::Json(_)
+        ));
+        assert!(matches!(
+            detect_content(raw("file://data", None, b"{\"a\": 1}\n{\"a\": 2}\n")),
+            Loader::Ndjson(_)
+        ));
+        assert!(matches!(
+            detect_content(raw("file://data", None, b"PAR1")),
+            Loader::Parquet(_)
+        ));
+        assert!(matches!(
+            detect_content(raw("file://data", None, b"a,b\n")),
+            Loader::Csv(_)
+        ));
+    }
+
+    #[test]
+    fn json_and_ndjson_load_into_the_same_frame() {
+        let json = br#"[{"a": 1, "b": "x"}, {"a": 2, "b": "y"}]"#;
+        let ndjson = b"{\"a\": 1, \"b\": \"x\"}\n{\"a\": 2, \"b\": \"y\"}\n";
+
+        let from_json = JsonLoader(json.to_vec()).load().unwrap();
+        let from_ndjson = NdjsonLoader(ndjson.to_vec()).load().unwrap();
+        assert_eq!(from_json.shape(), (2, 2));
+        assert!(from_json.frame_equal(&from_ndjson));
+    }
+}
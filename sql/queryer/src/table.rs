@@ -0,0 +1,33 @@
+use crate::DataSet;
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+// CREATE TABLE ... AS SELECT 创建的表，在 FROM 里直接用名字访问
+static TABLES: Lazy<RwLock<HashMap<String, DataSet>>> = Lazy::new(Default::default);
+
+/// 注册一张命名的表，已经存在的同名表会被替换。表名不区分大小写
+pub fn register_table(name: &str, ds: DataSet) -> Option<DataSet> {
+    TABLES.write().unwrap().insert(name.to_lowercase(), ds)
+}
+
+/// 删除一张命名的表
+pub fn drop_table(name: &str) -> Option<DataSet> {
+    TABLES.write().unwrap().remove(&name.to_lowercase())
+}
+
+/// 所有命名的表的名字
+pub fn tables() -> Vec<String> {
+    let mut names: Vec<String> = TABLES.read().unwrap().keys().cloned().collect();
+    names.sort();
+    names
+}
+
+// DataFrame 里的列都是 Arc，clone 的代价很小
+pub(crate) fn table(name: &str) -> Option<DataSet> {
+    let tables = TABLES.read().unwrap();
+    tables
+        .get(&name.to_lowercase())
+        .map(|ds| DataSet(ds.0.clone()))
+}
@@ -0,0 +1,69 @@
+use hyper::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// 测试用的本地 http 服务返回的内容和收到的请求
+#[derive(Default)]
+pub(crate) struct State {
+    // (etag, body)
+    pub(crate) content: Mutex<(String, String)>,
+    // 收到的请求的路径
+    pub(crate) paths: Mutex<Vec<String>>,
+    pub(crate) requests: AtomicUsize,
+    pub(crate) not_modified: AtomicUsize,
+}
+
+impl State {
+    pub(crate) fn new(etag: &str, body: &str) -> Arc<Self> {
+        let state = Self::default();
+        *state.content.lock().unwrap() = (etag.into(), body.into());
+        Arc::new(state)
+    }
+}
+
+/// 启动一个本地的 http 服务，If-None-Match 和当前的 etag 一致时返回 304。
+/// 只看路径，所以也可以当作不校验签名的 S3 替身
+pub(crate) async fn serve(state: Arc<State>) -> SocketAddr {
+    let make_svc = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = state.clone();
+                async move {
+                    state.requests.fetch_add(1, Ordering::SeqCst);
+                    state
+                        .paths
+                        .lock()
+                        .unwrap()
+                        .push(req.uri().path().to_owned());
+                    let (etag, body) = state.content.lock().unwrap().clone();
+                    let matched = req
+                        .headers()
+                        .get(IF_NONE_MATCH)
+                        .and_then(|v| v.to_str().ok())
+                        == Some(etag.as_str());
+                    let resp = if matched {
+                        state.not_modified.fetch_add(1, Ordering::SeqCst);
+                        Response::builder()
+                            .status(StatusCode::NOT_MODIFIED)
+                            .body(Body::empty())
+                    } else {
+                        Response::builder()
+                            .header(ETAG, etag)
+                            .header(CONTENT_TYPE, "text/csv")
+                            .body(Body::from(body))
+                    };
+                    Ok::<_, Infallible>(resp.unwrap())
+                }
+            }))
+        }
+    });
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
@@ -0,0 +1,138 @@
+use crate::loader::{from_extension, Format};
+use crate::{DataSet, TyrDialect};
+
+use anyhow::{anyhow, Result};
+use polars::prelude::*;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::fs::File;
+use tokio::fs;
+
+/// 把 `COPY (SELECT ...) TO 'file://...'` 拆成里面的查询和输出的位置，不是 COPY 时返回 None。
+/// sqlparser 只支持 `COPY table FROM STDIN`，所以这里自己处理
+pub(crate) fn parse_copy(sql: &str) -> Result<Option<(String, String)>> {
+    let invalid = || anyhow!("invalid COPY statement, expect COPY (SELECT ...) TO 'file://path'");
+    let sql = sql.trim_start();
+    let rest = match sql.get(..4) {
+        Some(keyword) if keyword.eq_ignore_ascii_case("copy") => &sql[4..],
+        _ => return Ok(None),
+    };
+    if !rest.starts_with(|c: char| c == '(' || c.is_whitespace()) {
+        return Ok(None);
+    }
+
+    // 查询直接从原来的 sql 里截出来，token 拼回去会丢掉字符串里 `''` 的转义
+    let body = rest.trim_start().strip_prefix('(').ok_or_else(invalid)?;
+    let end = closing_paren(body).ok_or_else(invalid)?;
+    let (query, tail) = (body[..end].trim(), &body[end + 1..]);
+
+    let tokens = Tokenizer::new(&TyrDialect::default(), tail)
+        .tokenize()
+        .map_err(|e| anyhow!("{:?}", e))?;
+    let mut iter = tokens.iter().filter(|t| !matches!(t, Token::Whitespace(_)));
+    match iter.next() {
+        Some(Token::Word(w)) if w.keyword == Keyword::TO => {}
+        _ => return Err(invalid()),
+    }
+    let target = match iter.next() {
+        Some(Token::SingleQuotedString(s)) => s.clone(),
+        _ => return Err(invalid()),
+    };
+    if iter.any(|t| *t != Token::SemiColon) {
+        return Err(invalid());
+    }
+    Ok(Some((query.to_owned(), target)))
+}
+
+// 找到和开头的左括号配对的右括号，字符串和带引号的标识符里的括号不算
+fn closing_paren(body: &str) -> Option<usize> {
+    let mut depth = 1;
+    let mut quote = None;
+    for (i, c) in body.char_indices() {
+        match (quote, c) {
+            // `''` 相当于先结束再开始一个字符串
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 按扩展名把数据写成 csv / json / ndjson / parquet 文件，返回写入的行数
+pub(crate) async fn write(ds: &DataSet, target: &str) -> Result<usize> {
+    let path = target
+        .strip_prefix("file://")
+        .ok_or_else(|| anyhow!("unsupported target {}, expect file://path", target))?;
+    let format = from_extension(path).ok_or_else(|| {
+        anyhow!(
+            "unknown format of {}, expect .csv, .json, .ndjson or .parquet",
+            target
+        )
+    })?;
+
+    match format {
+        Format::Csv => fs::write(path, ds.to_csv()?).await?,
+        Format::Json => fs::write(path, ds.to_json()?).await?,
+        Format::Ndjson => fs::write(path, ds.to_ndjson()?).await?,
+        // parquet writer 需要 File，写文件是阻塞的，放到阻塞线程里执行
+        Format::Parquet => {
+            let (path, frame) = (path.to_owned(), ds.0.clone());
+            tokio::task::spawn_blocking(move || -> Result<()> {
+                ParquetWriter::new(File::create(path)?).finish(&frame)?;
+                Ok(())
+            })
+            .await??
+        }
+    }
+    Ok(ds.height())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_copy_works() {
+        let (query, target) = parse_copy(
+            "copy (SELECT a FROM file:///tmp/a.csv WHERE b IN (1, 2)) TO 'file:///tmp/out.csv';",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(query, "SELECT a FROM file:///tmp/a.csv WHERE b IN (1, 2)");
+        assert_eq!(target, "file:///tmp/out.csv");
+
+        let (query, target) = parse_copy(
+            "COPY (SELECT a FROM file:///tmp/a.csv WHERE b = 'it''s (x)') TO 'file:///tmp/it''s.csv'",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            query,
+            "SELECT a FROM file:///tmp/a.csv WHERE b = 'it''s (x)'"
+        );
+        assert_eq!(target, "file:///tmp/it's.csv");
+
+        assert!(parse_copy("SELECT a FROM file:///tmp/a.csv")
+            .unwrap()
+            .is_none());
+        assert!(
+            parse_copy("COPY (SELECT a FROM file:///tmp/a.csv TO 'file:///tmp/out.csv'").is_err()
+        );
+        assert!(
+            parse_copy("COPY (SELECT a FROM file:///tmp/a.csv) TO file:///tmp/out.csv").is_err()
+        );
+        assert!(
+            parse_copy("COPY (SELECT a FROM file:///tmp/a.csv) TO 'file:///tmp/out.csv' x")
+                .is_err()
+        );
+    }
+}
@@ -1,5 +1,4 @@
-use crate::pb::Spec;
-use image::ImageOutputFormat;
+use crate::pb::{format::ImageFormat, Spec};
 
 mod photon;
 pub use photon::Photon;
@@ -9,7 +8,7 @@ pub trait Engine {
     // 对engine按照specs进行一系列有序处理
     fn apply(&mut self, specs: &[Spec]);
     // 从engine中生成目标图片，注意这里用是self,而非self的应用
-    fn generate(self, format: ImageOutputFormat) -> Vec<u8>;
+    fn generate(self, format: ImageFormat) -> Vec<u8>;
 }
 
 // SpecTrasnform :未来添加更多的spec , 只需要实现它即可
@@ -1,8 +1,8 @@
 use super::{Engine, SpecTransform};
-use crate::pb::*;
+use crate::pb::{format::ImageFormat, *};
 use anyhow::Result;
 use bytes::Bytes;
-use image::{DynamicImage, ImageBuffer, ImageOutputFormat};
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat, Rgba};
 use lazy_static::lazy_static;
 use photon_rs::{
     effects, filters, multiple, native::open_image_from_bytes, transform, PhotonImage,
@@ -19,6 +19,9 @@ lazy_static! {
   };
 }
 
+// jpeg 和 webp 的压缩质量
+const QUALITY: u8 = 85;
+
 // 我们目前支持 Photon engine
 pub struct Photon(PhotonImage);
 
@@ -41,13 +44,26 @@ impl Engine for Photon {
                 Some(spec::Data::Flipv(ref v)) => self.transform(v),
                 Some(spec::Data::Resize(ref v)) => self.transform(v),
                 Some(spec::Data::Watermark(ref v)) => self.transform(v),
+                // 输出格式在 generate 的时候才用到
+                Some(spec::Data::Format(_)) => {}
                 None => {}
             }
         }
     }
 
-    fn generate(self, format: ImageOutputFormat) -> Vec<u8> {
-        todo!()
+    fn generate(self, format: ImageFormat) -> Vec<u8> {
+        let (width, height) = (self.0.get_width(), self.0.get_height());
+        let pixels = self.0.get_raw_pixels();
+        match format {
+            // image 不支持 webp 编码，直接用 libwebp
+            ImageFormat::Webp => webp::Encoder::from_rgba(&pixels, width, height)
+                .encode(QUALITY as f32)
+                .to_vec(),
+            ImageFormat::Png => image_to_buf(pixels, width, height, ImageOutputFormat::Png),
+            ImageFormat::Jpeg | ImageFormat::Unspecified => {
+                image_to_buf(pixels, width, height, ImageOutputFormat::Jpeg(QUALITY))
+            }
+        }
     }
 }
 
@@ -108,3 +124,12 @@ impl SpecTransform<&Watermark> for Photon {
         multiple::watermark(&mut self.0, &WATERMARK, op.x, op.y);
     }
 }
+
+// photon 的图片是 RGBA 格式，交给 image 编码
+fn image_to_buf(pixels: Vec<u8>, width: u32, height: u32, format: ImageOutputFormat) -> Vec<u8> {
+    let img_buf = ImageBuffer::<Rgba<u8>, Vec<u8>>::from_vec(width, height, pixels).unwrap();
+    let img = DynamicImage::ImageRgba8(img_buf);
+    let mut buffer = Vec::with_capacity(32768);
+    img.write_to(&mut buffer, format).unwrap();
+    buffer
+}
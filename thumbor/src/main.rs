@@ -2,7 +2,7 @@ use anyhow::Result;
 use axum::{
     extract::{Extension, Path},
     handler::get,
-    http::{HeaderMap, HeaderValue, StatusCode},
+    http::{header::ACCEPT, HeaderMap, HeaderValue, StatusCode},
     AddExtensionLayer, Router,
 };
 use bytes::Bytes;
@@ -19,8 +19,9 @@ use tokio::sync::Mutex;
 use tower::ServiceBuilder;
 use tracing::{info, instrument};
 mod pb;
-use pb::*;
+use pb::{format::ImageFormat, *};
 mod engine;
+use engine::{Engine, Photon};
 
 #[derive(Deserialize)]
 struct Params {
@@ -54,6 +55,7 @@ async fn main() {
 async fn generate(
     Path(Params { spec, url }): Path<Params>,
     Extension(cache): Extension<Cache>,
+    headers: HeaderMap,
 ) -> Result<(HeaderMap, Vec<u8>), StatusCode> {
     let spec: ImageSpec = spec
         .as_str()
@@ -64,10 +66,60 @@ async fn generate(
         .await
         .map_err(|_| StatusCode::BAD_REQUEST)?;
 
-    // TODO:图片处理
+    // spec 里没有指定输出格式时，根据 Accept 头选择
+    let (format, negotiated) = match spec.format() {
+        Some(format) => (format, false),
+        None => (accepted_format(&headers), true),
+    };
+
+    // 解码、处理和编码都很吃 CPU，放到 blocking 线程池里跑，不占用 async 的 worker
+    let image = tokio::task::spawn_blocking(move || {
+        let mut engine: Photon = data.try_into()?;
+        engine.apply(&spec.specs);
+        Ok::<_, anyhow::Error>(engine.generate(format))
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|_| StatusCode::BAD_REQUEST)?;
+    info!("Finished processing: image size {}", image.len());
+
     let mut headers = HeaderMap::new();
-    headers.insert("content-type", HeaderValue::from_static("image/jpeg"));
-    Ok((headers, data.to_vec()))
+    headers.insert(
+        "content-type",
+        HeaderValue::from_static(format.content_type()),
+    );
+    // 同一个 url 会因为 Accept 不同返回不同的格式
+    if negotiated {
+        headers.insert("vary", HeaderValue::from_static("accept"));
+    }
+    Ok((headers, image))
+}
+
+// 按 Accept 里的 q 值选择支持的格式，都不支持时用 jpeg
+fn accepted_format(headers: &HeaderMap) -> ImageFormat {
+    let accept = headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let mut best = (ImageFormat::Jpeg, 0f32);
+    for item in accept.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+        let format = match parts.next() {
+            Some("image/webp") => ImageFormat::Webp,
+            Some("image/png") => ImageFormat::Png,
+            Some("image/jpeg" | "image/*" | "*/*") => ImageFormat::Jpeg,
+            _ => continue,
+        };
+        let q = parts
+            .find_map(|p| p.strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        if q > best.1 {
+            best = (format, q);
+        }
+    }
+    best.0
 }
 
 #[instrument(level = "info", skip(cache))]
@@ -103,3 +155,96 @@ fn print_test_url(url: &str) {
     let test_image = percent_encode(url.as_bytes(), NON_ALPHANUMERIC).to_string();
     println!("test url:htpp://localhost:3000/image/{}/{}", s, test_image);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header::{CONTENT_TYPE, VARY};
+    use image::GenericImageView;
+    use std::net::SocketAddr;
+
+    // 本地提供图片的 http 服务，代替真实的图片网站
+    async fn serve_image() -> SocketAddr {
+        let app = Router::new().route(
+            "/logo.png",
+            get(|| async { include_bytes!("../rust-logo.png").to_vec() }),
+        );
+        let server =
+            axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    async fn request(
+        spec: String,
+        path: &str,
+        accept: Option<&'static str>,
+    ) -> Result<(HeaderMap, Vec<u8>), StatusCode> {
+        let url = format!("http://{}{}", serve_image().await, path);
+        let params = Params {
+            spec,
+            url: percent_encode(url.as_bytes(), NON_ALPHANUMERIC).to_string(),
+        };
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(ACCEPT, HeaderValue::from_static(accept));
+        }
+        let cache: Cache = Arc::new(Mutex::new(LruCache::new(8)));
+        generate(Path(params), Extension(cache), headers).await
+    }
+
+    fn encode(specs: Vec<Spec>) -> String {
+        (&ImageSpec::new(specs)).into()
+    }
+
+    #[tokio::test]
+    async fn generate_applies_specs_and_format() {
+        let spec = encode(vec![
+            Spec::new_resize(32, 16, resize::SampleFilter::Nearest),
+            Spec::new_format(ImageFormat::Png),
+        ]);
+        let (headers, body) = request(spec, "/logo.png", Some("image/webp"))
+            .await
+            .unwrap();
+        assert_eq!(headers[CONTENT_TYPE], "image/png");
+        assert!(headers.get(VARY).is_none());
+        let img = image::load_from_memory(&body).unwrap();
+        assert_eq!(img.dimensions(), (32, 16));
+    }
+
+    #[tokio::test]
+    async fn generate_negotiates_format_by_accept() {
+        let spec = encode(vec![Spec::new_filter(filter::Filter::Marine)]);
+        let accept = "image/avif,image/webp,image/*,*/*;q=0.8";
+        let (headers, body) = request(spec.clone(), "/logo.png", Some(accept))
+            .await
+            .unwrap();
+        assert_eq!(headers[CONTENT_TYPE], "image/webp");
+        assert_eq!(headers[VARY], "accept");
+        assert_eq!(&body[..4], b"RIFF");
+        assert_eq!(&body[8..12], b"WEBP");
+
+        let accept = "image/png;q=0.9, image/jpeg;q=0.5";
+        let (headers, body) = request(spec.clone(), "/logo.png", Some(accept))
+            .await
+            .unwrap();
+        assert_eq!(headers[CONTENT_TYPE], "image/png");
+        assert!(body.starts_with(b"\x89PNG"));
+
+        let (headers, body) = request(spec, "/logo.png", None).await.unwrap();
+        assert_eq!(headers[CONTENT_TYPE], "image/jpeg");
+        assert!(body.starts_with(&[0xff, 0xd8]));
+    }
+
+    #[tokio::test]
+    async fn generate_rejects_bad_requests() {
+        let result = request("not a spec".into(), "/logo.png", None).await;
+        assert_eq!(result.unwrap_err(), StatusCode::BAD_REQUEST);
+
+        // 不是图片的内容
+        let spec = encode(vec![Spec::new_watermark(20, 20)]);
+        let result = request(spec, "/not-found.png", None).await;
+        assert_eq!(result.unwrap_err(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -87,10 +87,27 @@ pub struct Watermark {
     #[prost(uint32, tag="2")]
     pub y: u32,
 }
+/// 输出的图片格式，不指定时根据请求的 Accept 头决定
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Format {
+    #[prost(enumeration="format::ImageFormat", tag="1")]
+    pub format: i32,
+}
+/// Nested message and enum types in `Format`.
+pub mod format {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ImageFormat {
+        Unspecified = 0,
+        Jpeg = 1,
+        Png = 2,
+        Webp = 3,
+    }
+}
 /// 一个 spec 可以包含上述的处理方式之一
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Spec {
-    #[prost(oneof="spec::Data", tags="1, 2, 3, 4, 5, 6, 7")]
+    #[prost(oneof="spec::Data", tags="1, 2, 3, 4, 5, 6, 7, 8")]
     pub data: ::core::option::Option<spec::Data>,
 }
 /// Nested message and enum types in `Spec`.
@@ -111,5 +128,7 @@ pub mod spec {
         Filter(super::Filter),
         #[prost(message, tag="7")]
         Watermark(super::Watermark),
+        #[prost(message, tag="8")]
+        Format(super::Format),
     }
 }
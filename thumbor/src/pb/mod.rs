@@ -9,6 +9,18 @@ impl ImageSpec {
     pub fn new(specs: Vec<Spec>) -> Self {
         Self { specs }
     }
+
+    // 输出格式以最后一个 Format spec 为准，没有指定时返回 None
+    pub fn format(&self) -> Option<format::ImageFormat> {
+        self.specs
+            .iter()
+            .filter_map(|spec| match spec.data {
+                Some(spec::Data::Format(ref v)) => format::ImageFormat::from_i32(v.format),
+                _ => None,
+            })
+            .filter(|f| *f != format::ImageFormat::Unspecified)
+            .last()
+    }
 }
 
 // 让ImageSpec可以生成一个字符串
@@ -39,6 +51,16 @@ impl filter::Filter {
     }
 }
 
+impl format::ImageFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            format::ImageFormat::Unspecified | format::ImageFormat::Jpeg => "image/jpeg",
+            format::ImageFormat::Png => "image/png",
+            format::ImageFormat::Webp => "image/webp",
+        }
+    }
+}
+
 impl From<resize::SampleFilter> for SamplingFilter {
     fn from(v: resize::SampleFilter) -> Self {
         match v {
@@ -89,6 +111,14 @@ impl Spec {
             data: Some(spec::Data::Watermark(Watermark { x, y })),
         }
     }
+
+    pub fn new_format(format: format::ImageFormat) -> Self {
+        Self {
+            data: Some(spec::Data::Format(Format {
+                format: format as i32,
+            })),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -106,4 +136,16 @@ mod tests {
         println!("{:?}", s);
         assert_eq!(image_spec, s.as_str().try_into().unwrap());
     }
+
+    #[test]
+    fn last_format_spec_wins() {
+        let image_spec = ImageSpec::new(vec![
+            Spec::new_format(format::ImageFormat::Png),
+            Spec::new_watermark(20, 20),
+            Spec::new_format(format::ImageFormat::Webp),
+            Spec::new_format(format::ImageFormat::Unspecified),
+        ]);
+        assert_eq!(image_spec.format(), Some(format::ImageFormat::Webp));
+        assert_eq!(ImageSpec::new(vec![]).format(), None);
+    }
 }